@@ -1,3 +1,5 @@
+#![allow(clippy::unwrap_used)]
+
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use std::fs;
 use std::path::PathBuf;
@@ -5,7 +7,10 @@ use std::sync::atomic::AtomicBool;
 use tempfile::TempDir;
 
 // Import the crate functions we want to benchmark
-use magento_static_deploy::copier::{copy_directory_with_overrides, copy_file};
+use magento_static_deploy::copier::{
+    copy_directory_with_overrides, copy_file, install_files, CopyBackend, CopyOptions, CopyStats,
+    LinkMode, INSTALL_BATCH_SIZE,
+};
 use magento_static_deploy::scanner::discover_themes;
 use magento_static_deploy::theme::Area;
 
@@ -55,6 +60,8 @@ fn bench_copy_file(c: &mut Criterion) {
 fn bench_copy_directory(c: &mut Criterion) {
     let mut group = c.benchmark_group("copy_directory");
     let shutdown = AtomicBool::new(false);
+    let stats = CopyStats::new();
+    let options = CopyOptions::default();
 
     for file_count in [100, 500, 1000].iter() {
         let temp = TempDir::new().unwrap();
@@ -73,7 +80,8 @@ fn bench_copy_directory(c: &mut Criterion) {
                         black_box(&src),
                         black_box(&dst),
                         &shutdown,
-                        false,
+                        &options,
+                        &stats,
                     )
                     .unwrap()
                 })
@@ -84,6 +92,50 @@ fn bench_copy_directory(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark planned-file installation with each available I/O backend
+/// (build with `--features io-uring` to include the io_uring backend)
+fn bench_install_backends(c: &mut Criterion) {
+    let mut group = c.benchmark_group("install_files");
+    let stats = CopyStats::new();
+    let file_count = 1000;
+
+    let temp = TempDir::new().unwrap();
+    let src = create_test_files(&temp, file_count);
+    let dst = temp.path().join("dst");
+    let files: Vec<_> = walkdir::WalkDir::new(&src)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| {
+            let relative = e.path().strip_prefix(&src).unwrap().to_path_buf();
+            (e.path().to_path_buf(), dst.join(relative), LinkMode::Copy)
+        })
+        .collect();
+
+    for backend in [CopyBackend::Std, CopyBackend::IoUring] {
+        if !backend.is_available() {
+            continue;
+        }
+        let options = CopyOptions {
+            backend,
+            ..CopyOptions::default()
+        };
+
+        group.throughput(Throughput::Elements(file_count as u64));
+        group.bench_function(BenchmarkId::from_parameter(backend.as_str()), |b| {
+            b.iter(|| {
+                for batch in files.chunks(INSTALL_BATCH_SIZE) {
+                    for result in install_files(black_box(batch), &options, &stats) {
+                        result.unwrap();
+                    }
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
 /// Benchmark theme discovery (requires mock Magento structure)
 fn bench_theme_discovery(c: &mut Criterion) {
     let temp = TempDir::new().unwrap();
@@ -117,6 +169,7 @@ criterion_group!(
     benches,
     bench_copy_file,
     bench_copy_directory,
+    bench_install_backends,
     bench_theme_discovery,
 );
 criterion_main!(benches);
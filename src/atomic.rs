@@ -0,0 +1,306 @@
+//! Atomic deploys through staging directories.
+//!
+//! With `--atomic`, a job deploys into a hidden staging sibling of its
+//! output (`.en_US.staging` next to `en_US`), seeded with hard links to the
+//! live files so incremental deploys stay cheap. Installing a file always
+//! replaces such a shared link instead of writing through it, so the live
+//! tree is never modified. When the job succeeds, the staging tree is
+//! swapped in with `renameat2(RENAME_EXCHANGE)` on Linux (two renames
+//! elsewhere) and the old tree is kept as `.en_US.previous` for
+//! `--rollback`. A failed or cancelled job only discards its staging tree.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::attrs::{self, OutputAttrs};
+use crate::copier::{replicate_directory, CopyOptions, CopyStats, LinkMode};
+use crate::error::DeployError;
+
+/// Staging directory for an output
+pub fn staging_path(output: &Path) -> PathBuf {
+    sibling(output, "staging")
+}
+
+/// Where the tree replaced by the last swap is kept
+pub fn previous_path(output: &Path) -> PathBuf {
+    sibling(output, "previous")
+}
+
+fn sibling(output: &Path, suffix: &str) -> PathBuf {
+    let name = output
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    output.with_file_name(format!(".{name}.{suffix}"))
+}
+
+/// Create a fresh staging directory for `output`, replacing any leftover
+/// from an interrupted deploy. With `seed`, the live files are hard linked
+/// into it first. `attrs` applies to the staging directory, which becomes
+/// the output directory.
+pub fn prepare_staging(
+    output: &Path,
+    seed: bool,
+    attrs: &OutputAttrs,
+    shutdown: &AtomicBool,
+) -> Result<PathBuf, DeployError> {
+    let staging = staging_path(output);
+    remove_tree(&staging)?;
+    attrs::create_dir_all(&staging, attrs)?;
+
+    if seed && output.is_dir() {
+        let options = CopyOptions {
+            include_dev: true,
+            link_mode: LinkMode::Hardlink,
+            attrs: *attrs,
+            ..CopyOptions::default()
+        };
+        if let Err(e) = replicate_directory(output, &staging, shutdown, &options, &CopyStats::new())
+        {
+            discard_staging(&staging);
+            return Err(e);
+        }
+    }
+
+    Ok(staging)
+}
+
+/// Remove a staging directory after a failed or cancelled job
+pub fn discard_staging(staging: &Path) {
+    if let Err(e) = remove_tree(staging) {
+        eprintln!("Warning: {e}");
+    }
+}
+
+/// Make `staging` the live `output`, keeping the replaced tree as the
+/// previous deploy
+pub fn swap_in(staging: &Path, output: &Path) -> Result<(), DeployError> {
+    let swap_error = |e| DeployError::SwapFailed {
+        staging: staging.to_path_buf(),
+        output: output.to_path_buf(),
+        source: e,
+    };
+
+    if fs::symlink_metadata(output).is_err() {
+        return fs::rename(staging, output).map_err(swap_error);
+    }
+
+    let previous = previous_path(output);
+    remove_tree(&previous)?;
+
+    if exchange(staging, output).map_err(swap_error)? {
+        // The old tree is now at the staging path
+        if let Err(e) = fs::rename(staging, &previous) {
+            eprintln!(
+                "Warning: failed to keep previous deploy as {}: {e}",
+                previous.display()
+            );
+        }
+        return Ok(());
+    }
+
+    // No exchange support: the output is missing between the two renames
+    fs::rename(output, &previous).map_err(swap_error)?;
+    fs::rename(staging, output).map_err(swap_error)
+}
+
+/// Swap the previous deploy of `output` back in. The replaced tree becomes
+/// the new previous deploy, so rolling back twice restores the latest one.
+/// Returns false if there is no previous deploy.
+pub fn rollback(output: &Path) -> Result<bool, DeployError> {
+    let previous = previous_path(output);
+    if !previous.is_dir() {
+        return Ok(false);
+    }
+
+    let swap_error = |e| DeployError::SwapFailed {
+        staging: previous.clone(),
+        output: output.to_path_buf(),
+        source: e,
+    };
+
+    if fs::symlink_metadata(output).is_err() {
+        fs::rename(&previous, output).map_err(swap_error)?;
+        return Ok(true);
+    }
+
+    if !exchange(&previous, output).map_err(swap_error)? {
+        let staging = staging_path(output);
+        remove_tree(&staging)?;
+        fs::rename(output, &staging).map_err(swap_error)?;
+        fs::rename(&previous, output).map_err(swap_error)?;
+        fs::rename(&staging, &previous).map_err(swap_error)?;
+    }
+    Ok(true)
+}
+
+/// Remove a file or directory tree; missing is fine
+fn remove_tree(path: &Path) -> Result<(), DeployError> {
+    let result = match fs::symlink_metadata(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => Err(e),
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+    };
+
+    result.map_err(|e| DeployError::PruneFailed {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Atomically exchange two paths with `renameat2(RENAME_EXCHANGE)`.
+/// Returns false when the kernel or filesystem doesn't support it.
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+
+    // SAFETY: both pointers are NUL-terminated strings owned by live
+    // `CString`s for the duration of the call.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let e = io::Error::last_os_error();
+    match e.raw_os_error() {
+        Some(libc::ENOSYS) | Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => Ok(false),
+        _ => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(_a: &Path, _b: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    // ==================== path tests ====================
+
+    #[test]
+    fn test_staging_and_previous_paths() {
+        let output = Path::new("/pub/static/frontend/Vendor/theme/en_US");
+        assert_eq!(
+            staging_path(output),
+            PathBuf::from("/pub/static/frontend/Vendor/theme/.en_US.staging")
+        );
+        assert_eq!(
+            previous_path(output),
+            PathBuf::from("/pub/static/frontend/Vendor/theme/.en_US.previous")
+        );
+    }
+
+    // ==================== prepare_staging tests ====================
+
+    #[test]
+    fn test_prepare_staging_seeds_live_files() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("en_US");
+        write(output.join("js/app.js"), "live");
+        write(staging_path(&output).join("stale.js"), "stale");
+
+        let shutdown = AtomicBool::new(false);
+        let staging = prepare_staging(&output, true, &OutputAttrs::default(), &shutdown).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(staging.join("js/app.js")).unwrap(),
+            "live"
+        );
+        assert!(!staging.join("stale.js").exists());
+
+        let empty = prepare_staging(&output, false, &OutputAttrs::default(), &shutdown).unwrap();
+        assert_eq!(fs::read_dir(empty).unwrap().count(), 0);
+    }
+
+    // ==================== swap_in tests ====================
+
+    #[test]
+    fn test_swap_in_first_deploy() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("en_US");
+        let shutdown = AtomicBool::new(false);
+        let staging = prepare_staging(&output, true, &OutputAttrs::default(), &shutdown).unwrap();
+        write(staging.join("app.js"), "new");
+
+        swap_in(&staging, &output).unwrap();
+
+        assert_eq!(fs::read_to_string(output.join("app.js")).unwrap(), "new");
+        assert!(!staging.exists());
+        assert!(!previous_path(&output).exists());
+    }
+
+    #[test]
+    fn test_swap_in_keeps_previous_and_rollback() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("en_US");
+        write(output.join("app.js"), "old");
+        write(previous_path(&output).join("app.js"), "older");
+
+        let shutdown = AtomicBool::new(false);
+        let staging = prepare_staging(&output, true, &OutputAttrs::default(), &shutdown).unwrap();
+        // Replace, don't write through the seeded hard link
+        fs::remove_file(staging.join("app.js")).unwrap();
+        fs::write(staging.join("app.js"), "new").unwrap();
+        assert_eq!(fs::read_to_string(output.join("app.js")).unwrap(), "old");
+
+        swap_in(&staging, &output).unwrap();
+        let previous = previous_path(&output);
+        assert_eq!(fs::read_to_string(output.join("app.js")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(previous.join("app.js")).unwrap(), "old");
+        assert!(!staging.exists());
+
+        assert!(rollback(&output).unwrap());
+        assert_eq!(fs::read_to_string(output.join("app.js")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(previous.join("app.js")).unwrap(), "new");
+
+        assert!(rollback(&output).unwrap());
+        assert_eq!(fs::read_to_string(output.join("app.js")).unwrap(), "new");
+    }
+
+    #[test]
+    fn test_rollback_without_previous() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("en_US");
+        write(output.join("app.js"), "live");
+
+        assert!(!rollback(&output).unwrap());
+        assert!(output.join("app.js").exists());
+    }
+
+    #[test]
+    fn test_discard_staging() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path().join("en_US");
+        let shutdown = AtomicBool::new(false);
+        let staging = prepare_staging(&output, false, &OutputAttrs::default(), &shutdown).unwrap();
+        write(staging.join("partial.js"), "partial");
+
+        discard_staging(&staging);
+        assert!(!staging.exists());
+        discard_staging(&staging);
+    }
+}
@@ -0,0 +1,324 @@
+//! Permissions, ownership and timestamps of deployed outputs.
+//!
+//! By default copies get the process umask, owner and the current time.
+//! `OutputAttrs` overrides that for copied files and for directories the
+//! deploy creates. Hard links and symlinks are left alone: changing them
+//! would change the source files they point at.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::DeployError;
+
+/// Owner and/or group to give outputs (`--chown user:group`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Owner {
+    /// Parse `user:group`, `user` or `:group`. Names are looked up in the
+    /// user and group databases; numeric ids are taken as is.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (user, group) = match spec.split_once(':') {
+            Some((user, group)) => (user, group),
+            None => (spec, ""),
+        };
+
+        let uid = match user {
+            "" => None,
+            name => Some(user_id(name)?),
+        };
+        let gid = match group {
+            "" => None,
+            name => Some(group_id(name)?),
+        };
+
+        (uid.is_some() || gid.is_some()).then_some(Self { uid, gid })
+    }
+}
+
+fn user_id(name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        // SAFETY: `name` is NUL-terminated; the returned entry is only read
+        // before any other passwd lookup (this runs once, during startup)
+        let entry = unsafe { libc::getpwnam(name.as_ptr()) };
+        if !entry.is_null() {
+            // SAFETY: non-null entries point at a valid `passwd`
+            return Some(unsafe { (*entry).pw_uid });
+        }
+    }
+
+    None
+}
+
+fn group_id(name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        // SAFETY: as in `user_id`, for the group database
+        let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+        if !entry.is_null() {
+            // SAFETY: non-null entries point at a valid `group`
+            return Some(unsafe { (*entry).gr_gid });
+        }
+    }
+
+    None
+}
+
+/// Parse an octal permission mode (`644`, `0644` or `0o644`)
+pub fn parse_mode(s: &str) -> Option<u32> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    if digits.is_empty() {
+        return None;
+    }
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+}
+
+/// Attributes applied to copied files and created directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputAttrs {
+    /// Permission bits for copied files (default: umask)
+    pub file_mode: Option<u32>,
+    /// Permission bits for created directories (default: umask)
+    pub dir_mode: Option<u32>,
+    /// Owner/group for both (default: the deploying user)
+    pub owner: Option<Owner>,
+    /// Give copied files their source's modification time
+    pub preserve_mtime: bool,
+}
+
+impl OutputAttrs {
+    /// Whether files keep the default attributes
+    fn is_default_for_files(&self) -> bool {
+        self.file_mode.is_none() && self.owner.is_none() && !self.preserve_mtime
+    }
+
+    /// Apply to `dst`, a file just copied from `src`
+    pub fn apply_file(&self, src: &Path, dst: &Path) -> Result<(), DeployError> {
+        if self.is_default_for_files() {
+            return Ok(());
+        }
+
+        let attr_error = |e: io::Error| match e.kind() {
+            io::ErrorKind::PermissionDenied => DeployError::PermissionDenied {
+                path: dst.to_path_buf(),
+                source: e,
+            },
+            _ => DeployError::CopyFailed {
+                src: src.to_path_buf(),
+                dst: dst.to_path_buf(),
+                source: e,
+            },
+        };
+
+        if self.preserve_mtime {
+            let modified = fs::metadata(src)
+                .and_then(|meta| meta.modified())
+                .map_err(attr_error)?;
+            File::options()
+                .write(true)
+                .open(dst)
+                .and_then(|file| file.set_modified(modified))
+                .map_err(attr_error)?;
+        }
+
+        self.apply_mode_and_owner(dst, self.file_mode)
+            .map_err(attr_error)
+    }
+
+    /// Apply to a directory the deploy created
+    pub fn apply_dir(&self, dir: &Path) -> Result<(), DeployError> {
+        self.apply_mode_and_owner(dir, self.dir_mode)
+            .map_err(|e| dir_error(dir, e))
+    }
+
+    #[cfg(unix)]
+    fn apply_mode_and_owner(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(owner) = self.owner {
+            std::os::unix::fs::chown(path, owner.uid, owner.gid)?;
+        }
+        // After chown, which may clear setuid/setgid bits
+        if let Some(mode) = mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn apply_mode_and_owner(&self, _path: &Path, _mode: Option<u32>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Create `dir` and its missing ancestors, applying `attrs` to each
+/// directory created (not to ones that already existed)
+pub fn create_dir_all(dir: &Path, attrs: &OutputAttrs) -> Result<(), DeployError> {
+    if attrs.dir_mode.is_none() && attrs.owner.is_none() {
+        return fs::create_dir_all(dir).map_err(|e| dir_error(dir, e));
+    }
+
+    let missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|path| fs::symlink_metadata(path).is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    fs::create_dir_all(dir).map_err(|e| dir_error(dir, e))?;
+
+    for path in missing.iter().rev() {
+        attrs.apply_dir(path)?;
+    }
+    Ok(())
+}
+
+fn dir_error(dir: &Path, e: io::Error) -> DeployError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => DeployError::PermissionDenied {
+            path: dir.to_path_buf(),
+            source: e,
+        },
+        _ => DeployError::CreateDirFailed {
+            path: dir.to_path_buf(),
+            source: e,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // ==================== parse tests ====================
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("644"), Some(0o644));
+        assert_eq!(parse_mode("0644"), Some(0o644));
+        assert_eq!(parse_mode("0o2775"), Some(0o2775));
+        assert_eq!(parse_mode("888"), None);
+        assert_eq!(parse_mode("77777"), None);
+        assert_eq!(parse_mode(""), None);
+        assert_eq!(parse_mode("rw-r--r--"), None);
+    }
+
+    #[test]
+    fn test_owner_parse_numeric() {
+        assert_eq!(
+            Owner::parse("33:33"),
+            Some(Owner {
+                uid: Some(33),
+                gid: Some(33)
+            })
+        );
+        assert_eq!(
+            Owner::parse("1000"),
+            Some(Owner {
+                uid: Some(1000),
+                gid: None
+            })
+        );
+        assert_eq!(
+            Owner::parse(":82"),
+            Some(Owner {
+                uid: None,
+                gid: Some(82)
+            })
+        );
+        assert_eq!(Owner::parse(":"), None);
+        assert_eq!(Owner::parse("no-such-user-xyz:0"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_owner_parse_names() {
+        assert_eq!(
+            Owner::parse("root:root"),
+            Some(Owner {
+                uid: Some(0),
+                gid: Some(0)
+            })
+        );
+    }
+
+    // ==================== apply tests ====================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_file_mode_and_mtime() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&src, "a").unwrap();
+        fs::write(&dst, "a").unwrap();
+        let past = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(&src)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let attrs = OutputAttrs {
+            file_mode: Some(0o640),
+            preserve_mtime: true,
+            ..OutputAttrs::default()
+        };
+        attrs.apply_file(&src, &dst).unwrap();
+
+        let meta = fs::metadata(&dst).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(meta.modified().unwrap(), past);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_dir_all_applies_to_created_dirs_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let existing = temp.path().join("existing");
+        fs::create_dir(&existing).unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let attrs = OutputAttrs {
+            dir_mode: Some(0o750),
+            ..OutputAttrs::default()
+        };
+        create_dir_all(&existing.join("a/b"), &attrs).unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(&existing), 0o700);
+        assert_eq!(mode(&existing.join("a")), 0o750);
+        assert_eq!(mode(&existing.join("a/b")), 0o750);
+    }
+
+    #[test]
+    fn test_apply_file_default_is_noop() {
+        let temp = TempDir::new().unwrap();
+        // Missing files are fine when there is nothing to apply
+        OutputAttrs::default()
+            .apply_file(&temp.path().join("a"), &temp.path().join("b"))
+            .unwrap();
+    }
+}
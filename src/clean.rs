@@ -0,0 +1,337 @@
+//! Removal of deployed static content (`clean` subcommand).
+//!
+//! Mirrors the manual `rm -rf pub/static/{frontend,adminhtml}
+//! pub/static/_cache var/view_preprocessed` before a full deploy, without
+//! taking `pub/static/.htaccess` and `deployed_version.txt` along. Area,
+//! theme and locale filters narrow it down to the matching outputs. The
+//! matching manifests go too, so the next deploy doesn't trust outputs that
+//! are gone, and so does the run journal.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::{previous_path, staging_path};
+use crate::error::DeployError;
+use crate::journal::journal_path;
+use crate::theme::{Area, LocaleCode};
+
+/// What to clean
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    pub areas: Vec<Area>,
+    /// `Vendor/name` themes; `None` for all
+    pub themes: Option<Vec<String>>,
+    /// `None` for all
+    pub locales: Option<Vec<LocaleCode>>,
+    /// Also remove `pub/static/.htaccess`
+    pub remove_htaccess: bool,
+    /// Also remove `pub/static/deployed_version.txt`
+    pub remove_version: bool,
+}
+
+impl CleanOptions {
+    /// Whether everything deployed is to be cleaned
+    fn is_unfiltered(&self) -> bool {
+        self.themes.is_none()
+            && self.locales.is_none()
+            && [Area::Frontend, Area::Adminhtml]
+                .iter()
+                .all(|area| self.areas.contains(area))
+    }
+}
+
+/// Directories laid out as `<area>/<Vendor>/<theme>/<locale>`
+fn area_trees(magento_root: &Path) -> [PathBuf; 3] {
+    let static_dir = magento_root.join("pub").join("static");
+    [
+        static_dir.clone(),
+        static_dir.join("_requirejs"),
+        magento_root
+            .join("var")
+            .join("view_preprocessed")
+            .join("pub")
+            .join("static"),
+    ]
+}
+
+/// Manifests, laid out as `<area>/<Vendor>/<theme>/<locale>.tsv`
+fn manifests_dir(magento_root: &Path) -> PathBuf {
+    magento_root
+        .join("var")
+        .join("static_deploy")
+        .join("manifests")
+}
+
+fn exists(path: &Path) -> bool {
+    path.symlink_metadata().is_ok()
+}
+
+/// Subdirectories of `dir`, sorted, skipping hidden ones
+fn subdirs(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            (!name.starts_with('.')).then(|| (name, entry.path()))
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Matching targets in one `<area>/<Vendor>/<theme>/<locale>` tree
+fn collect_area(area_dir: &Path, manifests: bool, options: &CleanOptions, out: &mut Vec<PathBuf>) {
+    if options.themes.is_none() && options.locales.is_none() {
+        if exists(area_dir) {
+            out.push(area_dir.to_path_buf());
+        }
+        return;
+    }
+
+    for (vendor, vendor_dir) in subdirs(area_dir) {
+        for (name, theme_dir) in subdirs(&vendor_dir) {
+            let full_name = format!("{vendor}/{name}");
+            if let Some(themes) = &options.themes {
+                if !themes.contains(&full_name) {
+                    continue;
+                }
+            }
+            let Some(locales) = &options.locales else {
+                out.push(theme_dir);
+                continue;
+            };
+            for locale in locales {
+                let candidates = if manifests {
+                    vec![theme_dir.join(format!("{}.tsv", locale.as_str()))]
+                } else {
+                    let output = theme_dir.join(locale.as_str());
+                    vec![staging_path(&output), previous_path(&output), output]
+                };
+                out.extend(candidates.into_iter().filter(|path| exists(path)));
+            }
+        }
+    }
+}
+
+/// Paths `clean` removes for `options`, in removal order; only existing
+/// ones are listed
+pub fn clean_targets(magento_root: &Path, options: &CleanOptions) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+    let [static_dir, requirejs_dir, preprocessed_dir] = area_trees(magento_root);
+    let view_preprocessed = magento_root.join("var").join("view_preprocessed");
+
+    for area in &options.areas {
+        collect_area(
+            &static_dir.join(area.as_str()),
+            false,
+            options,
+            &mut targets,
+        );
+        collect_area(
+            &requirejs_dir.join(area.as_str()),
+            false,
+            options,
+            &mut targets,
+        );
+        if !options.is_unfiltered() {
+            collect_area(
+                &preprocessed_dir.join(area.as_str()),
+                false,
+                options,
+                &mut targets,
+            );
+        }
+        collect_area(
+            &manifests_dir(magento_root).join(area.as_str()),
+            true,
+            options,
+            &mut targets,
+        );
+    }
+
+    // Everything preprocessed is derived, not just the per-theme trees
+    if options.is_unfiltered() && exists(&view_preprocessed) {
+        targets.push(view_preprocessed);
+    }
+
+    // Merged bundles may include any theme's files; they're rebuilt on demand
+    let cache = static_dir.join("_cache");
+    if exists(&cache) {
+        targets.push(cache);
+    }
+
+    // Jobs it records as done may just have been removed
+    let journal = journal_path(magento_root);
+    if exists(&journal) {
+        targets.push(journal);
+    }
+
+    let protected = [
+        (options.remove_htaccess, ".htaccess"),
+        (options.remove_version, "deployed_version.txt"),
+    ];
+    for (remove, name) in protected {
+        let path = static_dir.join(name);
+        if remove && exists(&path) {
+            targets.push(path);
+        }
+    }
+
+    targets
+}
+
+/// Remove a file, symlink or directory tree listed by [`clean_targets`]
+pub fn remove_target(path: &Path) -> Result<(), DeployError> {
+    let is_dir = path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    let removed = if is_dir {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    removed.map_err(|source| DeployError::PruneFailed {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn touch(root: &Path, rel: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "x").unwrap();
+    }
+
+    /// A Magento root with two themes deployed in two locales
+    fn deployed_root() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        for rel in [
+            "pub/static/.htaccess",
+            "pub/static/deployed_version.txt",
+            "pub/static/_cache/merged/abc.js",
+            "pub/static/frontend/Vendor/one/en_US/app.js",
+            "pub/static/frontend/Vendor/one/nl_NL/app.js",
+            "pub/static/frontend/Vendor/one/.nl_NL.previous/app.js",
+            "pub/static/frontend/Vendor/two/en_US/app.js",
+            "pub/static/adminhtml/Magento/backend/en_US/app.js",
+            "pub/static/_requirejs/frontend/Vendor/one/nl_NL/requirejs-config.js",
+            "var/view_preprocessed/pub/static/frontend/Vendor/one/nl_NL/css/styles.less",
+            "var/view_preprocessed/pub/static/frontend/Vendor/two/en_US/css/styles.less",
+            "var/static_deploy/manifests/frontend/Vendor/one/en_US.tsv",
+            "var/static_deploy/manifests/frontend/Vendor/one/nl_NL.tsv",
+            "var/static_deploy/manifests/frontend/Vendor/two/en_US.tsv",
+            "var/static_deploy/journal.tsv",
+        ] {
+            touch(root, rel);
+        }
+        temp
+    }
+
+    fn options(areas: &[Area]) -> CleanOptions {
+        CleanOptions {
+            areas: areas.to_vec(),
+            themes: None,
+            locales: None,
+            remove_htaccess: false,
+            remove_version: false,
+        }
+    }
+
+    fn relative(root: &Path, targets: &[PathBuf]) -> Vec<String> {
+        targets
+            .iter()
+            .map(|path| path.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    // ==================== clean_targets tests ====================
+
+    #[test]
+    fn test_clean_targets_everything_keeps_protected_files() {
+        let temp = deployed_root();
+        let targets = clean_targets(temp.path(), &options(&[Area::Frontend, Area::Adminhtml]));
+        assert_eq!(
+            relative(temp.path(), &targets),
+            vec![
+                "pub/static/frontend",
+                "pub/static/_requirejs/frontend",
+                "var/static_deploy/manifests/frontend",
+                "pub/static/adminhtml",
+                "var/view_preprocessed",
+                "pub/static/_cache",
+                "var/static_deploy/journal.tsv",
+            ]
+        );
+
+        for target in &targets {
+            remove_target(target).unwrap();
+        }
+        assert!(temp.path().join("pub/static/.htaccess").exists());
+        assert!(temp.path().join("pub/static/deployed_version.txt").exists());
+        assert!(
+            clean_targets(temp.path(), &options(&[Area::Frontend, Area::Adminhtml])).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_clean_targets_protected_files_on_request() {
+        let temp = deployed_root();
+        let mut opts = options(&[Area::Adminhtml]);
+        opts.remove_htaccess = true;
+        opts.remove_version = true;
+        let targets = relative(temp.path(), &clean_targets(temp.path(), &opts));
+        assert!(targets.contains(&"pub/static/.htaccess".to_string()));
+        assert!(targets.contains(&"pub/static/deployed_version.txt".to_string()));
+        // One area: only its preprocessed tree
+        assert!(!targets.contains(&"var/view_preprocessed".to_string()));
+        assert!(!targets.contains(&"pub/static/frontend".to_string()));
+    }
+
+    #[test]
+    fn test_clean_targets_theme_and_locale_filters() {
+        let temp = deployed_root();
+        let mut opts = options(&[Area::Frontend]);
+        opts.themes = Some(vec!["Vendor/one".to_string()]);
+        opts.locales = Some(vec![LocaleCode::new("nl_NL")]);
+        assert_eq!(
+            relative(temp.path(), &clean_targets(temp.path(), &opts)),
+            vec![
+                "pub/static/frontend/Vendor/one/.nl_NL.previous",
+                "pub/static/frontend/Vendor/one/nl_NL",
+                "pub/static/_requirejs/frontend/Vendor/one/nl_NL",
+                "var/view_preprocessed/pub/static/frontend/Vendor/one/nl_NL",
+                "var/static_deploy/manifests/frontend/Vendor/one/nl_NL.tsv",
+                "pub/static/_cache",
+                "var/static_deploy/journal.tsv",
+            ]
+        );
+
+        opts.locales = None;
+        let targets = relative(temp.path(), &clean_targets(temp.path(), &opts));
+        assert_eq!(targets[0], "pub/static/frontend/Vendor/one");
+        assert!(!targets.iter().any(|t| t.contains("Vendor/two")));
+    }
+
+    #[test]
+    fn test_remove_target_file_and_dir() {
+        let temp = deployed_root();
+        let dir = temp.path().join("pub/static/frontend");
+        let file = temp.path().join("pub/static/.htaccess");
+        remove_target(&dir).unwrap();
+        remove_target(&file).unwrap();
+        assert!(!dir.exists());
+        assert!(!file.exists());
+        assert!(matches!(
+            remove_target(&file),
+            Err(DeployError::PruneFailed { .. })
+        ));
+    }
+}
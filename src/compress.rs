@@ -0,0 +1,462 @@
+//! Precompressed sidecars (`--precompress`).
+//!
+//! nginx's `gzip_static`, `brotli_static` and `zstd_static` serve `app.js.gz`,
+//! `app.js.br` or `app.js.zst` when it sits next to `app.js`. Each deployed
+//! file with an allow-listed extension and at least the minimum size gets
+//! one sidecar per codec, written by the worker that installed the file.
+//! A sidecar carries the modification time of its file, so files the
+//! incremental deploy leaves alone only need a `stat` to tell whether their
+//! sidecars are still current. Sidecars that don't come out smaller than
+//! the file are not written (and a stale one is removed).
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use flate2::write::GzEncoder;
+
+use crate::attrs::OutputAttrs;
+use crate::error::DeployError;
+
+/// Files smaller than this (bytes) aren't worth a sidecar by default
+pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Sidecar compression format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 3] = [Codec::Gzip, Codec::Brotli, Codec::Zstd];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "gzip" => Some(Codec::Gzip),
+            "br" => Some(Codec::Brotli),
+            "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Brotli => "br",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    /// Extension appended to the file name
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Codec::Gzip => "gz",
+            Codec::Brotli => "br",
+            Codec::Zstd => "zst",
+        }
+    }
+
+    /// Compress `data` at the codec's best ratio
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Codec::Brotli => {
+                const QUALITY: u32 = 11;
+                const WINDOW_BITS: u32 = 22;
+                let mut out = Vec::new();
+                {
+                    let mut writer =
+                        brotli::CompressorWriter::new(&mut out, 64 * 1024, QUALITY, WINDOW_BITS);
+                    writer.write_all(data)?;
+                    writer.flush()?;
+                }
+                Ok(out)
+            }
+            Codec::Zstd => zstd::bulk::compress(data, 19),
+        }
+    }
+}
+
+/// Which files get sidecars, in which formats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Precompress {
+    pub codecs: Vec<Codec>,
+    /// Lower-case extensions without the dot
+    pub extensions: Vec<String>,
+    /// Minimum file size in bytes
+    pub min_size: u64,
+}
+
+impl Precompress {
+    /// Whether a file at `path` of `size` bytes gets sidecars
+    pub fn applies(&self, path: &Path, size: u64) -> bool {
+        size >= self.min_size && self.allows(path)
+    }
+
+    /// Whether `path` has an allow-listed extension
+    fn allows(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                self.extensions
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+            })
+    }
+
+    /// Whether `path` is a sidecar this configuration produces for a file
+    /// `is_deployed` accepts
+    pub fn is_sidecar(&self, path: &Path, is_deployed: impl Fn(&Path) -> bool) -> bool {
+        let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+            return false;
+        };
+        self.codecs.iter().any(|codec| codec.extension() == ext)
+            && path.file_stem().is_some_and(|stem| {
+                let file = path.with_file_name(stem);
+                self.allows(&file) && is_deployed(&file)
+            })
+    }
+}
+
+/// Path of `file`'s sidecar for `codec`
+pub fn sidecar_path(file: &Path, codec: Codec) -> PathBuf {
+    let mut name = file.as_os_str().to_os_string();
+    name.push(".");
+    name.push(codec.extension());
+    PathBuf::from(name)
+}
+
+/// Sidecar counters, shared by all workers
+#[derive(Debug, Default)]
+pub struct SidecarStats {
+    /// Sidecars written
+    pub written: AtomicU64,
+    /// Sidecars not written because they weren't smaller than the file
+    pub no_gain: AtomicU64,
+    /// Bytes the written sidecars save over their files
+    pub saved: AtomicU64,
+}
+
+impl SidecarStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Write the sidecars of `file`, which was just installed
+pub fn write_sidecars(
+    file: &Path,
+    options: &Precompress,
+    attrs: &OutputAttrs,
+    stats: &SidecarStats,
+) -> Result<(), DeployError> {
+    sync_sidecars(file, options, attrs, stats, true)
+}
+
+/// Rewrite the sidecars of `file` that are missing or older than it
+pub fn refresh_sidecars(
+    file: &Path,
+    options: &Precompress,
+    attrs: &OutputAttrs,
+    stats: &SidecarStats,
+) -> Result<(), DeployError> {
+    sync_sidecars(file, options, attrs, stats, false)
+}
+
+fn sync_sidecars(
+    file: &Path,
+    options: &Precompress,
+    attrs: &OutputAttrs,
+    stats: &SidecarStats,
+    force: bool,
+) -> Result<(), DeployError> {
+    let compress_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| DeployError::CompressFailed { path, source }
+    };
+
+    if !options.allows(file) {
+        return Ok(());
+    }
+    let meta = fs::metadata(file).map_err(compress_error(file))?;
+    if !options.applies(file, meta.len()) {
+        // The file may have dropped below the minimum size
+        for &codec in &options.codecs {
+            remove_sidecar(&sidecar_path(file, codec))?;
+        }
+        return Ok(());
+    }
+    let modified = meta.modified().map_err(compress_error(file))?;
+
+    let stale: Vec<Codec> = options
+        .codecs
+        .iter()
+        .copied()
+        .filter(|&codec| force || !is_current(&sidecar_path(file, codec), modified))
+        .collect();
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    let data = fs::read(file).map_err(compress_error(file))?;
+    for codec in stale {
+        let sidecar = sidecar_path(file, codec);
+        let compressed = codec.compress(&data).map_err(compress_error(&sidecar))?;
+        if compressed.len() >= data.len() {
+            remove_sidecar(&sidecar)?;
+            stats.no_gain.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
+        write_sidecar(file, &sidecar, &compressed, modified, attrs)?;
+        stats.written.fetch_add(1, Ordering::Relaxed);
+        stats
+            .saved
+            .fetch_add((data.len() - compressed.len()) as u64, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Whether `sidecar` was written for the file version modified at `modified`
+fn is_current(sidecar: &Path, modified: SystemTime) -> bool {
+    fs::metadata(sidecar)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|sidecar_modified| sidecar_modified == modified)
+}
+
+/// Write through a temporary file so nginx never serves a partial sidecar
+fn write_sidecar(
+    file: &Path,
+    sidecar: &Path,
+    data: &[u8],
+    modified: SystemTime,
+    attrs: &OutputAttrs,
+) -> Result<(), DeployError> {
+    let name = sidecar
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = sidecar.with_file_name(format!(".{name}.tmp"));
+    let written = File::create(&tmp)
+        .and_then(|mut out| {
+            out.write_all(data)?;
+            out.set_modified(modified)
+        })
+        .map_err(|source| DeployError::CompressFailed {
+            path: sidecar.to_path_buf(),
+            source,
+        });
+    let attrs = OutputAttrs {
+        preserve_mtime: false,
+        ..*attrs
+    };
+    let installed = written
+        .and_then(|()| attrs.apply_file(file, &tmp))
+        .and_then(|()| {
+            fs::rename(&tmp, sidecar).map_err(|source| DeployError::CompressFailed {
+                path: sidecar.to_path_buf(),
+                source,
+            })
+        });
+    if installed.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    installed
+}
+
+fn remove_sidecar(sidecar: &Path) -> Result<(), DeployError> {
+    match fs::remove_file(sidecar) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(DeployError::CompressFailed {
+            path: sidecar.to_path_buf(),
+            source: e,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn options(codecs: &[Codec]) -> Precompress {
+        Precompress {
+            codecs: codecs.to_vec(),
+            extensions: vec!["js".to_string(), "css".to_string()],
+            min_size: 100,
+        }
+    }
+
+    fn compressible() -> String {
+        "define(['jquery'], function ($) { return $; });\n".repeat(50)
+    }
+
+    // ==================== Codec tests ====================
+
+    #[test]
+    fn test_codec_parse() {
+        for codec in Codec::ALL {
+            assert_eq!(Codec::parse(codec.as_str()), Some(codec));
+        }
+        assert_eq!(Codec::parse("gz"), None);
+        assert_eq!(
+            sidecar_path(Path::new("/out/app.js"), Codec::Zstd),
+            PathBuf::from("/out/app.js.zst")
+        );
+    }
+
+    #[test]
+    fn test_codec_round_trip() {
+        let data = compressible();
+
+        let mut out = String::new();
+        let gz = Codec::Gzip.compress(data.as_bytes()).unwrap();
+        flate2::read::GzDecoder::new(&gz[..])
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        let mut out = String::new();
+        let br = Codec::Brotli.compress(data.as_bytes()).unwrap();
+        brotli::Decompressor::new(&br[..], 4096)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        let zst = Codec::Zstd.compress(data.as_bytes()).unwrap();
+        assert_eq!(zstd::decode_all(&zst[..]).unwrap(), data.as_bytes());
+    }
+
+    // ==================== Precompress tests ====================
+
+    #[test]
+    fn test_applies_extension_and_size() {
+        let options = options(&[Codec::Gzip]);
+        assert!(options.applies(Path::new("app.js"), 100));
+        assert!(options.applies(Path::new("APP.CSS"), 1000));
+        assert!(!options.applies(Path::new("app.js"), 99));
+        assert!(!options.applies(Path::new("logo.png"), 1000));
+        assert!(!options.applies(Path::new("LICENSE"), 1000));
+    }
+
+    #[test]
+    fn test_is_sidecar() {
+        let options = options(&[Codec::Gzip, Codec::Brotli]);
+        let deployed = |path: &Path| path == Path::new("js/app.js") || path == Path::new("a.png");
+        assert!(options.is_sidecar(Path::new("js/app.js.gz"), deployed));
+        assert!(options.is_sidecar(Path::new("js/app.js.br"), deployed));
+        // Not enabled, not deployed, or never precompressed
+        assert!(!options.is_sidecar(Path::new("js/app.js.zst"), deployed));
+        assert!(!options.is_sidecar(Path::new("js/old.js.gz"), deployed));
+        assert!(!options.is_sidecar(Path::new("a.png.gz"), deployed));
+    }
+
+    // ==================== Sidecar tests ====================
+
+    #[test]
+    fn test_write_sidecars() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("app.js");
+        fs::write(&file, compressible()).unwrap();
+        let stats = SidecarStats::new();
+
+        write_sidecars(
+            &file,
+            &options(&Codec::ALL),
+            &OutputAttrs::default(),
+            &stats,
+        )
+        .unwrap();
+        for codec in Codec::ALL {
+            let sidecar = sidecar_path(&file, codec);
+            assert!(sidecar.is_file(), "{}", codec.as_str());
+            assert!(is_current(
+                &sidecar,
+                fs::metadata(&file).unwrap().modified().unwrap()
+            ));
+        }
+        assert_eq!(stats.written.load(Ordering::Relaxed), 3);
+        assert!(stats.saved.load(Ordering::Relaxed) > 0);
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_sidecar_skipped_without_gain() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("random.js");
+        // Incompressible: a xorshift byte stream
+        let mut x: u64 = 0x2545_f491_4f6c_dd1d;
+        let data: Vec<u8> = (0..4096)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        fs::write(&file, data).unwrap();
+        fs::write(sidecar_path(&file, Codec::Gzip), "stale").unwrap();
+        let stats = SidecarStats::new();
+
+        write_sidecars(
+            &file,
+            &options(&[Codec::Gzip]),
+            &OutputAttrs::default(),
+            &stats,
+        )
+        .unwrap();
+        assert!(!sidecar_path(&file, Codec::Gzip).exists());
+        assert_eq!(stats.no_gain.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_refresh_sidecars_follows_file() {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("app.js");
+        let sidecar = sidecar_path(&file, Codec::Gzip);
+        let options = options(&[Codec::Gzip]);
+        let attrs = OutputAttrs::default();
+        fs::write(&file, compressible()).unwrap();
+
+        // Missing: written
+        let stats = SidecarStats::new();
+        refresh_sidecars(&file, &options, &attrs, &stats).unwrap();
+        assert_eq!(stats.written.load(Ordering::Relaxed), 1);
+
+        // Current: left alone
+        refresh_sidecars(&file, &options, &attrs, &stats).unwrap();
+        assert_eq!(stats.written.load(Ordering::Relaxed), 1);
+
+        // The file changed (an override won): rewritten
+        fs::write(&file, compressible() + "// override\n").unwrap();
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        refresh_sidecars(&file, &options, &attrs, &stats).unwrap();
+        assert_eq!(stats.written.load(Ordering::Relaxed), 2);
+        let mut out = String::new();
+        flate2::read::GzDecoder::new(File::open(&sidecar).unwrap())
+            .read_to_string(&mut out)
+            .unwrap();
+        assert!(out.ends_with("// override\n"));
+
+        // Shrunk below the minimum: removed
+        fs::write(&file, "tiny").unwrap();
+        refresh_sidecars(&file, &options, &attrs, &stats).unwrap();
+        assert!(!sidecar.exists());
+    }
+}
@@ -1,16 +1,37 @@
 //! CLI configuration and runtime settings for static content deployment.
 
-use clap::Parser;
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::attrs::{parse_mode, OutputAttrs, Owner};
+use crate::clean::CleanOptions;
+use crate::compress::{Codec, Precompress, DEFAULT_MIN_SIZE};
+use crate::copier::{CopyBackend, CopyMode, CopyOptions, LinkMode};
+use crate::deployer::DeployStrategy;
+use crate::minify::{magento_excludes, Minify};
+use crate::priority::IoPriority;
+use crate::ratelimit::RateLimiter;
 use crate::theme::{Area, LocaleCode};
 
+/// Default --io-backend: io_uring when compiled in (Linux only)
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const DEFAULT_IO_BACKEND: &str = "io-uring";
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+const DEFAULT_IO_BACKEND: &str = "std";
+
 /// High-performance static content deployment for Magento 2
 #[derive(Parser, Debug)]
 #[command(name = "magento-static-deploy")]
 #[command(version)]
 #[command(about = "High-performance static content deployment for Magento 2")]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Magento root directory
     #[arg(default_value = ".")]
     pub magento_root: PathBuf,
@@ -43,6 +64,270 @@ pub struct Cli {
     /// Include development files (.ts, .less, .md, node_modules, etc.)
     #[arg(short = 'd', long)]
     pub include_dev: bool,
+
+    /// How file contents are written: copy bytes, or clone extents (FICLONE) on btrfs/XFS
+    #[arg(long, default_value = "copy", value_parser = ["copy", "reflink", "auto"])]
+    pub copy_mode: String,
+
+    /// Materialize files as copies, hard links, or symlinks back to the sources (developer mode)
+    #[arg(long, default_value = "copy", value_parser = ["copy", "hardlink", "symlink"])]
+    pub link_mode: String,
+
+    /// Deploy every locale from sources, or resolve one locale per theme and replicate it
+    #[arg(long, default_value = "standard", value_parser = ["standard", "quick"])]
+    pub strategy: String,
+
+    /// I/O backend for file copies (io-uring needs a build with the io-uring feature)
+    #[arg(long, default_value = DEFAULT_IO_BACKEND, value_parser = ["std", "io-uring"])]
+    pub io_backend: String,
+
+    /// Ignore the previous deploy's manifest and copy every file
+    #[arg(long)]
+    pub full: bool,
+
+    /// Don't rewrite destination files whose content (size + xxh3) already matches
+    #[arg(long)]
+    pub skip_identical: bool,
+
+    /// Delete files in deployed outputs that no source provides anymore (keeps .htaccess)
+    #[arg(long)]
+    pub prune: bool,
+
+    /// Deploy into staging directories and swap each output in only when its job succeeds
+    #[arg(long)]
+    pub atomic: bool,
+
+    /// Swap the previous atomic deploy of the selected themes/locales back in, then exit
+    #[arg(long, conflicts_with = "atomic")]
+    pub rollback: bool,
+
+    /// Octal mode for copied files, e.g. 0644 (default: umask)
+    #[arg(long, value_name = "MODE")]
+    pub file_mode: Option<String>,
+
+    /// Octal mode for created directories, e.g. 0755 (default: umask)
+    #[arg(long, value_name = "MODE")]
+    pub dir_mode: Option<String>,
+
+    /// Owner for copied files and created directories (user:group, user or :group)
+    #[arg(long, value_name = "USER:GROUP")]
+    pub chown: Option<String>,
+
+    /// Give copied files the modification time of their source
+    #[arg(long)]
+    pub preserve_mtime: bool,
+
+    /// Deploy even if the pre-flight check says the outputs won't fit (bytes or inodes)
+    #[arg(long)]
+    pub force: bool,
+
+    /// Cap copy throughput in MB/s (1 MB = 1,000,000 bytes)
+    #[arg(long, value_name = "MB/s")]
+    pub io_limit: Option<f64>,
+
+    /// Cap the number of files installed per second
+    #[arg(long, value_name = "N")]
+    pub files_per_sec: Option<u64>,
+
+    /// Run the deploy (and bin/magento) at this niceness, -20 to 19
+    #[arg(long, value_name = "N", allow_hyphen_values = true,
+          value_parser = clap::value_parser!(i32).range(-20..=19))]
+    pub nice: Option<i32>,
+
+    /// I/O scheduling class: idle, or best-effort at the lowest level
+    #[arg(long, value_name = "CLASS", value_parser = ["idle", "best-effort"])]
+    pub ionice: Option<String>,
+
+    /// Record files that fail to deploy and carry on with the rest of the job
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Continue an interrupted deploy: skip jobs it completed, re-verify unfinished ones
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Wait for another deploy on this Magento root to finish instead of failing
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Wait at most this many seconds for another deploy (implies --wait)
+    #[arg(long, value_name = "SECS")]
+    pub lock_timeout: Option<u64>,
+
+    /// Write compressed sidecars next to deployed files (comma-separated: gzip, br, zstd)
+    #[arg(long, value_name = "CODECS", value_delimiter = ',',
+          value_parser = ["gzip", "br", "zstd"])]
+    pub precompress: Option<Vec<String>>,
+
+    /// Extensions that get sidecars (comma-separated)
+    #[arg(
+        long,
+        value_name = "EXTS",
+        value_delimiter = ',',
+        default_value = "js,css,html,json,svg,txt,xml,map,ttf,otf,eot,ico"
+    )]
+    pub precompress_ext: Vec<String>,
+
+    /// Smallest file that gets sidecars, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MIN_SIZE)]
+    pub precompress_min_size: u64,
+
+    /// Write a minified foo.min.js next to every foo.js
+    #[arg(long)]
+    pub minify_js: bool,
+
+    /// Write a minified foo.min.css next to every foo.css
+    #[arg(long)]
+    pub minify_css: bool,
+
+    /// Smallest CSS file that gets minified, in bytes (smaller ones are written as is)
+    #[arg(long, value_name = "BYTES", default_value_t = 0)]
+    pub minify_css_min_size: u64,
+
+    /// Minify Knockout .html templates in place, keeping <!-- ko --> comments
+    #[arg(long)]
+    pub minify_html: bool,
+
+    /// Regex of asset paths not to minify, on top of Magento's dev/{js,css}/minify_exclude (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    pub minify_exclude: Vec<String>,
+
+    /// Write each output's merged requirejs-config.js from the modules' and themes' ones
+    #[arg(long)]
+    pub requirejs_config: bool,
+}
+
+/// Subcommands other than deploying
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Remove deployed static content and preprocessed caches
+    Clean(CleanArgs),
+}
+
+/// Arguments of `clean`
+#[derive(Args, Debug)]
+pub struct CleanArgs {
+    /// Magento root directory
+    #[arg(default_value = ".")]
+    pub magento_root: PathBuf,
+
+    /// Areas to clean (comma-separated)
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "frontend,adminhtml"
+    )]
+    pub area: Vec<String>,
+
+    /// Only clean these themes, in Vendor/name format (comma-separated)
+    #[arg(short, long, value_delimiter = ',')]
+    pub theme: Option<Vec<String>>,
+
+    /// Only clean these locales (comma-separated)
+    #[arg(short, long, value_delimiter = ',')]
+    pub locale: Option<Vec<String>>,
+
+    /// List what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Also remove pub/static/.htaccess
+    #[arg(long)]
+    pub remove_htaccess: bool,
+
+    /// Also remove pub/static/deployed_version.txt
+    #[arg(long)]
+    pub remove_version: bool,
+
+    /// Wait for a deploy running on this Magento root to finish instead of failing
+    #[arg(long)]
+    pub wait: bool,
+
+    /// Wait at most this many seconds for a running deploy (implies --wait)
+    #[arg(long, value_name = "SECS")]
+    pub lock_timeout: Option<u64>,
+}
+
+impl CleanArgs {
+    /// Magento root, canonicalized like the deploy's
+    pub fn magento_root(&self) -> PathBuf {
+        self.magento_root
+            .canonicalize()
+            .unwrap_or_else(|_| self.magento_root.clone())
+    }
+
+    /// What to clean, with the filters validated
+    pub fn options(&self) -> anyhow::Result<CleanOptions> {
+        let locales = match &self.locale {
+            None => None,
+            Some(locales) => {
+                let mut validated = Vec::with_capacity(locales.len());
+                for locale_str in locales {
+                    match LocaleCode::validated(locale_str) {
+                        Ok(locale) => validated.push(locale),
+                        Err(msg) => anyhow::bail!(msg),
+                    }
+                }
+                Some(validated)
+            }
+        };
+
+        Ok(CleanOptions {
+            areas: self.area.iter().filter_map(|s| Area::parse(s)).collect(),
+            themes: self.theme.clone(),
+            locales,
+            remove_htaccess: self.remove_htaccess,
+            remove_version: self.remove_version,
+        })
+    }
+
+    /// How long to wait for the deploy lock
+    pub fn lock_wait(&self) -> Option<Duration> {
+        lock_wait(self.wait, self.lock_timeout)
+    }
+}
+
+/// Magento's `dev/{content_type}/minify_exclude` patterns plus the
+/// `--minify-exclude` ones, compiled. `None` if a Magento pattern can't be
+/// compiled: minifying would then touch files Magento leaves alone.
+fn minify_excludes(
+    magento_root: &Path,
+    content_type: &str,
+    extra: &[String],
+) -> anyhow::Result<Option<Vec<Regex>>> {
+    let mut extra_excludes = Vec::new();
+    for pattern in extra {
+        match Regex::new(pattern) {
+            Ok(regex) => extra_excludes.push(regex),
+            Err(e) => anyhow::bail!("invalid --minify-exclude '{}': {}", pattern, e),
+        }
+    }
+
+    let mut excludes = Vec::new();
+    // Third-party modules may use PCRE-only syntax; don't fail on theirs
+    for pattern in magento_excludes(magento_root, content_type) {
+        match Regex::new(&pattern) {
+            Ok(regex) => excludes.push(regex),
+            Err(e) => {
+                eprintln!(
+                    "Warning: not minifying {content_type} files: unsupported minify exclude '{pattern}': {e}"
+                );
+                return Ok(None);
+            }
+        }
+    }
+    excludes.extend(extra_excludes);
+    Ok(Some(excludes))
+}
+
+/// `--wait`/`--lock-timeout` as a maximum wait (`None`: don't wait)
+fn lock_wait(wait: bool, lock_timeout: Option<u64>) -> Option<Duration> {
+    match lock_timeout {
+        Some(secs) => Some(Duration::from_secs(secs)),
+        None if wait => Some(Duration::MAX),
+        None => None,
+    }
 }
 
 /// Runtime configuration parsed from CLI
@@ -62,6 +347,48 @@ pub struct Config {
     pub verbose: bool,
     /// Include development files (default: exclude)
     pub include_dev: bool,
+    /// Byte copy or reflink
+    pub copy_mode: CopyMode,
+    /// Copy, hard link or symlink
+    pub link_mode: LinkMode,
+    /// Standard or quick execution
+    pub strategy: DeployStrategy,
+    /// Std or io_uring file copies
+    pub io_backend: CopyBackend,
+    /// Copy everything instead of only files changed since the last deploy
+    pub full: bool,
+    /// Keep destination files whose content already matches the source
+    pub skip_identical: bool,
+    /// Remove orphaned files from successfully deployed outputs
+    pub prune: bool,
+    /// Stage outputs and swap them in on success
+    pub atomic: bool,
+    /// Restore the previous atomic deploy instead of deploying
+    pub rollback: bool,
+    /// Modes, owner and mtime of outputs
+    pub attrs: OutputAttrs,
+    /// Skip the pre-flight free space check
+    pub force: bool,
+    /// Copy throughput cap in bytes per second
+    pub io_limit: Option<u64>,
+    /// Files installed per second cap
+    pub files_per_sec: Option<u64>,
+    /// Process niceness
+    pub nice: Option<i32>,
+    /// I/O scheduling class
+    pub ionice: Option<IoPriority>,
+    /// Collect per-file failures instead of failing the job
+    pub keep_going: bool,
+    /// Pick up where the journal of an interrupted run left off
+    pub resume: bool,
+    /// How long to wait for the deploy lock (`None`: don't wait)
+    pub lock_wait: Option<Duration>,
+    /// Sidecar formats, extensions and minimum size
+    pub precompress: Option<Precompress>,
+    /// What to minify, and the excludes
+    pub minify: Option<Minify>,
+    /// Generate merged requirejs-config.js files
+    pub requirejs_config: bool,
 }
 
 impl Config {
@@ -80,6 +407,127 @@ impl Config {
             }
         }
 
+        let Some(copy_mode) = CopyMode::parse(&cli.copy_mode) else {
+            anyhow::bail!("invalid copy mode '{}'", cli.copy_mode);
+        };
+        let Some(link_mode) = LinkMode::parse(&cli.link_mode) else {
+            anyhow::bail!("invalid link mode '{}'", cli.link_mode);
+        };
+
+        let Some(strategy) = DeployStrategy::parse(&cli.strategy) else {
+            anyhow::bail!("invalid strategy '{}'", cli.strategy);
+        };
+
+        let Some(io_backend) = CopyBackend::parse(&cli.io_backend) else {
+            anyhow::bail!("invalid I/O backend '{}'", cli.io_backend);
+        };
+        if !io_backend.is_available() {
+            anyhow::bail!(
+                "I/O backend '{}' is not available in this build (rebuild with --features io-uring on Linux)",
+                cli.io_backend
+            );
+        }
+
+        let mode = |value: &Option<String>, what: &str| -> anyhow::Result<Option<u32>> {
+            match value {
+                None => Ok(None),
+                Some(s) => match parse_mode(s) {
+                    Some(mode) => Ok(Some(mode)),
+                    None => {
+                        anyhow::bail!("invalid {} '{}': expected an octal mode like 0644", what, s)
+                    }
+                },
+            }
+        };
+        let owner = match &cli.chown {
+            None => None,
+            Some(spec) => match Owner::parse(spec) {
+                Some(owner) => Some(owner),
+                None => anyhow::bail!("invalid --chown '{}': unknown user or group", spec),
+            },
+        };
+        let attrs = OutputAttrs {
+            file_mode: mode(&cli.file_mode, "file mode")?,
+            dir_mode: mode(&cli.dir_mode, "dir mode")?,
+            owner,
+            preserve_mtime: cli.preserve_mtime,
+        };
+
+        let io_limit = match cli.io_limit {
+            None => None,
+            Some(mb) if mb.is_finite() && mb > 0.0 => Some(((mb * 1_000_000.0) as u64).max(1)),
+            Some(mb) => anyhow::bail!("invalid --io-limit '{}': expected MB/s above 0", mb),
+        };
+        if cli.files_per_sec == Some(0) {
+            anyhow::bail!("invalid --files-per-sec '0': expected at least 1");
+        }
+        let ionice = match &cli.ionice {
+            None => None,
+            Some(s) => match IoPriority::parse(s) {
+                Some(priority) => Some(priority),
+                None => anyhow::bail!("invalid --ionice '{}'", s),
+            },
+        };
+
+        let lock_wait = lock_wait(cli.wait, cli.lock_timeout);
+
+        let precompress = match &cli.precompress {
+            None => None,
+            Some(names) => {
+                let mut codecs = Vec::new();
+                for name in names {
+                    match Codec::parse(name) {
+                        Some(codec) if !codecs.contains(&codec) => codecs.push(codec),
+                        Some(_) => {}
+                        None => anyhow::bail!("invalid --precompress '{}'", name),
+                    }
+                }
+                let extensions: Vec<String> = cli
+                    .precompress_ext
+                    .iter()
+                    .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                    .filter(|ext| !ext.is_empty())
+                    .collect();
+                if extensions.is_empty() {
+                    anyhow::bail!("--precompress-ext needs at least one extension");
+                }
+                Some(Precompress {
+                    codecs,
+                    extensions,
+                    min_size: cli.precompress_min_size,
+                })
+            }
+        };
+
+        let minify = if cli.minify_js || cli.minify_css || cli.minify_html {
+            // (enabled, excludes); off when Magento's patterns can't be honoured
+            let excludes = |enabled: bool, content_type: &str| -> anyhow::Result<_> {
+                if !enabled {
+                    return Ok((false, Vec::new()));
+                }
+                Ok(
+                    match minify_excludes(&magento_root, content_type, &cli.minify_exclude)? {
+                        Some(excludes) => (true, excludes),
+                        None => (false, Vec::new()),
+                    },
+                )
+            };
+            let (js, js_exclude) = excludes(cli.minify_js, "js")?;
+            let (css, css_exclude) = excludes(cli.minify_css, "css")?;
+            let (html, html_exclude) = excludes(cli.minify_html, "template")?;
+            Some(Minify {
+                js,
+                js_exclude,
+                css,
+                css_exclude,
+                css_min_size: cli.minify_css_min_size,
+                html,
+                html_exclude,
+            })
+        } else {
+            None
+        };
+
         Ok(Config {
             magento_root,
             areas,
@@ -88,8 +536,47 @@ impl Config {
             jobs: cli.jobs.max(1),
             verbose: cli.verbose,
             include_dev: cli.include_dev,
+            copy_mode,
+            link_mode,
+            strategy,
+            io_backend,
+            full: cli.full,
+            skip_identical: cli.skip_identical,
+            prune: cli.prune,
+            atomic: cli.atomic,
+            rollback: cli.rollback,
+            attrs,
+            force: cli.force,
+            io_limit,
+            files_per_sec: cli.files_per_sec,
+            nice: cli.nice,
+            ionice,
+            keep_going: cli.keep_going,
+            resume: cli.resume,
+            lock_wait,
+            precompress,
+            minify,
+            requirejs_config: cli.requirejs_config,
         })
     }
+
+    /// Copier options derived from this configuration.
+    /// Each call starts a separate rate limiter.
+    pub fn copy_options(&self) -> CopyOptions {
+        CopyOptions {
+            include_dev: self.include_dev,
+            copy_mode: self.copy_mode,
+            link_mode: self.link_mode,
+            backend: self.io_backend,
+            skip_identical: self.skip_identical,
+            attrs: self.attrs,
+            rate_limit: RateLimiter::new(self.io_limit, self.files_per_sec).map(Arc::new),
+            keep_going: self.keep_going,
+            precompress: self.precompress.clone(),
+            minify: self.minify.clone(),
+            requirejs_config: self.requirejs_config,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -105,6 +592,7 @@ mod tests {
         include_dev: bool,
     ) -> Cli {
         Cli {
+            command: None,
             magento_root: PathBuf::from("/tmp"),
             area,
             theme,
@@ -112,6 +600,37 @@ mod tests {
             jobs,
             verbose,
             include_dev,
+            copy_mode: "copy".to_string(),
+            link_mode: "copy".to_string(),
+            strategy: "standard".to_string(),
+            io_backend: "std".to_string(),
+            full: false,
+            skip_identical: false,
+            prune: false,
+            atomic: false,
+            rollback: false,
+            file_mode: None,
+            dir_mode: None,
+            chown: None,
+            preserve_mtime: false,
+            force: false,
+            io_limit: None,
+            files_per_sec: None,
+            nice: None,
+            ionice: None,
+            keep_going: false,
+            resume: false,
+            wait: false,
+            lock_timeout: None,
+            precompress: None,
+            precompress_ext: vec!["js".to_string()],
+            precompress_min_size: DEFAULT_MIN_SIZE,
+            minify_js: false,
+            minify_css: false,
+            minify_css_min_size: 0,
+            minify_html: false,
+            minify_exclude: Vec::new(),
+            requirejs_config: false,
         }
     }
 
@@ -312,6 +831,529 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_config_from_cli_copy_mode_default() {
+        let cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+
+        let config = Config::from_cli(cli).unwrap();
+
+        assert_eq!(config.copy_mode, CopyMode::Copy);
+    }
+
+    #[test]
+    fn test_config_from_cli_copy_mode_reflink() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            true,
+        );
+        cli.copy_mode = "reflink".to_string();
+
+        let config = Config::from_cli(cli).unwrap();
+        let options = config.copy_options();
+
+        assert_eq!(config.copy_mode, CopyMode::Reflink);
+        assert_eq!(options.copy_mode, CopyMode::Reflink);
+        assert!(options.include_dev);
+    }
+
+    #[test]
+    fn test_config_from_cli_copy_mode_invalid() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.copy_mode = "clone".to_string();
+
+        assert!(Config::from_cli(cli).is_err());
+    }
+
+    #[test]
+    fn test_config_from_cli_link_mode() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.link_mode = "symlink".to_string();
+
+        let config = Config::from_cli(cli).unwrap();
+
+        assert_eq!(config.link_mode, LinkMode::Symlink);
+        assert_eq!(config.copy_options().link_mode, LinkMode::Symlink);
+    }
+
+    #[test]
+    fn test_config_from_cli_link_mode_invalid() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.link_mode = "junction".to_string();
+
+        assert!(Config::from_cli(cli).is_err());
+    }
+
+    #[test]
+    fn test_config_from_cli_skip_identical() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.skip_identical = true;
+        let config = Config::from_cli(cli).unwrap();
+        assert!(config.skip_identical);
+        assert!(config.copy_options().skip_identical);
+    }
+
+    #[test]
+    fn test_config_from_cli_atomic_and_rollback() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.atomic = true;
+
+        let config = Config::from_cli(cli).unwrap();
+        assert!(config.atomic);
+        assert!(!config.rollback);
+    }
+
+    #[test]
+    fn test_cli_atomic_conflicts_with_rollback() {
+        let result = Cli::try_parse_from(["magento-static-deploy", "--atomic", "--rollback"]);
+        assert!(result.is_err());
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--rollback"]).unwrap();
+        assert!(cli.rollback);
+    }
+
+    #[test]
+    fn test_cli_force() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert!(!Config::from_cli(cli).unwrap().force);
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--force"]).unwrap();
+        assert!(Config::from_cli(cli).unwrap().force);
+    }
+
+    #[test]
+    fn test_cli_io_limits_and_priority() {
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "--io-limit",
+            "2.5",
+            "--files-per-sec",
+            "500",
+            "--nice",
+            "-5",
+            "--ionice",
+            "idle",
+        ])
+        .unwrap();
+        let config = Config::from_cli(cli).unwrap();
+
+        assert_eq!(config.io_limit, Some(2_500_000));
+        assert_eq!(config.files_per_sec, Some(500));
+        assert_eq!(config.nice, Some(-5));
+        assert_eq!(config.ionice, Some(IoPriority::Idle));
+        assert!(config.copy_options().rate_limit.is_some());
+
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert!(Config::from_cli(cli)
+            .unwrap()
+            .copy_options()
+            .rate_limit
+            .is_none());
+    }
+
+    #[test]
+    fn test_cli_keep_going() {
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--keep-going"]).unwrap();
+        let config = Config::from_cli(cli).unwrap();
+        assert!(config.keep_going);
+        assert!(config.copy_options().keep_going);
+    }
+
+    #[test]
+    fn test_cli_resume() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert!(!Config::from_cli(cli).unwrap().resume);
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--resume"]).unwrap();
+        assert!(Config::from_cli(cli).unwrap().resume);
+    }
+
+    #[test]
+    fn test_cli_lock_wait() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert_eq!(Config::from_cli(cli).unwrap().lock_wait, None);
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--wait"]).unwrap();
+        assert_eq!(
+            Config::from_cli(cli).unwrap().lock_wait,
+            Some(Duration::MAX)
+        );
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--lock-timeout", "30"]).unwrap();
+        assert_eq!(
+            Config::from_cli(cli).unwrap().lock_wait,
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_cli_precompress() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert_eq!(Config::from_cli(cli).unwrap().precompress, None);
+
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "--precompress",
+            "br,gzip,br",
+            "--precompress-ext",
+            ".JS,css",
+            "--precompress-min-size",
+            "512",
+        ])
+        .unwrap();
+        let config = Config::from_cli(cli).unwrap();
+        let expected = Precompress {
+            codecs: vec![Codec::Brotli, Codec::Gzip],
+            extensions: vec!["js".to_string(), "css".to_string()],
+            min_size: 512,
+        };
+        assert_eq!(config.precompress.as_ref(), Some(&expected));
+        assert_eq!(config.copy_options().precompress, Some(expected));
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--precompress", "zstd"]).unwrap();
+        let precompress = Config::from_cli(cli).unwrap().precompress.unwrap();
+        assert!(precompress.extensions.contains(&"svg".to_string()));
+        assert_eq!(precompress.min_size, DEFAULT_MIN_SIZE);
+
+        assert!(Cli::try_parse_from(["magento-static-deploy", "--precompress", "lzma"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_minify_js() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert!(Config::from_cli(cli).unwrap().minify.is_none());
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let config_xml = temp
+            .path()
+            .join("vendor/magento/module-store/etc/config.xml");
+        std::fs::create_dir_all(config_xml.parent().unwrap()).unwrap();
+        std::fs::write(
+            &config_xml,
+            "<config><default><dev><js><minify_exclude><tiny_mce>/tiny_mce/</tiny_mce></minify_exclude></js></dev></default></config>",
+        )
+        .unwrap();
+        let root = temp.path().to_str().unwrap();
+
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "--minify-js",
+            "--minify-exclude",
+            "/vendor-bundle/",
+            root,
+        ])
+        .unwrap();
+        let minify = Config::from_cli(cli)
+            .unwrap()
+            .copy_options()
+            .minify
+            .unwrap();
+        assert!(minify.js);
+        let excludes: Vec<&str> = minify.js_exclude.iter().map(Regex::as_str).collect();
+        assert_eq!(excludes, vec!["/tiny_mce/", "/vendor-bundle/"]);
+
+        // A lookbehind isn't supported: JS isn't minified at all
+        std::fs::write(
+            &config_xml,
+            "<config><default><dev><js><minify_exclude><bad>(?<=x)y</bad></minify_exclude></js><css><minify_exclude><ok>/ok/</ok></minify_exclude></css></dev></default></config>",
+        )
+        .unwrap();
+        let cli =
+            Cli::try_parse_from(["magento-static-deploy", "--minify-js", "--minify-css", root])
+                .unwrap();
+        let minify = Config::from_cli(cli).unwrap().minify.unwrap();
+        assert!(!minify.js);
+        assert!(minify.css);
+
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "--minify-js",
+            "--minify-exclude",
+            "(unclosed",
+            root,
+        ])
+        .unwrap();
+        assert!(Config::from_cli(cli).is_err());
+    }
+
+    #[test]
+    fn test_cli_minify_css() {
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "--minify-css",
+            "--minify-css-min-size",
+            "2048",
+            "--minify-exclude",
+            "/email-fonts/",
+        ])
+        .unwrap();
+        let minify = Config::from_cli(cli).unwrap().minify.unwrap();
+        assert!(minify.css);
+        assert!(!minify.js);
+        assert_eq!(minify.css_min_size, 2048);
+        let excludes: Vec<&str> = minify.css_exclude.iter().map(Regex::as_str).collect();
+        assert_eq!(excludes, vec!["/email-fonts/"]);
+    }
+
+    #[test]
+    fn test_cli_minify_html() {
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--minify-html"]).unwrap();
+        let minify = Config::from_cli(cli).unwrap().minify.unwrap();
+        assert!(minify.html);
+        assert!(!minify.js && !minify.css);
+    }
+
+    #[test]
+    fn test_cli_requirejs_config() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+        assert!(
+            !Config::from_cli(cli)
+                .unwrap()
+                .copy_options()
+                .requirejs_config
+        );
+
+        let cli = Cli::try_parse_from(["magento-static-deploy", "--requirejs-config"]).unwrap();
+        assert!(
+            Config::from_cli(cli)
+                .unwrap()
+                .copy_options()
+                .requirejs_config
+        );
+    }
+
+    #[test]
+    fn test_cli_clean_subcommand() {
+        let cli = Cli::try_parse_from([
+            "magento-static-deploy",
+            "clean",
+            "/var/www/magento",
+            "-a",
+            "frontend",
+            "-t",
+            "Vendor/theme",
+            "-l",
+            "en_US,nl_NL",
+            "--dry-run",
+            "--remove-htaccess",
+        ])
+        .unwrap();
+        let Some(Command::Clean(args)) = cli.command else {
+            panic!("expected clean");
+        };
+        assert_eq!(args.magento_root, PathBuf::from("/var/www/magento"));
+        assert!(args.dry_run);
+        assert_eq!(args.lock_wait(), None);
+        let options = args.options().unwrap();
+        assert_eq!(options.areas, vec![Area::Frontend]);
+        assert_eq!(options.themes, Some(vec!["Vendor/theme".to_string()]));
+        assert_eq!(options.locales.map(|l| l.len()), Some(2));
+        assert!(options.remove_htaccess);
+        assert!(!options.remove_version);
+
+        // Without a subcommand, the first positional is still the root
+        let cli = Cli::try_parse_from(["magento-static-deploy", "/var/www/magento"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.magento_root, PathBuf::from("/var/www/magento"));
+    }
+
+    #[test]
+    fn test_cli_clean_invalid_locale() {
+        let cli = Cli::try_parse_from(["magento-static-deploy", "clean", "-l", "english"]).unwrap();
+        let Some(Command::Clean(args)) = cli.command else {
+            panic!("expected clean");
+        };
+        assert!(args.options().is_err());
+    }
+
+    #[test]
+    fn test_cli_io_limits_invalid() {
+        for arg in [
+            "--io-limit=0",
+            "--io-limit=-1",
+            "--io-limit=inf",
+            "--files-per-sec=0",
+        ] {
+            let cli = Cli::try_parse_from(["magento-static-deploy", arg]).unwrap();
+            assert!(Config::from_cli(cli).is_err(), "{arg}");
+        }
+
+        for arg in ["--nice=20", "--nice=-21", "--ionice=realtime"] {
+            assert!(
+                Cli::try_parse_from(["magento-static-deploy", arg]).is_err(),
+                "{arg}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_config_from_cli_output_attrs() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.file_mode = Some("0644".to_string());
+        cli.dir_mode = Some("2775".to_string());
+        cli.chown = Some("33:33".to_string());
+        cli.preserve_mtime = true;
+
+        let config = Config::from_cli(cli).unwrap();
+        let attrs = config.copy_options().attrs;
+
+        assert_eq!(attrs.file_mode, Some(0o644));
+        assert_eq!(attrs.dir_mode, Some(0o2775));
+        assert_eq!(
+            attrs.owner,
+            Some(Owner {
+                uid: Some(33),
+                gid: Some(33)
+            })
+        );
+        assert!(attrs.preserve_mtime);
+    }
+
+    #[test]
+    fn test_config_from_cli_output_attrs_invalid() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.file_mode = Some("rw-r--r--".to_string());
+        assert!(Config::from_cli(cli).is_err());
+
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.chown = Some("no-such-user-xyz".to_string());
+        assert!(Config::from_cli(cli).is_err());
+    }
+
+    #[test]
+    fn test_config_from_cli_strategy() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.strategy = "quick".to_string();
+
+        let config = Config::from_cli(cli).unwrap();
+
+        assert_eq!(config.strategy, DeployStrategy::Quick);
+    }
+
+    #[test]
+    fn test_config_from_cli_strategy_invalid() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.strategy = "compact".to_string();
+
+        assert!(Config::from_cli(cli).is_err());
+    }
+
+    #[test]
+    fn test_config_from_cli_io_backend() {
+        let mut cli = make_cli(
+            vec!["frontend".to_string()],
+            vec!["en_US".to_string()],
+            None,
+            4,
+            false,
+            false,
+        );
+        cli.io_backend = "io-uring".to_string();
+
+        let result = Config::from_cli(cli);
+
+        if CopyBackend::IoUring.is_available() {
+            assert_eq!(result.unwrap().copy_options().backend, CopyBackend::IoUring);
+        } else {
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_config_from_cli_io_backend_default() {
+        let cli = Cli::try_parse_from(["magento-static-deploy"]).unwrap();
+
+        let config = Config::from_cli(cli).unwrap();
+
+        // The default is always usable: io_uring only where it is compiled in
+        let expected = if cfg!(all(target_os = "linux", feature = "io-uring")) {
+            CopyBackend::IoUring
+        } else {
+            CopyBackend::Std
+        };
+        assert_eq!(config.io_backend, expected);
+    }
+
     // ==================== Config Clone tests ====================
 
     #[test]
@@ -1,22 +1,28 @@
 //! File copying operations with buffered I/O for optimal performance.
 //!
-//! Uses 64KB buffers for improved NVMe SSD throughput compared to
-//! the default 8KB buffer in `std::fs::copy`. File copying within
-//! directories is parallelized using Rayon for maximum throughput.
+//! On Linux, file contents are copied in-kernel with `copy_file_range(2)`
+//! (or `sendfile(2)`), falling back to 64KB userspace buffers when the
+//! kernel or filesystem can't. File copying within directories is
+//! parallelized using Rayon for maximum throughput. Files can also be
+//! materialized as hard links or symlinks instead of copies.
 
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
+use crate::attrs::{self, OutputAttrs};
+use crate::compress::Precompress;
 use crate::error::DeployError;
+use crate::minify::Minify;
+use crate::ratelimit::RateLimiter;
 
 /// Buffer size for file copy operations (64KB for optimal NVMe performance).
 /// Modern NVMe SSDs benefit from larger transfer sizes.
@@ -192,42 +198,1104 @@ fn is_disk_full_error(e: &std::io::Error) -> bool {
     }
 }
 
-/// Copy a single file from src to dst using thread-local buffer pool
-pub fn copy_file(src: &Path, dst: &Path) -> Result<u64, DeployError> {
-    // Create parent directory if needed
-    if let Some(parent) = dst.parent() {
+/// Check if an IO error is a cross-device link error (EXDEV)
+#[inline]
+fn is_cross_device_error(e: &std::io::Error) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        e.raw_os_error() == Some(libc::EXDEV)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Create the parent directory of `path` if it doesn't exist yet,
+/// applying `attrs` to the directories created
+fn ensure_parent_dir(path: &Path, attrs: &OutputAttrs) -> Result<(), DeployError> {
+    if let Some(parent) = path.parent() {
         if !parent.exists() {
-            fs::create_dir_all(parent).map_err(|e| {
-                if is_disk_full_error(&e) {
-                    return DeployError::DiskFull {
-                        path: parent.to_path_buf(),
-                    };
-                }
-                DeployError::CreateDirFailed {
-                    path: parent.to_path_buf(),
-                    source: e,
+            attrs::create_dir_all(parent, attrs).map_err(|e| match e {
+                DeployError::CreateDirFailed { path, source } if is_disk_full_error(&source) => {
+                    DeployError::DiskFull { path }
                 }
+                e => e,
             })?;
         }
     }
+    Ok(())
+}
+
+/// Remove `dst` if it is a symlink or a hard link shared with another path,
+/// so that truncating it for a copy can't write through to a source file
+fn unlink_if_shared(dst: &Path) -> std::io::Result<()> {
+    let Ok(meta) = fs::symlink_metadata(dst) else {
+        return Ok(());
+    };
+
+    #[cfg(unix)]
+    let shared = {
+        use std::os::unix::fs::MetadataExt;
+        meta.file_type().is_symlink() || meta.nlink() > 1
+    };
+    #[cfg(not(unix))]
+    let shared = meta.file_type().is_symlink();
+
+    if shared {
+        fs::remove_file(dst)?;
+    }
+    Ok(())
+}
+
+/// Device id of `path`, or of its nearest existing ancestor
+fn device_of(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        path.ancestors()
+            .find_map(|p| fs::metadata(p).ok())
+            .map(|m| m.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Outcome of trying to link a file into place
+enum LinkOutcome {
+    /// Link created
+    Linked(CopyMethod),
+    /// Destination exists and skip_existing is set
+    Exists,
+    /// Link not possible here (cross-device, unsupported); copy instead
+    Fallback,
+}
+
+/// Create a hard link or symlink at `dst` pointing at `src`.
+/// With `skip_existing`, an existing destination is left alone (override
+/// priority); otherwise it is replaced.
+fn link_file(
+    src: &Path,
+    dst: &Path,
+    mode: LinkMode,
+    skip_existing: bool,
+) -> Result<LinkOutcome, DeployError> {
+    let attempt = || -> std::io::Result<CopyMethod> {
+        match mode {
+            LinkMode::Hardlink => fs::hard_link(src, dst).map(|_| CopyMethod::Hardlink),
+            #[cfg(unix)]
+            LinkMode::Symlink => std::os::unix::fs::symlink(src, dst).map(|_| CopyMethod::Symlink),
+            _ => Err(std::io::Error::from(std::io::ErrorKind::Unsupported)),
+        }
+    };
+
+    let result = match attempt() {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            if skip_existing {
+                return Ok(LinkOutcome::Exists);
+            }
+            fs::remove_file(dst).and_then(|_| attempt())
+        }
+        other => other,
+    };
+
+    match result {
+        Ok(method) => Ok(LinkOutcome::Linked(method)),
+        Err(e) if is_cross_device_error(&e) || e.kind() == std::io::ErrorKind::Unsupported => {
+            Ok(LinkOutcome::Fallback)
+        }
+        Err(e) => Err(copy_error(src, dst, e)),
+    }
+}
+
+/// Link mode actually usable for copying `src` into `dst`: hard links
+/// can't cross devices, so such sources are copied
+pub(crate) fn effective_link_mode(src: &Path, dst: &Path, mode: LinkMode) -> LinkMode {
+    if mode == LinkMode::Hardlink && device_of(src) != device_of(dst) {
+        return LinkMode::Copy;
+    }
+    mode
+}
+
+/// How the bytes of a file were transferred to its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// In-kernel copy with `copy_file_range(2)` (Linux)
+    CopyFileRange,
+    /// In-kernel copy with `sendfile(2)` (Linux)
+    Sendfile,
+    /// Userspace copy through the thread-local 64KB buffer
+    Buffered,
+    /// Batched open/read/write/close through io_uring (`io-uring` feature)
+    IoUring,
+    /// Extents shared with the source via the `FICLONE` ioctl (no bytes copied)
+    Reflink,
+    /// Hard link to the source file (no bytes copied)
+    Hardlink,
+    /// Symbolic link pointing at the source file (no bytes copied)
+    Symlink,
+}
+
+impl CopyMethod {
+    /// All methods, in the order they are reported
+    pub const ALL: [CopyMethod; 7] = [
+        CopyMethod::Reflink,
+        CopyMethod::Hardlink,
+        CopyMethod::Symlink,
+        CopyMethod::IoUring,
+        CopyMethod::CopyFileRange,
+        CopyMethod::Sendfile,
+        CopyMethod::Buffered,
+    ];
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CopyMethod::CopyFileRange => "copy_file_range",
+            CopyMethod::Sendfile => "sendfile",
+            CopyMethod::Buffered => "buffered",
+            CopyMethod::IoUring => "io_uring",
+            CopyMethod::Reflink => "reflink",
+            CopyMethod::Hardlink => "hardlink",
+            CopyMethod::Symlink => "symlink",
+        }
+    }
+}
+
+/// How file contents are materialized at the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyMode {
+    /// Copy bytes (kernel fast path, buffered fallback)
+    #[default]
+    Copy,
+    /// Try `FICLONE` for every file, falling back to a byte copy per file
+    Reflink,
+    /// Try `FICLONE` only where it can work: same device as the source, and
+    /// not after the destination filesystem reported it unsupported
+    Auto,
+}
+
+impl CopyMode {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CopyMode::Copy => "copy",
+            CopyMode::Reflink => "reflink",
+            CopyMode::Auto => "auto",
+        }
+    }
+
+    #[inline]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "copy" => Some(CopyMode::Copy),
+            "reflink" => Some(CopyMode::Reflink),
+            "auto" => Some(CopyMode::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Whether files are copied or linked back to their source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkMode {
+    /// Write a copy of the file (see `CopyMode`)
+    #[default]
+    Copy,
+    /// Hard link to the source; sources on another device are copied instead
+    Hardlink,
+    /// Absolute symlink to the source, like Magento developer mode
+    Symlink,
+}
+
+impl LinkMode {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkMode::Copy => "copy",
+            LinkMode::Hardlink => "hardlink",
+            LinkMode::Symlink => "symlink",
+        }
+    }
+
+    #[inline]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "copy" => Some(LinkMode::Copy),
+            "hardlink" => Some(LinkMode::Hardlink),
+            "symlink" => Some(LinkMode::Symlink),
+            _ => None,
+        }
+    }
+}
+
+/// Which I/O path executes planned file copies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyBackend {
+    /// One file at a time with regular syscalls
+    #[cfg_attr(not(all(target_os = "linux", feature = "io-uring")), default)]
+    Std,
+    /// Batches of small files through io_uring, falling back to Std at
+    /// runtime when the kernel refuses (requires the `io-uring` feature)
+    #[cfg_attr(all(target_os = "linux", feature = "io-uring"), default)]
+    IoUring,
+}
+
+impl CopyBackend {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CopyBackend::Std => "std",
+            CopyBackend::IoUring => "io-uring",
+        }
+    }
+
+    #[inline]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "std" => Some(CopyBackend::Std),
+            "io-uring" => Some(CopyBackend::IoUring),
+            _ => None,
+        }
+    }
+
+    /// Whether this build can use the backend at all
+    pub fn is_available(&self) -> bool {
+        match self {
+            CopyBackend::Std => true,
+            CopyBackend::IoUring => cfg!(all(target_os = "linux", feature = "io-uring")),
+        }
+    }
+}
+
+/// Options controlling how directories and files are copied
+#[derive(Debug, Clone, Default)]
+pub struct CopyOptions {
+    /// Include development files (default: exclude)
+    pub include_dev: bool,
+    /// Byte copy or reflink
+    pub copy_mode: CopyMode,
+    /// Copy, hard link or symlink
+    pub link_mode: LinkMode,
+    /// Std or io_uring
+    pub backend: CopyBackend,
+    /// Leave destinations whose content already matches the source alone
+    pub skip_identical: bool,
+    /// Modes, owner and mtime of copied files and created directories
+    pub attrs: OutputAttrs,
+    /// Bytes/sec and files/sec cap shared by all workers
+    pub rate_limit: Option<Arc<RateLimiter>>,
+    /// Record files that fail and carry on with the rest
+    pub keep_going: bool,
+    /// Write compressed sidecars next to installed files
+    pub precompress: Option<Precompress>,
+    /// Write minified copies of installed files
+    pub minify: Option<Minify>,
+    /// Write each output's merged requirejs-config.js
+    pub requirejs_config: bool,
+}
+
+/// A file that couldn't be deployed (`--keep-going`)
+#[derive(Debug)]
+pub struct FileFailure {
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub error: DeployError,
+}
+
+impl CopyOptions {
+    /// Wait for the rate limit to allow `files` files of `bytes` bytes
+    #[inline]
+    fn throttle(&self, files: u64, bytes: u64) {
+        if let Some(limit) = &self.rate_limit {
+            limit.acquire(files, bytes);
+        }
+    }
+
+    /// Wait for the rate limit to allow copying `src`
+    #[inline]
+    fn throttle_copy(&self, src: &Path) {
+        if let Some(limit) = &self.rate_limit {
+            limit.acquire(1, fs::metadata(src).map(|m| m.len()).unwrap_or(0));
+        }
+    }
+}
+
+/// Per-method file counters, updated lock-free from parallel workers
+#[derive(Debug, Default)]
+pub struct CopyStats {
+    pub copy_file_range: AtomicU64,
+    pub sendfile: AtomicU64,
+    pub buffered: AtomicU64,
+    pub io_uring: AtomicU64,
+    pub reflink: AtomicU64,
+    pub hardlink: AtomicU64,
+    pub symlink: AtomicU64,
+    /// Destinations left in place because their content already matched
+    pub identical: AtomicU64,
+    /// Set once the destination filesystem rejected FICLONE (CopyMode::Auto stops trying)
+    reflink_unsupported: AtomicBool,
+}
+
+impl CopyStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one file transferred with `method`
+    #[inline]
+    pub fn record(&self, method: CopyMethod) {
+        self.counter(method).fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of files transferred with `method`
+    pub fn get(&self, method: CopyMethod) -> u64 {
+        self.counter(method).load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn counter(&self, method: CopyMethod) -> &AtomicU64 {
+        match method {
+            CopyMethod::CopyFileRange => &self.copy_file_range,
+            CopyMethod::Sendfile => &self.sendfile,
+            CopyMethod::Buffered => &self.buffered,
+            CopyMethod::IoUring => &self.io_uring,
+            CopyMethod::Reflink => &self.reflink,
+            CopyMethod::Hardlink => &self.hardlink,
+            CopyMethod::Symlink => &self.symlink,
+        }
+    }
+
+    /// Number of files whose bytes were copied rather than cloned
+    pub fn copied(&self) -> u64 {
+        self.get(CopyMethod::CopyFileRange)
+            + self.get(CopyMethod::Sendfile)
+            + self.get(CopyMethod::Buffered)
+            + self.get(CopyMethod::IoUring)
+    }
+
+    /// Number of files materialized as hard links or symlinks
+    pub fn linked(&self) -> u64 {
+        self.get(CopyMethod::Hardlink) + self.get(CopyMethod::Symlink)
+    }
+}
 
-    let src_file = File::open(src).map_err(|e| DeployError::CopyFailed {
+/// Map an IO error during copy to DiskFull, PermissionDenied or CopyFailed
+fn copy_error(src: &Path, dst: &Path, e: std::io::Error) -> DeployError {
+    if is_disk_full_error(&e) {
+        return DeployError::DiskFull {
+            path: dst.to_path_buf(),
+        };
+    }
+    if e.kind() == std::io::ErrorKind::PermissionDenied {
+        return DeployError::PermissionDenied {
+            path: dst.to_path_buf(),
+            source: e,
+        };
+    }
+    DeployError::CopyFailed {
         src: src.to_path_buf(),
         dst: dst.to_path_buf(),
         source: e,
-    })?;
-    let dst_file = File::create(dst).map_err(|e| {
-        if is_disk_full_error(&e) {
-            return DeployError::DiskFull {
-                path: dst.to_path_buf(),
+    }
+}
+
+/// Map an error opening the source to PermissionDenied or CopyFailed
+fn open_error(src: &Path, dst: &Path, e: std::io::Error) -> DeployError {
+    if e.kind() == std::io::ErrorKind::PermissionDenied {
+        return DeployError::PermissionDenied {
+            path: src.to_path_buf(),
+            source: e,
+        };
+    }
+    DeployError::CopyFailed {
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        source: e,
+    }
+}
+
+/// Kernel-side copy fast path for Linux.
+///
+/// Tries `copy_file_range(2)` first, then `sendfile(2)`. Returns `Ok(None)`
+/// when neither syscall is usable for this pair of files (e.g. EXDEV on
+/// kernels before 5.3, ENOSYS, or a filesystem that rejects the call) so
+/// the caller can fall back to the buffered loop. Fallback only happens
+/// before any byte was written; later failures are real errors.
+#[cfg(target_os = "linux")]
+mod kernel_copy {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    use super::CopyMethod;
+
+    /// Largest chunk handed to the kernel per call (1GB, well below the
+    /// 0x7ffff000 limit both syscalls apply)
+    const MAX_CHUNK: u64 = 1 << 30;
+
+    /// Errors that mean "this syscall can't do this copy", not "the copy failed"
+    #[inline]
+    fn is_unsupported(e: &io::Error) -> bool {
+        matches!(
+            e.raw_os_error(),
+            Some(libc::EXDEV)
+                | Some(libc::ENOSYS)
+                | Some(libc::EOPNOTSUPP)
+                | Some(libc::EINVAL)
+                | Some(libc::EPERM)
+        )
+    }
+
+    pub(super) fn copy(src: &File, dst: &File, len: u64) -> io::Result<Option<(u64, CopyMethod)>> {
+        if let Some(bytes) = copy_loop(len, |chunk| {
+            // SAFETY: both descriptors are owned by live `File`s for the
+            // duration of the call; null offsets make the kernel use and
+            // advance the file positions.
+            unsafe {
+                libc::copy_file_range(
+                    src.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    dst.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    chunk,
+                    0,
+                )
+            }
+        })? {
+            return Ok(Some((bytes, CopyMethod::CopyFileRange)));
+        }
+
+        if let Some(bytes) = copy_loop(len, |chunk| {
+            // SAFETY: as above; a null offset makes sendfile read from and
+            // advance the source file position.
+            unsafe {
+                libc::sendfile(
+                    dst.as_raw_fd(),
+                    src.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    chunk,
+                )
+            }
+        })? {
+            return Ok(Some((bytes, CopyMethod::Sendfile)));
+        }
+
+        Ok(None)
+    }
+
+    /// Drive a copy syscall until `len` bytes are transferred or EOF.
+    /// Returns `Ok(None)` if the very first call reports the syscall as
+    /// unsupported, or transfers nothing of a non-empty file (procfs, sysfs
+    /// and some FUSE filesystems report 0 instead of failing).
+    pub(super) fn copy_loop(
+        len: u64,
+        mut call: impl FnMut(usize) -> isize,
+    ) -> io::Result<Option<u64>> {
+        let mut total = 0u64;
+
+        while total < len {
+            let chunk = (len - total).min(MAX_CHUNK) as usize;
+            let ret = call(chunk);
+
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                if total == 0 && is_unsupported(&err) {
+                    return Ok(None);
+                }
+                return Err(err);
+            }
+            if ret == 0 {
+                if total == 0 {
+                    return Ok(None);
+                }
+                // Source shrank while copying
+                break;
+            }
+
+            total += ret as u64;
+        }
+
+        Ok(Some(total))
+    }
+}
+
+/// Extent cloning with the `FICLONE` ioctl (btrfs, XFS with reflink, ...)
+#[cfg(target_os = "linux")]
+mod reflink {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    /// Outcome of a clone attempt
+    pub(super) enum Clone {
+        /// Destination now shares the source's extents
+        Done,
+        /// This file can't be cloned (e.g. EXDEV, EINVAL); copy it instead
+        Fallback,
+        /// The destination filesystem doesn't implement FICLONE at all
+        Unsupported,
+    }
+
+    pub(super) fn clone(src: &File, dst: &File) -> io::Result<Clone> {
+        // SAFETY: both descriptors are owned by live `File`s for the
+        // duration of the call; FICLONE takes the source fd by value.
+        let ret = unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) };
+        if ret == 0 {
+            return Ok(Clone::Done);
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOTTY) | Some(libc::ENOSYS) => {
+                Ok(Clone::Unsupported)
+            }
+            Some(libc::EXDEV) | Some(libc::EINVAL) | Some(libc::EPERM) | Some(libc::EBADF) => {
+                Ok(Clone::Fallback)
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+/// Batched small-file copies through io_uring (`io-uring` feature).
+///
+/// Per-file syscalls dominate when deploying tens of thousands of small
+/// assets, so a batch goes through the ring in phases, each submitted at
+/// once: unlink destinations, open sources and destinations, read, write,
+/// close. Files larger than the per-file buffer finish with positional
+/// read/write on the already open descriptors.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring {
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::FileExt;
+    use std::os::unix::io::FromRawFd;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use io_uring::{opcode, squeue, types, IoUring, Probe};
+
+    use super::{
+        copy_error, ensure_parent_dir, replace_file, unlink_if_shared, CopyMethod, CopyOptions,
+        CopyStats, LinkMode, INSTALL_BATCH_SIZE,
+    };
+    use crate::error::DeployError;
+
+    /// Bytes read per file in the batched phase
+    const BUFFER_SIZE: usize = 64 * 1024;
+    /// Room for opening every source and destination of a batch at once
+    const RING_ENTRIES: u32 = (INSTALL_BATCH_SIZE * 2) as u32;
+
+    /// Set once io_uring setup failed; later batches skip straight to Std
+    static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+    struct Ring {
+        ring: IoUring,
+        /// IORING_OP_UNLINKAT needs Linux 5.11
+        unlink: bool,
+        buffers: Vec<Vec<u8>>,
+    }
+
+    thread_local! {
+        static RING: RefCell<Option<Ring>> = const { RefCell::new(None) };
+    }
+
+    fn setup() -> io::Result<Option<Ring>> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let mut probe = Probe::new();
+        ring.submitter().register_probe(&mut probe)?;
+
+        let required = [
+            opcode::OpenAt::CODE,
+            opcode::Read::CODE,
+            opcode::Write::CODE,
+            opcode::Close::CODE,
+        ];
+        if !required.iter().all(|&code| probe.is_supported(code)) {
+            return Ok(None);
+        }
+
+        Ok(Some(Ring {
+            unlink: probe.is_supported(opcode::UnlinkAt::CODE),
+            ring,
+            buffers: (0..INSTALL_BATCH_SIZE)
+                .map(|_| vec![0u8; BUFFER_SIZE])
+                .collect(),
+        }))
+    }
+
+    /// Install `files`, copying the plain ones through the ring.
+    /// Returns `None` (nothing touched) when io_uring isn't usable.
+    pub(super) fn install(
+        files: &[(PathBuf, PathBuf, LinkMode)],
+        options: &CopyOptions,
+        stats: &CopyStats,
+    ) -> Option<Vec<Result<u64, DeployError>>> {
+        if UNAVAILABLE.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        RING.with(|cell| {
+            let mut slot = cell.borrow_mut();
+            if slot.is_none() {
+                match setup() {
+                    Ok(Some(ring)) => *slot = Some(ring),
+                    Ok(None) | Err(_) => {
+                        UNAVAILABLE.store(true, Ordering::Relaxed);
+                        return None;
+                    }
+                }
+            }
+            let ring = slot.as_mut()?;
+
+            let copies: Vec<usize> = (0..files.len())
+                .filter(|&i| files[i].2 == LinkMode::Copy)
+                .collect();
+            let mut results: Vec<Option<Result<u64, DeployError>>> =
+                (0..files.len()).map(|_| None).collect();
+
+            for chunk in copies.chunks(INSTALL_BATCH_SIZE) {
+                let batch: Vec<(&Path, &Path)> = chunk
+                    .iter()
+                    .map(|&i| (files[i].0.as_path(), files[i].1.as_path()))
+                    .collect();
+                match copy_batch(ring, &batch, options, stats) {
+                    Ok(copied) => {
+                        for (&i, result) in chunk.iter().zip(copied) {
+                            results[i] = Some(result);
+                        }
+                    }
+                    Err(_) => {
+                        // The ring may still reference our buffers: never free them
+                        if let Some(broken) = slot.take() {
+                            std::mem::forget(broken);
+                        }
+                        UNAVAILABLE.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+
+            Some(
+                files
+                    .iter()
+                    .zip(results)
+                    .map(|((src, dst, link_mode), result)| {
+                        result.unwrap_or_else(|| replace_file(src, dst, *link_mode, options, stats))
+                    })
+                    .collect(),
+            )
+        })
+    }
+
+    /// One file moving through the phases
+    struct Slot<'a> {
+        src: &'a Path,
+        dst: &'a Path,
+        src_path: CString,
+        dst_path: CString,
+        src_fd: i32,
+        dst_fd: i32,
+        /// Source size when the batch started
+        size: u64,
+        read: usize,
+        /// Creating the destination directory failed (kept as is, e.g. DiskFull)
+        dir_error: Option<DeployError>,
+        error: Option<io::Error>,
+    }
+
+    impl Slot<'_> {
+        fn active(&self) -> bool {
+            self.dir_error.is_none() && self.error.is_none()
+        }
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Copy a batch through the ring. An `Err` means the ring itself failed;
+    /// files not yet copied are left to the caller.
+    fn copy_batch(
+        ring: &mut Ring,
+        files: &[(&Path, &Path)],
+        options: &CopyOptions,
+        stats: &CopyStats,
+    ) -> io::Result<Vec<Result<u64, DeployError>>> {
+        let mut dirs_ready: HashSet<&Path> = HashSet::new();
+        let mut slots: Vec<Slot> = files
+            .iter()
+            .map(|&(src, dst)| {
+                let mut dir_error = None;
+                let mut error = None;
+                if let Some(parent) = dst.parent() {
+                    if !dirs_ready.contains(parent) {
+                        match ensure_parent_dir(dst, &options.attrs) {
+                            Ok(()) => {
+                                dirs_ready.insert(parent);
+                            }
+                            Err(e) => dir_error = Some(e),
+                        }
+                    }
+                }
+                let size = match std::fs::metadata(src) {
+                    Ok(meta) => meta.len(),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        0
+                    }
+                };
+                let (src_path, dst_path) = match (c_path(src), c_path(dst)) {
+                    (Ok(s), Ok(d)) => (s, d),
+                    (Err(e), _) | (_, Err(e)) => {
+                        error.get_or_insert(e);
+                        (CString::default(), CString::default())
+                    }
+                };
+                Slot {
+                    src,
+                    dst,
+                    src_path,
+                    dst_path,
+                    src_fd: -1,
+                    dst_fd: -1,
+                    size,
+                    read: 0,
+                    dir_error,
+                    error,
+                }
+            })
+            .collect();
+
+        if let Some(limit) = &options.rate_limit {
+            let bytes = slots.iter().map(|slot| slot.size).sum();
+            limit.acquire(files.len() as u64, bytes);
+        }
+
+        // Phase 1: drop previous outputs so a create can never write
+        // through a symlink or hard link left by a linked deploy
+        if ring.unlink {
+            let targets: Vec<usize> = (0..slots.len()).filter(|&i| slots[i].active()).collect();
+            let entries: Vec<squeue::Entry> = targets
+                .iter()
+                .map(|&i| {
+                    opcode::UnlinkAt::new(types::Fd(libc::AT_FDCWD), slots[i].dst_path.as_ptr())
+                        .build()
+                })
+                .collect();
+            for (&i, res) in targets.iter().zip(submit_all(&mut ring.ring, entries)?) {
+                if res < 0 && res != -libc::ENOENT {
+                    slots[i].error = Some(io::Error::from_raw_os_error(-res));
+                }
+            }
+        } else {
+            for slot in slots.iter_mut().filter(|s| s.active()) {
+                if let Err(e) = unlink_if_shared(slot.dst) {
+                    slot.error = Some(e);
+                }
+            }
+        }
+
+        // Phase 2: open sources and destinations
+        let targets: Vec<usize> = (0..slots.len()).filter(|&i| slots[i].active()).collect();
+        let mut entries = Vec::with_capacity(targets.len() * 2);
+        for &i in &targets {
+            entries.push(
+                opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), slots[i].src_path.as_ptr())
+                    .flags(libc::O_RDONLY | libc::O_CLOEXEC)
+                    .build(),
+            );
+            entries.push(
+                opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), slots[i].dst_path.as_ptr())
+                    .flags(
+                        libc::O_WRONLY
+                            | libc::O_CREAT
+                            | libc::O_TRUNC
+                            | libc::O_CLOEXEC
+                            | libc::O_NOFOLLOW,
+                    )
+                    .mode(0o666)
+                    .build(),
+            );
+        }
+        let mut opened = vec![-libc::ECANCELED; entries.len()];
+        if let Err(e) = submit_into(&mut ring.ring, entries, &mut opened) {
+            for &fd in opened.iter().filter(|&&fd| fd >= 0) {
+                // SAFETY: fd was opened by this batch and nothing else holds it
+                unsafe { libc::close(fd) };
+            }
+            return Err(e);
+        }
+        for (&i, fds) in targets.iter().zip(opened.chunks(2)) {
+            let slot = &mut slots[i];
+            slot.src_fd = fds[0];
+            slot.dst_fd = fds[1];
+            if let Some(&res) = fds.iter().find(|&&fd| fd < 0) {
+                slot.error = Some(io::Error::from_raw_os_error(-res));
+            }
+        }
+
+        let result = transfer(ring, &mut slots);
+
+        // Phase 5: close everything that was opened, even after errors
+        let mut fds: Vec<(usize, i32)> = Vec::with_capacity(slots.len() * 2);
+        for (i, slot) in slots.iter().enumerate() {
+            if slot.src_fd >= 0 {
+                fds.push((usize::MAX, slot.src_fd));
+            }
+            if slot.dst_fd >= 0 {
+                fds.push((i, slot.dst_fd));
+            }
+        }
+        let entries: Vec<squeue::Entry> = fds
+            .iter()
+            .map(|&(_, fd)| opcode::Close::new(types::Fd(fd)).build())
+            .collect();
+        match submit_all(&mut ring.ring, entries) {
+            Ok(closed) => {
+                for (&(i, _), res) in fds.iter().zip(closed) {
+                    // Close errors on a destination can mean lost writes
+                    if res < 0 && i != usize::MAX && slots[i].active() {
+                        slots[i].error = Some(io::Error::from_raw_os_error(-res));
+                    }
+                }
+            }
+            Err(e) => {
+                for &(_, fd) in &fds {
+                    // SAFETY: fd was opened by this batch and is closed exactly once
+                    unsafe { libc::close(fd) };
+                }
+                return Err(e);
+            }
+        }
+        result?;
+
+        Ok(slots
+            .into_iter()
+            .map(|slot| match (slot.dir_error, slot.error) {
+                (Some(e), _) => Err(e),
+                (None, Some(e)) => Err(copy_error(slot.src, slot.dst, e)),
+                (None, None) => options.attrs.apply_file(slot.src, slot.dst).map(|()| {
+                    stats.record(CopyMethod::IoUring);
+                    slot.read as u64
+                }),
+            })
+            .collect())
+    }
+
+    /// Phases 3 and 4: read each source into its buffer, write it out, and
+    /// finish with positional I/O the files that read didn't cover (larger
+    /// than the buffer, or a short read).
+    fn transfer(ring: &mut Ring, slots: &mut [Slot]) -> io::Result<()> {
+        let targets: Vec<usize> = (0..slots.len()).filter(|&i| slots[i].active()).collect();
+        let entries: Vec<squeue::Entry> = targets
+            .iter()
+            .map(|&i| {
+                opcode::Read::new(
+                    types::Fd(slots[i].src_fd),
+                    ring.buffers[i].as_mut_ptr(),
+                    BUFFER_SIZE as u32,
+                )
+                .offset(0)
+                .build()
+            })
+            .collect();
+        for (&i, res) in targets.iter().zip(submit_all(&mut ring.ring, entries)?) {
+            if res < 0 {
+                slots[i].error = Some(io::Error::from_raw_os_error(-res));
+            } else {
+                slots[i].read = res as usize;
+            }
+        }
+
+        let targets: Vec<usize> = (0..slots.len())
+            .filter(|&i| slots[i].active() && slots[i].read > 0)
+            .collect();
+        let entries: Vec<squeue::Entry> = targets
+            .iter()
+            .map(|&i| {
+                opcode::Write::new(
+                    types::Fd(slots[i].dst_fd),
+                    ring.buffers[i].as_ptr(),
+                    slots[i].read as u32,
+                )
+                .offset(0)
+                .build()
+            })
+            .collect();
+        for (&i, res) in targets.iter().zip(submit_all(&mut ring.ring, entries)?) {
+            let slot = &mut slots[i];
+            if res < 0 {
+                slot.error = Some(io::Error::from_raw_os_error(-res));
+                continue;
+            }
+            let written = res as usize;
+            let full_buffer = slot.read == BUFFER_SIZE;
+            if written < slot.read || (slot.read as u64) < slot.size || full_buffer {
+                if let Err(e) = finish(slot, &ring.buffers[i], written) {
+                    slot.error = Some(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Complete a short write and copy whatever didn't fit in the buffer
+    fn finish(slot: &mut Slot, buffer: &[u8], written: usize) -> io::Result<()> {
+        // SAFETY: both fds are open and owned by the batch, which closes them;
+        // ManuallyDrop keeps File from closing them a second time
+        let src = ManuallyDrop::new(unsafe { File::from_raw_fd(slot.src_fd) });
+        let dst = ManuallyDrop::new(unsafe { File::from_raw_fd(slot.dst_fd) });
+
+        dst.write_all_at(&buffer[written..slot.read], written as u64)?;
+
+        let mut offset = slot.read as u64;
+        let mut chunk = vec![0u8; BUFFER_SIZE];
+        loop {
+            let n = src.read_at(&mut chunk, offset)?;
+            if n == 0 {
+                break;
+            }
+            dst.write_all_at(&chunk[..n], offset)?;
+            offset += n as u64;
+        }
+
+        slot.read = offset as usize;
+        Ok(())
+    }
+
+    /// Submit `entries` and wait for all completions; results are in entry
+    /// order (negative values are `-errno`).
+    fn submit_all(ring: &mut IoUring, entries: Vec<squeue::Entry>) -> io::Result<Vec<i32>> {
+        let mut results = vec![-libc::ECANCELED; entries.len()];
+        submit_into(ring, entries, &mut results)?;
+        Ok(results)
+    }
+
+    /// `submit_all` into `results`, which keeps the completions received
+    /// before an error.
+    fn submit_into(
+        ring: &mut IoUring,
+        entries: Vec<squeue::Entry>,
+        results: &mut [i32],
+    ) -> io::Result<()> {
+        for (base, chunk) in entries
+            .chunks(RING_ENTRIES as usize)
+            .enumerate()
+            .map(|(n, chunk)| (n * RING_ENTRIES as usize, chunk))
+        {
+            for (offset, entry) in chunk.iter().enumerate() {
+                let entry = entry.clone().user_data((base + offset) as u64);
+                // SAFETY: paths and buffers referenced by the entry outlive
+                // this call, which waits for every completion before returning
+                unsafe { ring.submission().push(&entry) }
+                    .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+            }
+
+            let mut done = 0;
+            while done < chunk.len() {
+                match ring.submit_and_wait(chunk.len() - done) {
+                    Ok(_) => {}
+                    Err(e) if e.raw_os_error() == Some(libc::EINTR) => continue,
+                    Err(e) => return Err(e),
+                }
+                for cqe in ring.completion() {
+                    if let Some(slot) = results.get_mut(cqe.user_data() as usize) {
+                        *slot = cqe.result();
+                    }
+                    done += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Try to clone `src_file` into `dst_file` according to the copy mode.
+/// Returns the cloned length, or `None` if the caller should copy bytes.
+#[cfg(target_os = "linux")]
+fn try_reflink(
+    src_file: &File,
+    dst_file: &File,
+    len: u64,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> std::io::Result<Option<u64>> {
+    use std::os::unix::fs::MetadataExt;
+
+    match options.copy_mode {
+        CopyMode::Copy => return Ok(None),
+        CopyMode::Reflink => {}
+        CopyMode::Auto => {
+            if stats.reflink_unsupported.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            // FICLONE only works within one filesystem
+            let same_device = match (src_file.metadata(), dst_file.metadata()) {
+                (Ok(s), Ok(d)) => s.dev() == d.dev(),
+                _ => false,
             };
+            if !same_device {
+                return Ok(None);
+            }
         }
-        DeployError::CopyFailed {
-            src: src.to_path_buf(),
-            dst: dst.to_path_buf(),
-            source: e,
+    }
+
+    match reflink::clone(src_file, dst_file)? {
+        reflink::Clone::Done => Ok(Some(len)),
+        reflink::Clone::Fallback => Ok(None),
+        reflink::Clone::Unsupported => {
+            stats.reflink_unsupported.store(true, Ordering::Relaxed);
+            Ok(None)
         }
-    })?;
+    }
+}
+
+/// Copy all bytes from an opened source to an opened destination.
+/// Clones extents when the copy mode allows it, then uses the kernel fast
+/// path on Linux, and the buffered loop elsewhere or as fallback.
+fn copy_contents(
+    src: &Path,
+    src_file: File,
+    dst: &Path,
+    dst_file: File,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<(u64, CopyMethod), DeployError> {
+    #[cfg(target_os = "linux")]
+    {
+        // Empty (or unknown-size) files gain nothing from the syscalls
+        let len = src_file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len > 0 {
+            if let Some(bytes) = try_reflink(&src_file, &dst_file, len, options, stats)
+                .map_err(|e| copy_error(src, dst, e))?
+            {
+                return Ok((bytes, CopyMethod::Reflink));
+            }
+
+            if let Some(result) =
+                kernel_copy::copy(&src_file, &dst_file, len).map_err(|e| copy_error(src, dst, e))?
+            {
+                return Ok(result);
+            }
+        }
+    }
 
     let mut reader = BufReader::with_capacity(COPY_BUFFER_SIZE, src_file);
     let mut writer = BufWriter::with_capacity(COPY_BUFFER_SIZE, dst_file);
@@ -240,28 +1308,15 @@ pub fn copy_file(src: &Path, dst: &Path) -> Result<u64, DeployError> {
         loop {
             let bytes_read = reader
                 .read(&mut buffer[..])
-                .map_err(|e| DeployError::CopyFailed {
-                    src: src.to_path_buf(),
-                    dst: dst.to_path_buf(),
-                    source: e,
-                })?;
+                .map_err(|e| copy_error(src, dst, e))?;
 
             if bytes_read == 0 {
                 break;
             }
 
-            writer.write_all(&buffer[..bytes_read]).map_err(|e| {
-                if is_disk_full_error(&e) {
-                    return DeployError::DiskFull {
-                        path: dst.to_path_buf(),
-                    };
-                }
-                DeployError::CopyFailed {
-                    src: src.to_path_buf(),
-                    dst: dst.to_path_buf(),
-                    source: e,
-                }
-            })?;
+            writer
+                .write_all(&buffer[..bytes_read])
+                .map_err(|e| copy_error(src, dst, e))?;
 
             total += bytes_read as u64;
         }
@@ -269,45 +1324,457 @@ pub fn copy_file(src: &Path, dst: &Path) -> Result<u64, DeployError> {
         Ok(total)
     })?;
 
-    writer.flush().map_err(|e| DeployError::CopyFailed {
-        src: src.to_path_buf(),
-        dst: dst.to_path_buf(),
-        source: e,
+    writer.flush().map_err(|e| copy_error(src, dst, e))?;
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (options, stats);
+
+    Ok((total_bytes, CopyMethod::Buffered))
+}
+
+/// Copy a single file from src to dst using the fastest available method
+pub fn copy_file(src: &Path, dst: &Path) -> Result<u64, DeployError> {
+    copy_file_with_method(src, dst, &CopyOptions::default(), &CopyStats::new())
+        .map(|(bytes, _)| bytes)
+}
+
+/// Copy a single file according to `options`, record and return the method used
+pub fn copy_file_with_method(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<(u64, CopyMethod), DeployError> {
+    // Create parent directory if needed
+    ensure_parent_dir(dst, &options.attrs)?;
+    options.throttle_copy(src);
+
+    let src_file = File::open(src).map_err(|e| open_error(src, dst, e))?;
+    // Never truncate through a link left by a previous hardlink/symlink deploy
+    unlink_if_shared(dst).map_err(|e| copy_error(src, dst, e))?;
+    let dst_file = File::create(dst).map_err(|e| copy_error(src, dst, e))?;
+
+    let (bytes, method) = copy_contents(src, src_file, dst, dst_file, options, stats)?;
+    options.attrs.apply_file(src, dst)?;
+    stats.record(method);
+    Ok((bytes, method))
+}
+
+/// Write `contents`, generated from `src` (e.g. minified), to `dst`,
+/// replacing whatever is there. Returns the bytes written.
+pub fn write_file(
+    src: &Path,
+    dst: &Path,
+    contents: &[u8],
+    options: &CopyOptions,
+) -> Result<u64, DeployError> {
+    ensure_parent_dir(dst, &options.attrs)?;
+    options.throttle(1, contents.len() as u64);
+    unlink_if_shared(dst).map_err(|e| copy_error(src, dst, e))?;
+    fs::write(dst, contents).map_err(|e| copy_error(src, dst, e))?;
+    options.attrs.apply_file(src, dst)?;
+    Ok(contents.len() as u64)
+}
+
+/// Internal implementation for parallel directory copying
+/// skip_existing: if true, skip files that already exist at destination
+fn copy_directory_impl(
+    src: &Path,
+    dst: &Path,
+    shutdown: &AtomicBool,
+    options: &CopyOptions,
+    skip_existing: bool,
+    stats: &CopyStats,
+) -> Result<(u64, u64), DeployError> {
+    // Check for early cancellation
+    if shutdown.load(Ordering::Relaxed) {
+        return Err(DeployError::Cancelled);
+    }
+
+    // Collect all file entries first for parallel processing
+    let entries: Vec<_> = WalkDir::new(src)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !should_exclude_file(e.path(), options.include_dev))
+        .collect();
+
+    // Hard links can't cross devices: copy such sources instead
+    let link_mode = effective_link_mode(src, dst, options.link_mode);
+    // Symlinks must point at absolute source paths
+    let link_root = if link_mode == LinkMode::Symlink && !src.is_absolute() {
+        std::env::current_dir()?.join(src)
+    } else {
+        src.to_path_buf()
+    };
+
+    // Atomic counters for parallel aggregation
+    let files_copied = AtomicU64::new(0);
+    let bytes_copied = AtomicU64::new(0);
+
+    // Process files in parallel using Rayon
+    let result: Result<(), DeployError> = entries.par_iter().try_for_each(|entry| {
+        // Check for cancellation
+        if shutdown.load(Ordering::Relaxed) {
+            return Err(DeployError::Cancelled);
+        }
+
+        let src_path = entry.path();
+        let relative = src_path.strip_prefix(src).unwrap_or(src_path);
+        let dst_path = dst.join(relative);
+
+        if link_mode != LinkMode::Copy {
+            ensure_parent_dir(&dst_path, &options.attrs)?;
+            match link_file(
+                &link_root.join(relative),
+                &dst_path,
+                link_mode,
+                skip_existing,
+            )? {
+                LinkOutcome::Linked(method) => {
+                    stats.record(method);
+                    files_copied.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                // Higher priority source already materialized this file
+                LinkOutcome::Exists => return Ok(()),
+                LinkOutcome::Fallback => {}
+            }
+        }
+
+        // Skip if destination already exists and skip_existing is true
+        if skip_existing {
+            // Use create_new to atomically check existence and create in one syscall
+            ensure_parent_dir(&dst_path, &options.attrs)?;
+
+            // Try to create file exclusively - if it exists, skip
+            // (higher priority source already copied)
+            if let Some(bytes) = copy_file_exclusive(src_path, &dst_path, options, stats)? {
+                files_copied.fetch_add(1, Ordering::Relaxed);
+                bytes_copied.fetch_add(bytes, Ordering::Relaxed);
+            }
+        } else {
+            // Normal copy (overwrite if exists)
+            let (bytes, _) = copy_file_with_method(src_path, &dst_path, options, stats)?;
+            files_copied.fetch_add(1, Ordering::Relaxed);
+            bytes_copied.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        Ok(())
+    });
+
+    result?;
+
+    Ok((
+        files_copied.load(Ordering::Relaxed),
+        bytes_copied.load(Ordering::Relaxed),
+    ))
+}
+
+/// List files under `src` as paths relative to it, sorted by name.
+/// Development files are left out unless `include_dev` is set.
+///
+/// Symlinks are followed like `copy_directory` does, so a symlinked web
+/// directory (e.g. a package linked in by composer) still deploys its files.
+/// WalkDir reports a link back to an ancestor as an error entry, which is
+/// skipped, so cycles don't recurse.
+pub fn list_files(src: &Path, include_dev: bool) -> Vec<PathBuf> {
+    WalkDir::new(src)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !should_exclude_file(e.path(), include_dev))
+        .filter_map(|e| e.path().strip_prefix(src).ok().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Materialize a single planned file at `dst`, replacing whatever is there.
+///
+/// Override resolution already happened in the plan, so this is a plain
+/// create. `link_mode` should come from `effective_link_mode` for the file's
+/// source root, and `src` must be absolute in Symlink mode.
+/// Returns the bytes written (0 for links), or `None` when `skip_identical`
+/// left an identical `dst` in place.
+pub fn install_file(
+    src: &Path,
+    dst: &Path,
+    link_mode: LinkMode,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<Option<u64>, DeployError> {
+    if skip_if_identical(src, dst, link_mode, options, stats) {
+        return Ok(None);
+    }
+    replace_file(src, dst, link_mode, options, stats).map(Some)
+}
+
+/// `install_file` without the `skip_identical` check
+fn replace_file(
+    src: &Path,
+    dst: &Path,
+    link_mode: LinkMode,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<u64, DeployError> {
+    if link_mode != LinkMode::Copy {
+        ensure_parent_dir(dst, &options.attrs)?;
+        options.throttle(1, 0);
+        match link_file(src, dst, link_mode, false)? {
+            LinkOutcome::Linked(method) => {
+                stats.record(method);
+                return Ok(0);
+            }
+            LinkOutcome::Exists | LinkOutcome::Fallback => {}
+        }
+    }
+
+    let (bytes, _) = copy_file_with_method(src, dst, options, stats)?;
+    Ok(bytes)
+}
+
+/// Files handed to `install_files` at once by the work queue
+pub const INSTALL_BATCH_SIZE: usize = 32;
+
+/// Materialize a batch of planned files, returning one result per file.
+///
+/// With the io_uring backend, plain copies go through the ring together;
+/// links, reflinks and everything else (or all files, when io_uring is
+/// unavailable at runtime) go through `install_file` one by one.
+/// Identical files skipped by `skip_identical` yield `None`.
+pub fn install_files(
+    files: &[(PathBuf, PathBuf, LinkMode)],
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Vec<Result<Option<u64>, DeployError>> {
+    if options.skip_identical {
+        let identical: Vec<bool> = files
+            .iter()
+            .map(|(src, dst, link_mode)| skip_if_identical(src, dst, *link_mode, options, stats))
+            .collect();
+        if identical.contains(&true) {
+            let rest: Vec<(PathBuf, PathBuf, LinkMode)> = files
+                .iter()
+                .zip(&identical)
+                .filter(|(_, &same)| !same)
+                .map(|(file, _)| file.clone())
+                .collect();
+            let mut results = install_batch(&rest, options, stats).into_iter();
+            return identical
+                .into_iter()
+                .map(|same| {
+                    if same {
+                        Ok(None)
+                    } else {
+                        results.next().unwrap_or(Ok(0)).map(Some)
+                    }
+                })
+                .collect();
+        }
+    }
+
+    install_batch(files, options, stats)
+        .into_iter()
+        .map(|result| result.map(Some))
+        .collect()
+}
+
+/// `install_files` without the `skip_identical` check
+fn install_batch(
+    files: &[(PathBuf, PathBuf, LinkMode)],
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Vec<Result<u64, DeployError>> {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if options.backend == CopyBackend::IoUring && options.copy_mode == CopyMode::Copy {
+        if let Some(results) = uring::install(files, options, stats) {
+            return results;
+        }
+    }
+
+    files
+        .iter()
+        .map(|(src, dst, link_mode)| replace_file(src, dst, *link_mode, options, stats))
+        .collect()
+}
+
+/// With `skip_identical`, check whether a copy into `dst` can be skipped
+/// and count it if so. Links are always recreated.
+fn skip_if_identical(
+    src: &Path,
+    dst: &Path,
+    link_mode: LinkMode,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> bool {
+    if !options.skip_identical || link_mode != LinkMode::Copy {
+        return false;
+    }
+    if !files_identical(src, dst) {
+        return false;
+    }
+    stats.identical.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// Whether `dst` is a regular, unshared file with the same content as `src`.
+/// Sizes are compared first; only equal-sized files are hashed (xxh3).
+/// Any error counts as "different".
+pub fn files_identical(src: &Path, dst: &Path) -> bool {
+    let (Ok(src_meta), Ok(dst_meta)) = (fs::metadata(src), fs::symlink_metadata(dst)) else {
+        return false;
+    };
+    if !dst_meta.is_file() || src_meta.len() != dst_meta.len() {
+        return false;
+    }
+
+    // A hard link shared with another path is replaced rather than kept,
+    // like `unlink_if_shared` does for copies
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if dst_meta.nlink() > 1 {
+            return false;
+        }
+    }
+
+    match (content_hash(src), content_hash(dst)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// xxh3-64 of a file's content
+fn content_hash(path: &Path) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    COPY_BUFFER.with(|buf| -> std::io::Result<()> {
+        let mut buf = buf.borrow_mut();
+        loop {
+            let n = match file.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            hasher.update(&buf[..n]);
+        }
     })?;
+    Ok(hasher.digest())
+}
+
+/// Copy `src` to `dst` only if `dst` doesn't exist yet.
+/// Uses create_new to check existence and create in one syscall;
+/// returns `None` when the destination already exists.
+fn copy_file_exclusive(
+    src: &Path,
+    dst: &Path,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<Option<u64>, DeployError> {
+    match OpenOptions::new().write(true).create_new(true).open(dst) {
+        Ok(dst_file) => {
+            let (bytes, _) = copy_file_to_handle(src, dst, dst_file, options, stats)?;
+            Ok(Some(bytes))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(None),
+        Err(e) => Err(copy_error(src, dst, e)),
+    }
+}
 
-    Ok(total_bytes)
+/// Copy file content to an already-opened file handle
+fn copy_file_to_handle(
+    src: &Path,
+    dst: &Path,
+    dst_file: File,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<(u64, CopyMethod), DeployError> {
+    options.throttle_copy(src);
+    let src_file = File::open(src).map_err(|e| open_error(src, dst, e))?;
+
+    let (bytes, method) = copy_contents(src, src_file, dst, dst_file, options, stats)?;
+    options.attrs.apply_file(src, dst)?;
+    stats.record(method);
+    Ok((bytes, method))
 }
 
-/// Internal implementation for parallel directory copying
-/// skip_existing: if true, skip files that already exist at destination
-fn copy_directory_impl(
+/// Copy directory recursively, returns (files_copied, bytes_copied)
+#[allow(dead_code)]
+pub fn copy_directory(
     src: &Path,
     dst: &Path,
     shutdown: &AtomicBool,
-    include_dev: bool,
-    skip_existing: bool,
+    options: &CopyOptions,
+    stats: &CopyStats,
 ) -> Result<(u64, u64), DeployError> {
-    // Check for early cancellation
+    copy_directory_impl(src, dst, shutdown, options, false, stats)
+}
+
+/// Mirror an already deployed output tree into another output tree.
+///
+/// Used to fan one deployed locale out to the other locales of a theme.
+/// Regular files are hard linked when `link_mode` is Hardlink and copied
+/// otherwise, cloned where the filesystem allows even with the default
+/// `copy_mode`; symlinks are recreated with the same target so they keep
+/// pointing at the original sources.
+/// Destination files that already mirror theirs are left alone (counted as
+/// identical); other existing ones are replaced, like planned files.
+pub fn replicate_directory(
+    src: &Path,
+    dst: &Path,
+    shutdown: &AtomicBool,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<(u64, u64), DeployError> {
+    let options = CopyOptions {
+        keep_going: false,
+        ..options.clone()
+    };
+    replicate_directory_keep_going(src, dst, shutdown, &options, stats)
+        .map(|(files, bytes, _)| (files, bytes))
+}
+
+/// `replicate_directory` that, with `keep_going`, records files that fail
+/// (sorted by destination) and mirrors the rest.
+/// Returns (files written, bytes, failures).
+pub fn replicate_directory_keep_going(
+    src: &Path,
+    dst: &Path,
+    shutdown: &AtomicBool,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<(u64, u64, Vec<FileFailure>), DeployError> {
+    let failures = Mutex::new(Vec::new());
     if shutdown.load(Ordering::Relaxed) {
         return Err(DeployError::Cancelled);
     }
 
-    // Collect all file entries first for parallel processing
     let entries: Vec<_> = WalkDir::new(src)
-        .follow_links(true)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| !should_exclude_file(e.path(), include_dev))
+        .filter(|e| e.file_type().is_file() || e.path_is_symlink())
         .collect();
 
-    // Atomic counters for parallel aggregation
+    let link_mode = effective_link_mode(src, dst, options.link_mode);
+    // The primary is deployed output, not a source that may change under
+    // the clone: share its extents wherever the filesystem can
+    let auto;
+    let options = if options.copy_mode == CopyMode::Copy {
+        auto = CopyOptions {
+            copy_mode: CopyMode::Auto,
+            ..options.clone()
+        };
+        &auto
+    } else {
+        options
+    };
     let files_copied = AtomicU64::new(0);
     let bytes_copied = AtomicU64::new(0);
 
-    // Process files in parallel using Rayon
-    let result: Result<(), DeployError> = entries.par_iter().try_for_each(|entry| {
-        // Check for cancellation
+    entries.par_iter().try_for_each(|entry| {
         if shutdown.load(Ordering::Relaxed) {
             return Err(DeployError::Cancelled);
         }
@@ -316,118 +1783,129 @@ fn copy_directory_impl(
         let relative = src_path.strip_prefix(src).unwrap_or(src_path);
         let dst_path = dst.join(relative);
 
-        // Skip if destination already exists and skip_existing is true
-        if skip_existing {
-            // Use create_new to atomically check existence and create in one syscall
-            if let Some(parent) = dst_path.parent() {
-                if !parent.exists() {
-                    fs::create_dir_all(parent).map_err(|e| {
-                        if is_disk_full_error(&e) {
-                            return DeployError::DiskFull {
-                                path: parent.to_path_buf(),
-                            };
-                        }
-                        DeployError::CreateDirFailed {
-                            path: parent.to_path_buf(),
-                            source: e,
-                        }
-                    })?;
-                }
+        match replicate_entry(entry, &dst_path, link_mode, options, stats) {
+            Ok(Some(bytes)) => {
+                files_copied.fetch_add(1, Ordering::Relaxed);
+                bytes_copied.fetch_add(bytes, Ordering::Relaxed);
+                Ok(())
             }
-
-            // Try to create file exclusively - if it exists, skip
-            match OpenOptions::new()
-                .write(true)
-                .create_new(true)
-                .open(&dst_path)
-            {
-                Ok(dst_file) => {
-                    // File created, now copy content
-                    let bytes = copy_file_to_handle(src_path, dst_file)?;
-                    files_copied.fetch_add(1, Ordering::Relaxed);
-                    bytes_copied.fetch_add(bytes, Ordering::Relaxed);
-                }
-                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
-                    // File exists, skip it (higher priority source already copied)
-                }
-                Err(e) => {
-                    if is_disk_full_error(&e) {
-                        return Err(DeployError::DiskFull {
-                            path: dst_path.clone(),
-                        });
-                    }
-                    return Err(DeployError::CopyFailed {
+            Ok(None) => Ok(()),
+            Err(e) if options.keep_going => {
+                failures
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(FileFailure {
                         src: src_path.to_path_buf(),
                         dst: dst_path,
-                        source: e,
+                        error: e,
                     });
-                }
+                Ok(())
             }
-        } else {
-            // Normal copy (overwrite if exists)
-            let bytes = copy_file(src_path, &dst_path)?;
-            files_copied.fetch_add(1, Ordering::Relaxed);
-            bytes_copied.fetch_add(bytes, Ordering::Relaxed);
+            Err(e) => Err(e),
         }
+    })?;
 
-        Ok(())
-    });
-
-    result?;
+    let mut failures = failures
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+    failures.sort_unstable_by(|a, b| a.dst.cmp(&b.dst));
 
     Ok((
         files_copied.load(Ordering::Relaxed),
         bytes_copied.load(Ordering::Relaxed),
+        failures,
     ))
 }
 
-/// Copy file content to an already-opened file handle
-fn copy_file_to_handle(src: &Path, dst_file: File) -> Result<u64, DeployError> {
-    let src_file = File::open(src).map_err(|e| DeployError::CopyFailed {
-        src: src.to_path_buf(),
-        dst: src.to_path_buf(), // Not ideal but we don't have dst path here
-        source: e,
-    })?;
-
-    let mut reader = BufReader::with_capacity(COPY_BUFFER_SIZE, src_file);
-    let mut writer = BufWriter::with_capacity(COPY_BUFFER_SIZE, dst_file);
-
-    // Use thread-local buffer
-    let total_bytes = COPY_BUFFER.with(|buf| -> Result<u64, DeployError> {
-        let mut buffer = buf.borrow_mut();
-        let mut total = 0u64;
-
-        loop {
-            let bytes_read = reader.read(&mut buffer[..]).map_err(DeployError::Io)?;
-
-            if bytes_read == 0 {
-                break;
-            }
-
-            writer
-                .write_all(&buffer[..bytes_read])
-                .map_err(DeployError::Io)?;
-
-            total += bytes_read as u64;
+/// Mirror one file or symlink of a deployed tree; returns the bytes written,
+/// or `None` when `dst_path` already mirrors it
+fn replicate_entry(
+    entry: &walkdir::DirEntry,
+    dst_path: &Path,
+    link_mode: LinkMode,
+    options: &CopyOptions,
+    stats: &CopyStats,
+) -> Result<Option<u64>, DeployError> {
+    let src_path = entry.path();
+    if replica_current(entry, dst_path, link_mode, options) {
+        stats.identical.fetch_add(1, Ordering::Relaxed);
+        return Ok(None);
+    }
+    ensure_parent_dir(dst_path, &options.attrs)?;
+
+    let outcome = if entry.path_is_symlink() {
+        options.throttle(1, 0);
+        let target = fs::read_link(src_path).map_err(|e| copy_error(src_path, dst_path, e))?;
+        link_file(&target, dst_path, LinkMode::Symlink, false)?
+    } else if link_mode == LinkMode::Hardlink {
+        options.throttle(1, 0);
+        link_file(src_path, dst_path, LinkMode::Hardlink, false)?
+    } else {
+        LinkOutcome::Fallback
+    };
+
+    match outcome {
+        LinkOutcome::Linked(method) => {
+            stats.record(method);
+            Ok(Some(0))
         }
-
-        Ok(total)
-    })?;
-
-    writer.flush().map_err(DeployError::Io)?;
-
-    Ok(total_bytes)
+        LinkOutcome::Exists | LinkOutcome::Fallback => {
+            let (bytes, _) = copy_file_with_method(src_path, dst_path, options, stats)?;
+            Ok(Some(bytes))
+        }
+    }
 }
 
-/// Copy directory recursively, returns (files_copied, bytes_copied)
-#[allow(dead_code)]
-pub fn copy_directory(
-    src: &Path,
+/// Whether `dst` already mirrors `entry` of a deployed tree: the same
+/// symlink target, the same inode when hard linking, or otherwise a copy of
+/// the same size written since `entry` (with the same mtime when mtimes are
+/// preserved). A rewritten primary file is newer than its old replicas.
+fn replica_current(
+    entry: &walkdir::DirEntry,
     dst: &Path,
-    shutdown: &AtomicBool,
-    include_dev: bool,
-) -> Result<(u64, u64), DeployError> {
-    copy_directory_impl(src, dst, shutdown, include_dev, false)
+    link_mode: LinkMode,
+    options: &CopyOptions,
+) -> bool {
+    let Ok(dst_meta) = fs::symlink_metadata(dst) else {
+        return false;
+    };
+    if entry.path_is_symlink() {
+        return dst_meta.file_type().is_symlink()
+            && fs::read_link(entry.path()).ok() == fs::read_link(dst).ok();
+    }
+    let Ok(src_meta) = entry.metadata() else {
+        return false;
+    };
+    if !dst_meta.is_file() {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let same_inode = src_meta.dev() == dst_meta.dev() && src_meta.ino() == dst_meta.ino();
+        if link_mode == LinkMode::Hardlink {
+            return same_inode;
+        }
+        // A link left by a hardlink deploy is replaced by a copy
+        if same_inode || dst_meta.nlink() > 1 {
+            return false;
+        }
+    }
+    #[cfg(not(unix))]
+    if link_mode == LinkMode::Hardlink {
+        return false;
+    }
+
+    let (Ok(src_mtime), Ok(dst_mtime)) = (src_meta.modified(), dst_meta.modified()) else {
+        return false;
+    };
+    src_meta.len() == dst_meta.len()
+        && if options.attrs.preserve_mtime {
+            dst_mtime == src_mtime
+        } else {
+            dst_mtime >= src_mtime
+        }
 }
 
 /// Copy directory with override semantics (skip existing files)
@@ -436,9 +1914,10 @@ pub fn copy_directory_with_overrides(
     src: &Path,
     dst: &Path,
     shutdown: &AtomicBool,
-    include_dev: bool,
+    options: &CopyOptions,
+    stats: &CopyStats,
 ) -> Result<(u64, u64), DeployError> {
-    copy_directory_impl(src, dst, shutdown, include_dev, true)
+    copy_directory_impl(src, dst, shutdown, options, true, stats)
 }
 
 #[cfg(test)]
@@ -447,6 +1926,13 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    fn options(include_dev: bool) -> CopyOptions {
+        CopyOptions {
+            include_dev,
+            ..CopyOptions::default()
+        }
+    }
+
     // ==================== should_exclude_file tests ====================
 
     #[test]
@@ -528,81 +2014,426 @@ mod tests {
     }
 
     #[test]
-    fn test_should_exclude_file_case_insensitive_extension() {
-        let path = Path::new("/some/path/file.TS");
-        assert!(should_exclude_file(path, false));
-    }
+    fn test_should_exclude_file_case_insensitive_extension() {
+        let path = Path::new("/some/path/file.TS");
+        assert!(should_exclude_file(path, false));
+    }
+
+    // ==================== kernel_copy tests ====================
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_loop_zero_transfer_falls_back() {
+        // procfs-style: 0 bytes for a file stat reports as non-empty
+        assert_eq!(kernel_copy::copy_loop(10, |_| 0).unwrap(), None);
+
+        // Source shrank after the first chunk: keep what was copied
+        let mut calls = [4, 0].into_iter();
+        assert_eq!(
+            kernel_copy::copy_loop(10, |_| calls.next().unwrap()).unwrap(),
+            Some(4)
+        );
+        assert_eq!(kernel_copy::copy_loop(0, |_| 0).unwrap(), Some(0));
+    }
+
+    // ==================== copy_file tests ====================
+
+    #[test]
+    fn test_copy_file_success() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("source.txt");
+        let dst = temp.path().join("dest.txt");
+
+        fs::write(&src, "test content").unwrap();
+
+        let bytes = copy_file(&src, &dst).unwrap();
+
+        assert_eq!(bytes, 12);
+        assert!(dst.exists());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "test content");
+    }
+
+    #[test]
+    fn test_copy_file_creates_parent_directories() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("source.txt");
+        let dst = temp.path().join("nested/deep/dest.txt");
+
+        fs::write(&src, "content").unwrap();
+
+        let bytes = copy_file(&src, &dst).unwrap();
+
+        assert_eq!(bytes, 7);
+        assert!(dst.exists());
+    }
+
+    #[test]
+    fn test_copy_file_source_not_found() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("nonexistent.txt");
+        let dst = temp.path().join("dest.txt");
+
+        let result = copy_file(&src, &dst);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_copy_file_large_file() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("large.bin");
+        let dst = temp.path().join("large_copy.bin");
+
+        // Create 1MB file
+        let data = vec![0xABu8; 1024 * 1024];
+        fs::write(&src, &data).unwrap();
+
+        let bytes = copy_file(&src, &dst).unwrap();
+
+        assert_eq!(bytes, 1024 * 1024);
+        assert_eq!(fs::read(&dst).unwrap(), data);
+    }
+
+    #[test]
+    fn test_copy_file_empty_file() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("empty.txt");
+        let dst = temp.path().join("empty_copy.txt");
+
+        fs::write(&src, "").unwrap();
+
+        let bytes = copy_file(&src, &dst).unwrap();
+
+        assert_eq!(bytes, 0);
+        assert!(dst.exists());
+    }
+
+    #[test]
+    fn test_copy_file_with_method_matches_content() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("source.bin");
+        let dst = temp.path().join("dest.bin");
+
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&src, &data).unwrap();
+
+        let (bytes, method) =
+            copy_file_with_method(&src, &dst, &CopyOptions::default(), &CopyStats::new()).unwrap();
+
+        assert_eq!(bytes, data.len() as u64);
+        assert_eq!(fs::read(&dst).unwrap(), data);
+        if cfg!(not(target_os = "linux")) {
+            assert_eq!(method, CopyMethod::Buffered);
+        }
+    }
+
+    #[test]
+    fn test_copy_file_with_method_empty_file_is_buffered() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("empty.txt");
+        let dst = temp.path().join("empty_copy.txt");
+
+        fs::write(&src, "").unwrap();
+
+        let (bytes, method) =
+            copy_file_with_method(&src, &dst, &CopyOptions::default(), &CopyStats::new()).unwrap();
+
+        assert_eq!(bytes, 0);
+        assert_eq!(method, CopyMethod::Buffered);
+    }
+
+    #[test]
+    fn test_copy_file_overwrites_existing_destination() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("source.txt");
+        let dst = temp.path().join("dest.txt");
+
+        fs::write(&src, "new").unwrap();
+        fs::write(&dst, "much longer old content").unwrap();
+
+        copy_file(&src, &dst).unwrap();
+
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "new");
+    }
+
+    // ==================== CopyStats tests ====================
+
+    #[test]
+    fn test_copy_stats_record() {
+        let stats = CopyStats::new();
+        stats.record(CopyMethod::CopyFileRange);
+        stats.record(CopyMethod::CopyFileRange);
+        stats.record(CopyMethod::Buffered);
+
+        assert_eq!(stats.get(CopyMethod::CopyFileRange), 2);
+        assert_eq!(stats.get(CopyMethod::Sendfile), 0);
+        assert_eq!(stats.get(CopyMethod::Buffered), 1);
+    }
+
+    #[test]
+    fn test_copy_directory_records_methods() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let dst = temp.path().join("dst");
+
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("a.js"), "alpha").unwrap();
+        fs::write(src.join("b.js"), "beta").unwrap();
+        fs::write(src.join("c.js"), "").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = CopyStats::new();
+        let options = CopyOptions {
+            include_dev: true,
+            ..CopyOptions::default()
+        };
+        let (files, _) = copy_directory(&src, &dst, &shutdown, &options, &stats).unwrap();
+
+        // Every file is recorded under exactly one method; plain copy mode never clones
+        assert_eq!(files, 3);
+        assert_eq!(stats.copied(), 3);
+        assert_eq!(stats.get(CopyMethod::Reflink), 0);
+    }
+
+    #[test]
+    fn test_copy_method_as_str() {
+        assert_eq!(CopyMethod::CopyFileRange.as_str(), "copy_file_range");
+        assert_eq!(CopyMethod::Sendfile.as_str(), "sendfile");
+        assert_eq!(CopyMethod::Buffered.as_str(), "buffered");
+        assert_eq!(CopyMethod::Reflink.as_str(), "reflink");
+    }
+
+    // ==================== CopyMode tests ====================
+
+    #[test]
+    fn test_copy_mode_parse() {
+        assert_eq!(CopyMode::parse("copy"), Some(CopyMode::Copy));
+        assert_eq!(CopyMode::parse("reflink"), Some(CopyMode::Reflink));
+        assert_eq!(CopyMode::parse("auto"), Some(CopyMode::Auto));
+        assert_eq!(CopyMode::parse("clone"), None);
+        assert_eq!(CopyMode::default(), CopyMode::Copy);
+    }
+
+    #[test]
+    fn test_copy_mode_as_str_roundtrip() {
+        for mode in [CopyMode::Copy, CopyMode::Reflink, CopyMode::Auto] {
+            assert_eq!(CopyMode::parse(mode.as_str()), Some(mode));
+        }
+    }
+
+    #[test]
+    fn test_copy_file_reflink_mode_falls_back_or_clones() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("source.bin");
+        let dst = temp.path().join("dest.bin");
+        fs::write(&src, vec![7u8; 64 * 1024]).unwrap();
+
+        let opts = CopyOptions {
+            copy_mode: CopyMode::Reflink,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let (bytes, method) = copy_file_with_method(&src, &dst, &opts, &stats).unwrap();
+
+        // Cloned on btrfs/XFS, copied everywhere else; content must match either way
+        assert_eq!(bytes, 64 * 1024);
+        assert_eq!(fs::read(&dst).unwrap(), vec![7u8; 64 * 1024]);
+        assert_eq!(stats.get(method), 1);
+        assert_eq!(stats.get(CopyMethod::Reflink) + stats.copied(), 1);
+    }
+
+    #[test]
+    fn test_copy_file_auto_mode_copies_all_files() {
+        let temp = TempDir::new().unwrap();
+        let opts = CopyOptions {
+            copy_mode: CopyMode::Auto,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+
+        for i in 0..3 {
+            let src = temp.path().join(format!("src{i}.txt"));
+            let dst = temp.path().join(format!("dst{i}.txt"));
+            fs::write(&src, "auto mode content").unwrap();
+            copy_file_with_method(&src, &dst, &opts, &stats).unwrap();
+            assert_eq!(fs::read_to_string(&dst).unwrap(), "auto mode content");
+        }
 
-    // ==================== copy_file tests ====================
+        assert_eq!(stats.get(CopyMethod::Reflink) + stats.copied(), 3);
+    }
 
     #[test]
-    fn test_copy_file_success() {
+    fn test_copy_file_auto_mode_stops_after_unsupported() {
         let temp = TempDir::new().unwrap();
         let src = temp.path().join("source.txt");
         let dst = temp.path().join("dest.txt");
+        fs::write(&src, "content").unwrap();
 
-        fs::write(&src, "test content").unwrap();
+        let opts = CopyOptions {
+            copy_mode: CopyMode::Auto,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        stats.reflink_unsupported.store(true, Ordering::Relaxed);
 
-        let bytes = copy_file(&src, &dst).unwrap();
+        let (_, method) = copy_file_with_method(&src, &dst, &opts, &stats).unwrap();
 
-        assert_eq!(bytes, 12);
-        assert!(dst.exists());
-        assert_eq!(fs::read_to_string(&dst).unwrap(), "test content");
+        assert_ne!(method, CopyMethod::Reflink);
+        assert_eq!(stats.get(CopyMethod::Reflink), 0);
     }
 
-    #[test]
-    fn test_copy_file_creates_parent_directories() {
-        let temp = TempDir::new().unwrap();
-        let src = temp.path().join("source.txt");
-        let dst = temp.path().join("nested/deep/dest.txt");
+    // ==================== LinkMode tests ====================
 
-        fs::write(&src, "content").unwrap();
+    #[test]
+    fn test_link_mode_parse() {
+        assert_eq!(LinkMode::parse("copy"), Some(LinkMode::Copy));
+        assert_eq!(LinkMode::parse("hardlink"), Some(LinkMode::Hardlink));
+        assert_eq!(LinkMode::parse("symlink"), Some(LinkMode::Symlink));
+        assert_eq!(LinkMode::parse("link"), None);
+        assert_eq!(LinkMode::default(), LinkMode::Copy);
+    }
 
-        let bytes = copy_file(&src, &dst).unwrap();
+    #[test]
+    fn test_effective_link_mode_same_device() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        // Destination doesn't exist yet: its nearest ancestor decides the device
+        let dst = temp.path().join("not/yet/created");
 
-        assert_eq!(bytes, 7);
-        assert!(dst.exists());
+        assert_eq!(
+            effective_link_mode(&src, &dst, LinkMode::Hardlink),
+            LinkMode::Hardlink
+        );
+        assert_eq!(
+            effective_link_mode(&src, &dst, LinkMode::Symlink),
+            LinkMode::Symlink
+        );
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_copy_file_source_not_found() {
-        let temp = TempDir::new().unwrap();
-        let src = temp.path().join("nonexistent.txt");
-        let dst = temp.path().join("dest.txt");
+    fn test_copy_directory_with_overrides_hardlink_mode() {
+        use std::os::unix::fs::MetadataExt;
 
-        let result = copy_file(&src, &dst);
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let dst = temp.path().join("dst");
+        fs::create_dir_all(src.join("js")).unwrap();
+        fs::write(src.join("js/app.js"), "code").unwrap();
+
+        let opts = CopyOptions {
+            include_dev: true,
+            link_mode: LinkMode::Hardlink,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let shutdown = AtomicBool::new(false);
+        let (files, bytes) =
+            copy_directory_with_overrides(&src, &dst, &shutdown, &opts, &stats).unwrap();
 
-        assert!(result.is_err());
+        assert_eq!(files, 1);
+        assert_eq!(bytes, 0);
+        assert_eq!(stats.get(CopyMethod::Hardlink), 1);
+        let src_meta = fs::metadata(src.join("js/app.js")).unwrap();
+        let dst_meta = fs::metadata(dst.join("js/app.js")).unwrap();
+        assert_eq!(src_meta.ino(), dst_meta.ino());
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_copy_file_large_file() {
+    fn test_copy_directory_with_overrides_symlink_mode() {
         let temp = TempDir::new().unwrap();
-        let src = temp.path().join("large.bin");
-        let dst = temp.path().join("large_copy.bin");
+        let src = temp.path().join("src");
+        let dst = temp.path().join("dst");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("styles.css"), "body{}").unwrap();
+
+        let opts = CopyOptions {
+            include_dev: true,
+            link_mode: LinkMode::Symlink,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let shutdown = AtomicBool::new(false);
+        copy_directory_with_overrides(&src, &dst, &shutdown, &opts, &stats).unwrap();
+
+        let link = dst.join("styles.css");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let target = fs::read_link(&link).unwrap();
+        assert!(target.is_absolute());
+        assert_eq!(target, src.join("styles.css"));
+        assert_eq!(stats.get(CopyMethod::Symlink), 1);
+    }
 
-        // Create 1MB file
-        let data = vec![0xABu8; 1024 * 1024];
-        fs::write(&src, &data).unwrap();
+    #[cfg(unix)]
+    #[test]
+    fn test_link_modes_keep_override_priority() {
+        let temp = TempDir::new().unwrap();
+        let high = temp.path().join("high");
+        let low = temp.path().join("low");
+        let dst = temp.path().join("dst");
+        fs::create_dir_all(&high).unwrap();
+        fs::create_dir_all(&low).unwrap();
+        fs::write(high.join("app.js"), "theme").unwrap();
+        fs::write(low.join("app.js"), "vendor").unwrap();
+        fs::write(low.join("lib.js"), "library").unwrap();
+
+        for mode in [LinkMode::Hardlink, LinkMode::Symlink] {
+            let _ = fs::remove_dir_all(&dst);
+            let opts = CopyOptions {
+                include_dev: true,
+                link_mode: mode,
+                ..CopyOptions::default()
+            };
+            let stats = CopyStats::new();
+            let shutdown = AtomicBool::new(false);
 
-        let bytes = copy_file(&src, &dst).unwrap();
+            copy_directory_with_overrides(&high, &dst, &shutdown, &opts, &stats).unwrap();
+            let (files, _) =
+                copy_directory_with_overrides(&low, &dst, &shutdown, &opts, &stats).unwrap();
 
-        assert_eq!(bytes, 1024 * 1024);
-        assert_eq!(fs::read(&dst).unwrap(), data);
+            assert_eq!(files, 1);
+            assert_eq!(fs::read_to_string(dst.join("app.js")).unwrap(), "theme");
+            assert_eq!(fs::read_to_string(dst.join("lib.js")).unwrap(), "library");
+        }
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_copy_file_empty_file() {
+    fn test_copy_file_does_not_write_through_symlink() {
         let temp = TempDir::new().unwrap();
-        let src = temp.path().join("empty.txt");
-        let dst = temp.path().join("empty_copy.txt");
+        let original = temp.path().join("original.js");
+        let new_src = temp.path().join("new.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&original, "original").unwrap();
+        fs::write(&new_src, "replacement").unwrap();
+        std::os::unix::fs::symlink(&original, &dst).unwrap();
+
+        copy_file(&new_src, &dst).unwrap();
+
+        assert_eq!(fs::read_to_string(&original).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "replacement");
+        assert!(!fs::symlink_metadata(&dst).unwrap().file_type().is_symlink());
+    }
 
-        fs::write(&src, "").unwrap();
+    #[test]
+    fn test_copy_file_does_not_write_through_hardlink() {
+        let temp = TempDir::new().unwrap();
+        let original = temp.path().join("original.js");
+        let new_src = temp.path().join("new.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&original, "original").unwrap();
+        fs::write(&new_src, "replacement").unwrap();
+        fs::hard_link(&original, &dst).unwrap();
 
-        let bytes = copy_file(&src, &dst).unwrap();
+        copy_file(&new_src, &dst).unwrap();
 
-        assert_eq!(bytes, 0);
-        assert!(dst.exists());
+        assert_eq!(fs::read_to_string(&original).unwrap(), "original");
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "replacement");
     }
 
     // ==================== copy_directory tests ====================
@@ -618,7 +2449,8 @@ mod tests {
         fs::write(src.join("file2.txt"), "content2").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, bytes) = copy_directory(&src, &dst, &shutdown, true).unwrap();
+        let (files, bytes) =
+            copy_directory(&src, &dst, &shutdown, &options(true), &CopyStats::new()).unwrap();
 
         assert_eq!(files, 2);
         assert_eq!(bytes, 16);
@@ -638,7 +2470,8 @@ mod tests {
         fs::write(src.join("package.json"), "{}").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, _) = copy_directory(&src, &dst, &shutdown, false).unwrap();
+        let (files, _) =
+            copy_directory(&src, &dst, &shutdown, &options(false), &CopyStats::new()).unwrap();
 
         // Only app.js should be copied (not .ts or package.json)
         assert_eq!(files, 1);
@@ -658,7 +2491,8 @@ mod tests {
         fs::write(src.join("app.ts"), "typescript").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, _) = copy_directory(&src, &dst, &shutdown, true).unwrap();
+        let (files, _) =
+            copy_directory(&src, &dst, &shutdown, &options(true), &CopyStats::new()).unwrap();
 
         assert_eq!(files, 2);
         assert!(dst.join("app.js").exists());
@@ -675,7 +2509,7 @@ mod tests {
         fs::write(src.join("file.txt"), "content").unwrap();
 
         let shutdown = AtomicBool::new(true);
-        let result = copy_directory(&src, &dst, &shutdown, true);
+        let result = copy_directory(&src, &dst, &shutdown, &options(true), &CopyStats::new());
 
         assert!(matches!(result, Err(DeployError::Cancelled)));
     }
@@ -692,7 +2526,8 @@ mod tests {
         fs::write(src.join("a/b/c/file3.txt"), "3").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, _) = copy_directory(&src, &dst, &shutdown, true).unwrap();
+        let (files, _) =
+            copy_directory(&src, &dst, &shutdown, &options(true), &CopyStats::new()).unwrap();
 
         assert_eq!(files, 3);
         assert!(dst.join("a/file1.txt").exists());
@@ -700,6 +2535,638 @@ mod tests {
         assert!(dst.join("a/b/c/file3.txt").exists());
     }
 
+    // ==================== list_files / install_file tests ====================
+
+    #[test]
+    fn test_list_files_relative_sorted() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("js")).unwrap();
+        fs::write(temp.path().join("js/b.js"), "b").unwrap();
+        fs::write(temp.path().join("js/a.js"), "a").unwrap();
+        fs::write(temp.path().join("style.less"), "less").unwrap();
+
+        let files = list_files(temp.path(), false);
+        assert_eq!(
+            files,
+            vec![PathBuf::from("js/a.js"), PathBuf::from("js/b.js")]
+        );
+
+        assert_eq!(list_files(temp.path(), true).len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_files_follows_symlinks_without_looping() {
+        let temp = TempDir::new().unwrap();
+        let shared = temp.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("a.js"), "a").unwrap();
+        let web = temp.path().join("web");
+        fs::create_dir_all(&web).unwrap();
+        std::os::unix::fs::symlink(&shared, web.join("linked")).unwrap();
+        std::os::unix::fs::symlink(&web, web.join("loop")).unwrap();
+
+        assert_eq!(list_files(&web, false), vec![PathBuf::from("linked/a.js")]);
+    }
+
+    #[test]
+    fn test_install_file_replaces_existing() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("out/dst.js");
+        fs::write(&src, "source").unwrap();
+
+        let stats = CopyStats::new();
+        let first = install_file(&src, &dst, LinkMode::Copy, &options(false), &stats).unwrap();
+        assert_eq!(first, Some(6));
+
+        fs::write(&src, "changed").unwrap();
+        let second = install_file(&src, &dst, LinkMode::Copy, &options(false), &stats).unwrap();
+        assert_eq!(second, Some(7));
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "changed");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_file_replaces_stale_symlink() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let old = temp.path().join("old.js");
+        let dst = temp.path().join("out/dst.js");
+        fs::write(&src, "source").unwrap();
+        fs::write(&old, "old").unwrap();
+        fs::create_dir_all(dst.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&old, &dst).unwrap();
+
+        install_file(
+            &src,
+            &dst,
+            LinkMode::Copy,
+            &options(false),
+            &CopyStats::new(),
+        )
+        .unwrap();
+
+        assert!(!fs::symlink_metadata(&dst).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "source");
+        // Previous symlink target untouched
+        assert_eq!(fs::read_to_string(&old).unwrap(), "old");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_file_hardlink() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("out/dst.js");
+        fs::write(&src, "source").unwrap();
+
+        let stats = CopyStats::new();
+        let written =
+            install_file(&src, &dst, LinkMode::Hardlink, &options(false), &stats).unwrap();
+
+        assert_eq!(written, Some(0));
+        assert_eq!(stats.get(CopyMethod::Hardlink), 1);
+    }
+
+    // ==================== install_files / CopyBackend tests ====================
+
+    #[test]
+    fn test_copy_backend_parse() {
+        assert_eq!(CopyBackend::parse("std"), Some(CopyBackend::Std));
+        assert_eq!(CopyBackend::parse("io-uring"), Some(CopyBackend::IoUring));
+        assert_eq!(CopyBackend::parse("aio"), None);
+        assert!(CopyBackend::Std.is_available());
+        assert!(CopyBackend::default().is_available());
+    }
+
+    // ==================== output attrs tests ====================
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_file_applies_attrs() {
+        use crate::attrs::OutputAttrs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("out/nested/dst.js");
+        fs::write(&src, "content").unwrap();
+        let src_mtime = fs::metadata(&src).unwrap().modified().unwrap();
+
+        let opts = CopyOptions {
+            attrs: OutputAttrs {
+                file_mode: Some(0o604),
+                dir_mode: Some(0o751),
+                preserve_mtime: true,
+                ..OutputAttrs::default()
+            },
+            ..CopyOptions::default()
+        };
+        install_file(&src, &dst, LinkMode::Copy, &opts, &CopyStats::new()).unwrap();
+
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(&dst), 0o604);
+        assert_eq!(mode(&temp.path().join("out")), 0o751);
+        assert_eq!(mode(&temp.path().join("out/nested")), 0o751);
+        assert_eq!(fs::metadata(&dst).unwrap().modified().unwrap(), src_mtime);
+    }
+
+    #[test]
+    fn test_copy_error_permission_denied() {
+        let err = copy_error(
+            Path::new("/src.js"),
+            Path::new("/dst.js"),
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        assert!(
+            matches!(err, DeployError::PermissionDenied { ref path, .. } if path == Path::new("/dst.js"))
+        );
+
+        let err = open_error(
+            Path::new("/src.js"),
+            Path::new("/dst.js"),
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        assert!(
+            matches!(err, DeployError::PermissionDenied { ref path, .. } if path == Path::new("/src.js"))
+        );
+    }
+
+    // ==================== skip_identical tests ====================
+
+    #[test]
+    fn test_files_identical() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&src, "same").unwrap();
+
+        assert!(!files_identical(&src, &dst));
+        fs::write(&dst, "same").unwrap();
+        assert!(files_identical(&src, &dst));
+        fs::write(&dst, "diff").unwrap();
+        assert!(!files_identical(&src, &dst));
+        fs::write(&dst, "longer").unwrap();
+        assert!(!files_identical(&src, &dst));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_identical_rejects_links() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let sym = temp.path().join("sym.js");
+        let hard = temp.path().join("hard.js");
+        fs::write(&src, "same").unwrap();
+        std::os::unix::fs::symlink(&src, &sym).unwrap();
+        fs::hard_link(&src, &hard).unwrap();
+
+        assert!(!files_identical(&src, &sym));
+        assert!(!files_identical(&src, &hard));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_file_skip_identical_keeps_inode() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&src, "content").unwrap();
+        fs::write(&dst, "content").unwrap();
+        let before = fs::metadata(&dst).unwrap();
+
+        let opts = CopyOptions {
+            skip_identical: true,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        assert_eq!(
+            install_file(&src, &dst, LinkMode::Copy, &opts, &stats).unwrap(),
+            None
+        );
+
+        let after = fs::metadata(&dst).unwrap();
+        assert_eq!(before.ino(), after.ino());
+        assert_eq!(before.modified().unwrap(), after.modified().unwrap());
+        assert_eq!(stats.identical.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.copied(), 0);
+
+        fs::write(&src, "changed").unwrap();
+        assert_eq!(
+            install_file(&src, &dst, LinkMode::Copy, &opts, &stats).unwrap(),
+            Some(7)
+        );
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "changed");
+    }
+
+    #[test]
+    fn test_install_files_skip_identical_mixed() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        for name in ["a.js", "b.js", "c.js"] {
+            fs::write(temp.path().join(name), name).unwrap();
+        }
+        fs::write(out.join("b.js"), "b.js").unwrap();
+
+        let files: Vec<_> = ["a.js", "b.js", "c.js"]
+            .iter()
+            .map(|name| (temp.path().join(name), out.join(name), LinkMode::Copy))
+            .collect();
+        let opts = CopyOptions {
+            skip_identical: true,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let results = install_files(&files, &opts, &stats);
+
+        let bytes: Vec<Option<u64>> = results.into_iter().map(|r| r.unwrap()).collect();
+        // The identical file is reported as skipped, not as a 0-byte copy
+        assert_eq!(bytes, vec![Some(4), None, Some(4)]);
+        assert_eq!(stats.identical.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.copied(), 2);
+        assert_eq!(fs::read_to_string(out.join("c.js")).unwrap(), "c.js");
+    }
+
+    fn install_files_case(backend: CopyBackend) {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let out = temp.path().join("out");
+        fs::create_dir_all(&src).unwrap();
+
+        let large: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(src.join("small.js"), "small").unwrap();
+        fs::write(src.join("empty.js"), "").unwrap();
+        fs::write(src.join("large.js"), &large).unwrap();
+        // Stale output from an earlier deploy gets replaced
+        fs::create_dir_all(out.join("nested")).unwrap();
+        fs::write(out.join("nested/small.js"), "stale content").unwrap();
+
+        let files = vec![
+            (
+                src.join("small.js"),
+                out.join("nested/small.js"),
+                LinkMode::Copy,
+            ),
+            (src.join("empty.js"), out.join("empty.js"), LinkMode::Copy),
+            (
+                src.join("large.js"),
+                out.join("deep/dir/large.js"),
+                LinkMode::Copy,
+            ),
+            (
+                src.join("missing.js"),
+                out.join("missing.js"),
+                LinkMode::Copy,
+            ),
+        ];
+        let opts = CopyOptions {
+            backend,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let results = install_files(&files, &opts, &stats);
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(*results[0].as_ref().unwrap(), Some(5));
+        assert_eq!(*results[1].as_ref().unwrap(), Some(0));
+        assert_eq!(*results[2].as_ref().unwrap(), Some(large.len() as u64));
+        assert!(matches!(results[3], Err(DeployError::CopyFailed { .. })));
+
+        assert_eq!(
+            fs::read_to_string(out.join("nested/small.js")).unwrap(),
+            "small"
+        );
+        assert_eq!(fs::read(out.join("empty.js")).unwrap().len(), 0);
+        assert_eq!(fs::read(out.join("deep/dir/large.js")).unwrap(), large);
+        assert_eq!(stats.copied(), 3);
+    }
+
+    #[test]
+    fn test_install_files_std_backend() {
+        install_files_case(CopyBackend::Std);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn test_install_files_io_uring_backend() {
+        // Uses the ring when the kernel allows it, the Std path otherwise
+        install_files_case(CopyBackend::IoUring);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn test_install_files_io_uring_never_writes_through_symlink() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.js");
+        let victim = temp.path().join("victim.js");
+        let dst = temp.path().join("dst.js");
+        fs::write(&src, "new").unwrap();
+        fs::write(&victim, "keep").unwrap();
+        std::os::unix::fs::symlink(&victim, &dst).unwrap();
+
+        let opts = CopyOptions {
+            backend: CopyBackend::IoUring,
+            ..CopyOptions::default()
+        };
+        let results = install_files(
+            &[(src, dst.clone(), LinkMode::Copy)],
+            &opts,
+            &CopyStats::new(),
+        );
+
+        assert!(results[0].is_ok());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep");
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn test_install_files_io_uring_keeps_dir_errors() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("a.js");
+        fs::write(&src, "a").unwrap();
+        fs::write(temp.path().join("out"), "not a directory").unwrap();
+        let big = temp.path().join("big.js");
+        fs::write(&big, "x".repeat(200 * 1024)).unwrap();
+
+        let opts = CopyOptions {
+            backend: CopyBackend::IoUring,
+            ..CopyOptions::default()
+        };
+        let results = install_files(
+            &[
+                (src, temp.path().join("out/js/a.js"), LinkMode::Copy),
+                (big, temp.path().join("ok/big.js"), LinkMode::Copy),
+            ],
+            &opts,
+            &CopyStats::new(),
+        );
+
+        // Same error as the Std path, not a stringified io::Error
+        assert!(matches!(
+            results[0],
+            Err(DeployError::CreateDirFailed { .. })
+        ));
+        assert_eq!(
+            fs::read(temp.path().join("ok/big.js")).unwrap().len(),
+            200 * 1024
+        );
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn test_install_files_io_uring_mixed_link_modes() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a.js");
+        let b = temp.path().join("b.js");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        let opts = CopyOptions {
+            backend: CopyBackend::IoUring,
+            ..CopyOptions::default()
+        };
+        let stats = CopyStats::new();
+        let results = install_files(
+            &[
+                (a, temp.path().join("out/a.js"), LinkMode::Hardlink),
+                (b, temp.path().join("out/b.js"), LinkMode::Copy),
+            ],
+            &opts,
+            &stats,
+        );
+
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(stats.get(CopyMethod::Hardlink), 1);
+        assert_eq!(stats.copied(), 1);
+    }
+
+    // ==================== replicate_directory tests ====================
+
+    #[test]
+    fn test_replicate_directory_copies_tree() {
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("nl_NL");
+        fs::create_dir_all(primary.join("js")).unwrap();
+        fs::write(primary.join("js/app.js"), "app").unwrap();
+        fs::write(primary.join("styles.css"), "css").unwrap();
+        // Dev files in the primary were deliberately deployed: mirror them too
+        fs::write(primary.join("notes.md"), "md").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = CopyStats::new();
+        let (files, bytes) =
+            replicate_directory(&primary, &replica, &shutdown, &options(false), &stats).unwrap();
+
+        assert_eq!(files, 3);
+        assert_eq!(bytes, 8);
+        assert_eq!(
+            fs::read_to_string(replica.join("js/app.js")).unwrap(),
+            "app"
+        );
+        assert!(replica.join("notes.md").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replicate_directory_hardlinks_files() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("de_DE");
+        fs::create_dir_all(&primary).unwrap();
+        fs::write(primary.join("app.js"), "app").unwrap();
+
+        let opts = CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..CopyOptions::default()
+        };
+        let shutdown = AtomicBool::new(false);
+        let stats = CopyStats::new();
+        replicate_directory(&primary, &replica, &shutdown, &opts, &stats).unwrap();
+
+        let a = fs::metadata(primary.join("app.js")).unwrap();
+        let b = fs::metadata(replica.join("app.js")).unwrap();
+        assert_eq!(a.ino(), b.ino());
+        assert_eq!(stats.get(CopyMethod::Hardlink), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replicate_directory_recreates_symlinks() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.js");
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("fr_FR");
+        fs::create_dir_all(&primary).unwrap();
+        fs::write(&source, "src").unwrap();
+        std::os::unix::fs::symlink(&source, primary.join("app.js")).unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = CopyStats::new();
+        replicate_directory(&primary, &replica, &shutdown, &options(true), &stats).unwrap();
+
+        assert_eq!(fs::read_link(replica.join("app.js")).unwrap(), source);
+        assert_eq!(stats.get(CopyMethod::Symlink), 1);
+    }
+
+    #[test]
+    fn test_replicate_directory_replaces_existing() {
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("nl_NL");
+        fs::create_dir_all(&primary).unwrap();
+        fs::create_dir_all(&replica).unwrap();
+        fs::write(primary.join("app.js"), "primary").unwrap();
+        fs::write(replica.join("app.js"), "existing").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let (files, _) = replicate_directory(
+            &primary,
+            &replica,
+            &shutdown,
+            &options(true),
+            &CopyStats::new(),
+        )
+        .unwrap();
+
+        assert_eq!(files, 1);
+        assert_eq!(
+            fs::read_to_string(replica.join("app.js")).unwrap(),
+            "primary"
+        );
+    }
+
+    #[test]
+    fn test_replicate_directory_keep_going() {
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("nl_NL");
+        fs::create_dir_all(&primary).unwrap();
+        fs::write(primary.join("app.js"), "app").unwrap();
+        fs::write(primary.join("lib.js"), "lib").unwrap();
+        // A directory in the way of one file
+        fs::create_dir_all(replica.join("app.js/dir")).unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let fail_fast = replicate_directory(
+            &primary,
+            &replica,
+            &shutdown,
+            &options(true),
+            &CopyStats::new(),
+        );
+        assert!(fail_fast.is_err());
+
+        let keep_going = CopyOptions {
+            keep_going: true,
+            ..options(true)
+        };
+        let stats = CopyStats::new();
+        let (files, _, failures) =
+            replicate_directory_keep_going(&primary, &replica, &shutdown, &keep_going, &stats)
+                .unwrap();
+
+        // lib.js may have made it before the failure stopped the first run
+        assert_eq!(files + stats.identical.load(Ordering::Relaxed), 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].src, primary.join("app.js"));
+        assert_eq!(failures[0].dst, replica.join("app.js"));
+        assert_eq!(fs::read_to_string(replica.join("lib.js")).unwrap(), "lib");
+    }
+
+    #[test]
+    fn test_replicate_directory_skips_current_files() {
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("nl_NL");
+        fs::create_dir_all(&primary).unwrap();
+        fs::write(primary.join("app.js"), "app").unwrap();
+        fs::write(primary.join("lib.js"), "lib").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        replicate_directory(
+            &primary,
+            &replica,
+            &shutdown,
+            &options(true),
+            &CopyStats::new(),
+        )
+        .unwrap();
+
+        let stats = CopyStats::new();
+        let (files, bytes) =
+            replicate_directory(&primary, &replica, &shutdown, &options(true), &stats).unwrap();
+        assert_eq!((files, bytes), (0, 0));
+        assert_eq!(stats.identical.load(Ordering::Relaxed), 2);
+
+        // The primary's app.js is deployed again, with other content of the
+        // same size
+        fs::write(primary.join("app.js"), "APP").unwrap();
+        let later = fs::metadata(replica.join("app.js"))
+            .unwrap()
+            .modified()
+            .unwrap()
+            + std::time::Duration::from_secs(1);
+        fs::File::options()
+            .write(true)
+            .open(primary.join("app.js"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        let stats = CopyStats::new();
+        let (files, _) =
+            replicate_directory(&primary, &replica, &shutdown, &options(true), &stats).unwrap();
+        assert_eq!(files, 1);
+        assert_eq!(stats.identical.load(Ordering::Relaxed), 1);
+        assert_eq!(fs::read_to_string(replica.join("app.js")).unwrap(), "APP");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replicate_directory_replaces_copies_with_hardlinks() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let primary = temp.path().join("en_US");
+        let replica = temp.path().join("nl_NL");
+        fs::create_dir_all(&primary).unwrap();
+        fs::write(primary.join("app.js"), "app").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        replicate_directory(
+            &primary,
+            &replica,
+            &shutdown,
+            &options(true),
+            &CopyStats::new(),
+        )
+        .unwrap();
+        let hardlink = CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..options(true)
+        };
+        let (files, _) =
+            replicate_directory(&primary, &replica, &shutdown, &hardlink, &CopyStats::new())
+                .unwrap();
+        assert_eq!(files, 1);
+        let (files, _) =
+            replicate_directory(&primary, &replica, &shutdown, &hardlink, &CopyStats::new())
+                .unwrap();
+        assert_eq!(files, 0);
+        assert_eq!(
+            fs::metadata(primary.join("app.js")).unwrap().ino(),
+            fs::metadata(replica.join("app.js")).unwrap().ino()
+        );
+    }
+
     // ==================== copy_directory_with_overrides tests ====================
 
     #[test]
@@ -714,7 +3181,9 @@ mod tests {
         fs::write(dst.join("file.txt"), "existing").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, _) = copy_directory_with_overrides(&src, &dst, &shutdown, true).unwrap();
+        let (files, _) =
+            copy_directory_with_overrides(&src, &dst, &shutdown, &options(true), &CopyStats::new())
+                .unwrap();
 
         // Should skip existing file
         assert_eq!(files, 0);
@@ -735,7 +3204,9 @@ mod tests {
         fs::write(src.join("new.txt"), "new file").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, bytes) = copy_directory_with_overrides(&src, &dst, &shutdown, true).unwrap();
+        let (files, bytes) =
+            copy_directory_with_overrides(&src, &dst, &shutdown, &options(true), &CopyStats::new())
+                .unwrap();
 
         assert_eq!(files, 1);
         assert_eq!(bytes, 8);
@@ -752,7 +3223,8 @@ mod tests {
         fs::write(src.join("file.txt"), "content").unwrap();
 
         let shutdown = AtomicBool::new(true);
-        let result = copy_directory_with_overrides(&src, &dst, &shutdown, true);
+        let result =
+            copy_directory_with_overrides(&src, &dst, &shutdown, &options(true), &CopyStats::new());
 
         assert!(matches!(result, Err(DeployError::Cancelled)));
     }
@@ -768,7 +3240,14 @@ mod tests {
         fs::write(src.join("app.ts"), "typescript").unwrap();
 
         let shutdown = AtomicBool::new(false);
-        let (files, _) = copy_directory_with_overrides(&src, &dst, &shutdown, false).unwrap();
+        let (files, _) = copy_directory_with_overrides(
+            &src,
+            &dst,
+            &shutdown,
+            &options(false),
+            &CopyStats::new(),
+        )
+        .unwrap();
 
         assert_eq!(files, 1);
         assert!(dst.join("app.js").exists());
@@ -0,0 +1,316 @@
+//! Module notation in CSS `url()` references.
+//!
+//! Magento's deploy rewrites `url('Magento_Catalog::images/foo.png')` into a
+//! path relative to the CSS file, e.g. `../Magento_Catalog/images/foo.png`
+//! from `css/styles.css`. `@{baseDir}` stands for the locale root the same
+//! way. `.css` files with such references are marked in the plan
+//! ([`Transform::ResolveCssUrls`]) and written once, resolved, by the work
+//! queue, as are the minified `.min.css` files; the others are copied or
+//! linked as usual. References to assets the output doesn't contain are
+//! reported.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::minify::Transform;
+use crate::plan::DeployPlan;
+
+/// Prefix standing for the locale root
+const BASE_DIR: &str = "@{baseDir}";
+
+/// A `url()` reference to an asset the output doesn't contain
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UnresolvedUrl {
+    /// The CSS file, relative to the locale root
+    pub css: PathBuf,
+    /// The reference as written
+    pub reference: String,
+}
+
+/// `css` with its references rewritten
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub css: Vec<u8>,
+    /// References whose asset `exists` doesn't know, rewritten all the same
+    pub unresolved: Vec<String>,
+}
+
+/// Rewrite the module notation and `@{baseDir}` references of `css`,
+/// deployed at `dest` (relative to the locale root). `exists` tells whether
+/// an asset is deployed. Returns `None` if there is nothing to rewrite.
+pub fn resolve_urls(css: &[u8], dest: &Path, exists: impl Fn(&Path) -> bool) -> Option<Resolved> {
+    if !contains(css, b"::") && !contains(css, BASE_DIR.as_bytes()) {
+        return None;
+    }
+    let dir = dest.parent().unwrap_or(Path::new(""));
+    let mut out = Vec::with_capacity(css.len());
+    let mut unresolved = Vec::new();
+    let mut copied = 0;
+    let mut i = 0;
+
+    while let Some((start, end)) = next_url(css, i) {
+        i = end;
+        let Some(reference) = std::str::from_utf8(&css[start..end]).ok() else {
+            continue;
+        };
+        let Some(target) = asset_path(reference) else {
+            continue;
+        };
+        let path = target.split(['?', '#']).next().unwrap_or_default();
+        if !exists(Path::new(path)) {
+            unresolved.push(reference.to_string());
+        }
+        out.extend_from_slice(&css[copied..start]);
+        out.extend_from_slice(relative_to(dir, &target).as_bytes());
+        copied = end;
+    }
+    if copied == 0 {
+        return None;
+    }
+    out.extend_from_slice(&css[copied..]);
+
+    Some(Resolved {
+        css: out,
+        unresolved,
+    })
+}
+
+/// Whether `css` has module notation or `@{baseDir}` references to rewrite
+pub fn has_references(css: &[u8]) -> bool {
+    let mut i = 0;
+    while let Some((start, end)) = next_url(css, i) {
+        i = end;
+        if std::str::from_utf8(&css[start..end])
+            .ok()
+            .and_then(asset_path)
+            .is_some()
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Mark the plain `.css` entries of `plan` whose source has references to
+/// rewrite, so they are written resolved instead of copied or linked.
+/// Only the entries `select` picks (by index) are read, e.g. the ones the
+/// incremental deploy is going to write.
+pub fn mark_references(plan: &mut DeployPlan, select: impl Fn(usize) -> bool + Sync) {
+    let referencing: HashSet<PathBuf> = plan
+        .entries()
+        .par_iter()
+        .enumerate()
+        .filter(|&(idx, entry)| {
+            entry.transform.is_none()
+                && entry.dest.extension().is_some_and(|ext| ext == "css")
+                && select(idx)
+        })
+        .filter(|(_, entry)| {
+            fs::read(plan.source_path(entry)).is_ok_and(|css| has_references(&css))
+        })
+        .map(|(_, entry)| entry.dest.clone())
+        .collect();
+    if referencing.is_empty() {
+        return;
+    }
+    plan.transform(|entry| {
+        referencing
+            .contains(&entry.dest)
+            .then_some(Transform::ResolveCssUrls)
+    });
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Span of the next `url()` argument at or after `from`, quotes excluded
+fn next_url(css: &[u8], from: usize) -> Option<(usize, usize)> {
+    let mut i = from;
+    loop {
+        let at = i + css
+            .get(i..)?
+            .windows(4)
+            .position(|w| w.eq_ignore_ascii_case(b"url("))?;
+        i = at + 4;
+        let is_word = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_');
+        if at > 0 && is_word(css[at - 1]) {
+            continue;
+        }
+        while css.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        let (start, close) = match css.get(i)? {
+            &quote @ (b'"' | b'\'') => (i + 1, quote),
+            _ => (i, b')'),
+        };
+        let len = css[start..].iter().position(|&b| b == close)?;
+        let mut end = start + len;
+        if close == b')' {
+            while end > start && css[end - 1].is_ascii_whitespace() {
+                end -= 1;
+            }
+        }
+        return Some((start, end));
+    }
+}
+
+/// Asset path (relative to the locale root) of a module notation or
+/// `@{baseDir}` reference
+fn asset_path(reference: &str) -> Option<String> {
+    if let Some(path) = reference.strip_prefix(BASE_DIR) {
+        return Some(path.trim_start_matches('/').to_string());
+    }
+    let (module, path) = reference.split_once("::")?;
+    let (vendor, name) = module.split_once('_')?;
+    let is_part = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric());
+    if !is_part(vendor) || !name.split('_').all(is_part) || path.is_empty() {
+        return None;
+    }
+    Some(format!("{module}/{}", path.trim_start_matches('/')))
+}
+
+/// `target` (relative to the locale root) as seen from `dir`
+fn relative_to(dir: &Path, target: &str) -> String {
+    let from: Vec<Component> = dir.components().collect();
+    let to: Vec<&str> = target.split('/').collect();
+    let (to_dirs, file) = to.split_at(to.len() - 1);
+    let common = from
+        .iter()
+        .zip(to_dirs)
+        .take_while(|(a, b)| a.as_os_str() == **b)
+        .count();
+
+    let mut parts: Vec<&str> = vec![".."; from.len() - common];
+    parts.extend(&to_dirs[common..]);
+    parts.extend(file);
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::FileSource;
+    use tempfile::TempDir;
+
+    fn resolve(css: &str, dest: &str, deployed: &[&str]) -> (String, Vec<String>) {
+        let resolved = resolve_urls(css.as_bytes(), Path::new(dest), |path| {
+            deployed.iter().any(|d| Path::new(d) == path)
+        })
+        .unwrap();
+        (
+            String::from_utf8(resolved.css).unwrap(),
+            resolved.unresolved,
+        )
+    }
+
+    // ==================== resolve_urls tests ====================
+
+    #[test]
+    fn test_resolve_urls_module_notation() {
+        let (css, unresolved) = resolve(
+            ".a{background:url('Magento_Catalog::images/foo.png')}\n.b{background:URL( \"Magento_Theme::fonts/x.woff2?v=2#i\" )}\n.c{background:url(Magento_Catalog::images/bar.png)}",
+            "css/styles.css",
+            &["Magento_Catalog/images/foo.png", "Magento_Theme/fonts/x.woff2"],
+        );
+        assert_eq!(
+            css,
+            ".a{background:url('../Magento_Catalog/images/foo.png')}\n.b{background:URL( \"../Magento_Theme/fonts/x.woff2?v=2#i\" )}\n.c{background:url(../Magento_Catalog/images/bar.png)}"
+        );
+        assert_eq!(unresolved, vec!["Magento_Catalog::images/bar.png"]);
+    }
+
+    #[test]
+    fn test_resolve_urls_relative_to_css_location() {
+        let deployed = ["Magento_Catalog/images/foo.png", "images/logo.svg"];
+        let (css, _) = resolve(
+            "a{background:url(Magento_Catalog::images/foo.png)}",
+            "Magento_Catalog/css/source/widget.css",
+            &deployed,
+        );
+        assert_eq!(css, "a{background:url(../../images/foo.png)}");
+
+        let (css, unresolved) = resolve(
+            "a{background:url('@{baseDir}images/logo.svg')}",
+            "styles.css",
+            &deployed,
+        );
+        assert_eq!(css, "a{background:url('images/logo.svg')}");
+        assert!(unresolved.is_empty());
+
+        let (css, _) = resolve(
+            "a{background:url('@{baseDir}/images/logo.svg')}",
+            "css/a/b.css",
+            &deployed,
+        );
+        assert_eq!(css, "a{background:url('../../images/logo.svg')}");
+    }
+
+    #[test]
+    fn test_resolve_urls_leaves_other_references() {
+        let css = "a::before{background:url(images/a.png)} b{background:url('https://cdn.example.com/x.png')} c{content:'Not::module'}";
+        assert_eq!(
+            resolve_urls(css.as_bytes(), Path::new("css/a.css"), |_| true),
+            None
+        );
+        assert_eq!(
+            resolve_urls(b"a{color:red}", Path::new("a.css"), |_| true),
+            None
+        );
+        assert_eq!(
+            resolve_urls(
+                b"a{background:url(\"Magento_Catalog::x.png",
+                Path::new("a.css"),
+                |_| true
+            ),
+            None
+        );
+    }
+
+    // ==================== mark_references tests ====================
+
+    #[test]
+    fn test_has_references() {
+        assert!(has_references(b"a{background:url(Magento_Catalog::a.png)}"));
+        assert!(has_references(b"a{background:url('@{baseDir}a.png')}"));
+        // Pseudo-elements aren't references
+        assert!(!has_references(b"a::before{background:url(images/a.png)}"));
+    }
+
+    #[test]
+    fn test_mark_references_only_css_that_needs_it() {
+        let temp = TempDir::new().unwrap();
+        let web = temp.path().join("web");
+        fs::create_dir_all(web.join("css")).unwrap();
+        fs::write(
+            web.join("css/styles.css"),
+            "a{background:url('Vendor_Module::img/a.png')}",
+        )
+        .unwrap();
+        fs::write(web.join("css/print.css"), "a::after{content:'x'}").unwrap();
+        fs::write(web.join("app.js"), "url('Vendor_Module::x')").unwrap();
+        let mut plan = DeployPlan::build(
+            temp.path().join("out"),
+            vec![FileSource::ThemeWeb {
+                theme: "Test/theme".to_string(),
+                path: web,
+            }],
+            true,
+        );
+
+        let mut unselected = plan.clone();
+        mark_references(&mut plan, |_| true);
+        let transform = |dest: &str| plan.get(Path::new(dest)).unwrap().transform;
+        assert_eq!(transform("css/styles.css"), Some(Transform::ResolveCssUrls));
+        // Left to be copied or linked
+        assert_eq!(transform("css/print.css"), None);
+        assert_eq!(transform("app.js"), None);
+
+        // Files that aren't going to be written aren't read
+        mark_references(&mut unselected, |_| false);
+        assert!(unselected.entries().iter().all(|e| e.transform.is_none()));
+    }
+}
@@ -4,6 +4,8 @@
 //! - Hyva themes (direct file copy)
 //! - Luma themes (delegation to bin/magento)
 //! - Progress tracking with cache-aligned atomic counters
+//! - One file-level work queue across all jobs
+//! - Quick strategy: resolve one locale per theme, replicate the rest
 
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -12,10 +14,27 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::copier::copy_directory_with_overrides;
+use rayon::prelude::*;
+
+use crate::atomic::{discard_staging, prepare_staging, swap_in};
+use crate::compress::{write_sidecars, SidecarStats};
+use crate::copier::{
+    files_identical, replicate_directory_keep_going, CopyOptions, CopyStats, FileFailure,
+};
+use crate::cssurl::{self, UnresolvedUrl};
 use crate::error::DeployError;
-use crate::scanner::{collect_file_sources, FileSource};
-use crate::theme::{resolve_parent_chain, LocaleCode, Theme, ThemeType};
+use crate::journal::{settings_fingerprint, sources_fingerprint, Journal};
+use crate::manifest::{
+    compare, manifest_path, output_settings, IncrementalPlan, Manifest, ManifestDelta,
+};
+use crate::minify::{minify_output, MinifySaved, MinifyStats};
+use crate::plan::{DeployPlan, SourceListings};
+use crate::prune::{prune_output, PruneReport};
+use crate::queue::{JobOutcome, WorkQueue, WorkUnit};
+use crate::requirejs::{self, module_config_files, theme_config_files, write_config};
+use crate::scanner::collect_file_sources;
+use crate::space::{self, Shortage, SpaceEstimate};
+use crate::theme::{resolve_parent_chain, Area, LocaleCode, Theme, ThemeType};
 
 /// A deployment job combining theme, locale, and area
 /// Uses Arc for efficient sharing across parallel workers without cloning
@@ -39,6 +58,38 @@ pub struct DeployResult {
     pub file_count: u64,
     /// Time taken for this job
     pub duration: Duration,
+    /// Changes against the previous deploy's manifest
+    pub delta: ManifestDelta,
+    /// Orphaned files removed from the output (`--prune`)
+    pub pruned: PruneReport,
+    /// Files that failed to deploy (`--keep-going`), sorted by destination
+    pub failures: Vec<FileFailure>,
+    /// Bytes saved by minification (`--minify-*`), per kind
+    pub minified: MinifySaved,
+    /// Module notation `url()` references to assets not deployed
+    pub unresolved_urls: Vec<UnresolvedUrl>,
+}
+
+impl DeployResult {
+    /// A result with `status` and nothing deployed yet
+    pub fn new(job: &DeployJob, status: DeployStatus) -> Self {
+        Self {
+            job: job.clone(),
+            status,
+            file_count: 0,
+            duration: Duration::ZERO,
+            delta: ManifestDelta::default(),
+            pruned: PruneReport::default(),
+            failures: Vec::new(),
+            minified: MinifySaved::default(),
+            unresolved_urls: Vec::new(),
+        }
+    }
+
+    /// A job that failed with `error` before deploying anything
+    pub fn failed(job: &DeployJob, error: DeployError) -> Self {
+        Self::new(job, DeployStatus::Failed(error))
+    }
 }
 
 /// Deployment outcome
@@ -46,12 +97,50 @@ pub struct DeployResult {
 pub enum DeployStatus {
     /// Successfully deployed
     Success,
+    /// Deployed except for the files in `DeployResult::failures` (`--keep-going`)
+    PartialSuccess,
     /// Failed with error
     Failed(DeployError),
     /// Cancelled by user (SIGINT)
     Cancelled,
     /// Delegated to bin/magento (Luma themes)
     Delegated,
+    /// Already completed by the interrupted run being resumed (`--resume`)
+    Resumed,
+}
+
+impl DeployStatus {
+    /// Whether the job's output was produced, possibly with failed files
+    pub fn is_deployed(&self) -> bool {
+        matches!(self, DeployStatus::Success | DeployStatus::PartialSuccess)
+    }
+}
+
+/// Execution strategy for the job matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeployStrategy {
+    /// Resolve and copy sources for every theme×locale job
+    #[default]
+    Standard,
+    /// Resolve each theme once, replicate its output to the other locales
+    Quick,
+}
+
+impl DeployStrategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "standard" => Some(Self::Standard),
+            "quick" => Some(Self::Quick),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Quick => "quick",
+        }
+    }
 }
 
 /// Cache-line aligned atomic counter to prevent false sharing
@@ -72,6 +161,16 @@ pub struct DeployStats {
     pub files_copied: CacheAlignedAtomic,
     pub bytes_copied: CacheAlignedAtomic,
     pub errors: CacheAlignedAtomic,
+    /// File installs retried after a transient error
+    pub retries: CacheAlignedAtomic,
+    /// Times the work queue reduced concurrency after running out of file descriptors
+    pub fd_backoffs: CacheAlignedAtomic,
+    /// Files per transfer method (copy_file_range, sendfile, buffered)
+    pub methods: CopyStats,
+    /// Precompressed sidecars written and skipped
+    pub sidecars: SidecarStats,
+    /// Minified files written and bytes saved
+    pub minified: MinifyStats,
 }
 
 impl DeployStats {
@@ -80,6 +179,11 @@ impl DeployStats {
             files_copied: CacheAlignedAtomic::new(0),
             bytes_copied: CacheAlignedAtomic::new(0),
             errors: CacheAlignedAtomic::new(0),
+            retries: CacheAlignedAtomic::new(0),
+            fd_backoffs: CacheAlignedAtomic::new(0),
+            methods: CopyStats::new(),
+            sidecars: SidecarStats::new(),
+            minified: MinifyStats::new(),
         }
     }
 }
@@ -114,6 +218,26 @@ pub fn read_deployed_version(magento_root: &Path) -> Option<String> {
         .map(|s| s.trim().to_string())
 }
 
+/// Shared inputs for deploying a set of jobs
+pub struct DeployContext<'a> {
+    pub all_themes: &'a [Theme],
+    pub magento_root: &'a Path,
+    pub shutdown: &'a AtomicBool,
+    pub stats: &'a DeployStats,
+    pub verbose: bool,
+    pub options: &'a CopyOptions,
+    /// Ignore manifests and copy every file (manifests are still written)
+    pub full: bool,
+    /// Remove files from successful outputs that this deploy didn't produce
+    pub prune: bool,
+    /// Deploy Hyva jobs into staging directories and swap them in on success
+    pub atomic: bool,
+    /// Refuse to deploy when the outputs won't fit on the output filesystem
+    pub check_space: bool,
+    /// Journal of this run; with `--resume`, also what the previous run did
+    pub journal: Option<&'a Journal>,
+}
+
 /// Deploy a single theme for a single locale
 pub fn deploy_theme(
     job: &DeployJob,
@@ -122,84 +246,794 @@ pub fn deploy_theme(
     shutdown: &AtomicBool,
     stats: &DeployStats,
     verbose: bool,
-    include_dev: bool,
+    options: &CopyOptions,
 ) -> DeployResult {
     let start = Instant::now();
 
     // Check for Luma theme - delegate to bin/magento
     if job.theme.theme_type == ThemeType::Luma {
-        return delegate_to_magento(job, magento_root, start, verbose);
+        let mut result = delegate_to_magento(job, magento_root, start, verbose);
+        minify_delegated(&mut result, magento_root, options, stats, shutdown);
+        return result;
     }
 
-    // Resolve parent chain
-    let parent_chain = resolve_parent_chain(&job.theme, all_themes);
+    let mut plans = plan_jobs(std::slice::from_ref(job), all_themes, magento_root, options);
+    for plan in &mut plans {
+        cssurl::mark_references(plan, |_| true);
+    }
+    let queue = WorkQueue::build(&plans, options);
 
-    // Collect all file sources
-    let sources = collect_file_sources(&job.theme, &parent_chain, magento_root);
+    match queue.execute(shutdown, options, stats, &|_| {}).pop() {
+        Some(outcome) => job_result(job, outcome),
+        None => DeployResult {
+            duration: start.elapsed(),
+            ..DeployResult::new(job, DeployStatus::Success)
+        },
+    }
+}
 
-    // Build output path
-    let output_path = output_path_for_theme(magento_root, &job.theme, &job.locale);
+/// Deploy a set of jobs through one global file-level work queue.
+///
+/// Luma jobs are delegated to bin/magento alongside the queue.
+/// `on_job_done` is called as each job finishes. Results are in job order.
+pub fn deploy_jobs(
+    jobs: &[DeployJob],
+    ctx: &DeployContext,
+    on_job_done: &(dyn Fn(&DeployJob) + Sync),
+) -> Vec<DeployResult> {
+    deploy_jobs_with_replicas(jobs, &[], ctx, on_job_done)
+}
+
+/// `deploy_jobs`, where `replicas[i]` lists the outputs job `i` will be
+/// replicated to afterwards (quick strategy), so the space check covers them
+fn deploy_jobs_with_replicas(
+    jobs: &[DeployJob],
+    replicas: &[Vec<PathBuf>],
+    ctx: &DeployContext,
+    on_job_done: &(dyn Fn(&DeployJob) + Sync),
+) -> Vec<DeployResult> {
+    let (luma, hyva): (Vec<usize>, Vec<usize>) =
+        (0..jobs.len()).partition(|&idx| jobs[idx].theme.theme_type == ThemeType::Luma);
+
+    let prepared = prepare_jobs(jobs, hyva, ctx);
+    let settings = settings_fingerprint(&prepared.settings);
+
+    if ctx.check_space {
+        let needed = prepared.space_needed(replicas, ctx.options);
+        if let Some(shortage) = space::check(&ctx.magento_root.join("pub/static"), &needed) {
+            return refuse_jobs(jobs, prepared, &shortage, ctx);
+        }
+    }
+
+    let (delegated, deployed) = rayon::join(
+        || {
+            luma.par_iter()
+                .map(|&idx| {
+                    let job = &jobs[idx];
+                    let result = if ctx.journal.is_some_and(|j| j.is_done(job, settings)) {
+                        resumed_result(job)
+                    } else {
+                        let mut result =
+                            delegate_to_magento(job, ctx.magento_root, Instant::now(), ctx.verbose);
+                        minify_delegated(
+                            &mut result,
+                            ctx.magento_root,
+                            ctx.options,
+                            ctx.stats,
+                            ctx.shutdown,
+                        );
+                        if let (Some(journal), DeployStatus::Delegated) =
+                            (ctx.journal, &result.status)
+                        {
+                            journal.done(job, settings);
+                        }
+                        result
+                    };
+                    on_job_done(job);
+                    (idx, result)
+                })
+                .collect::<Vec<_>>()
+        },
+        || run_prepared(jobs, prepared, ctx, on_job_done),
+    );
+
+    let mut results: Vec<Option<DeployResult>> = (0..jobs.len()).map(|_| None).collect();
+    for (idx, result) in delegated.into_iter().chain(deployed) {
+        results[idx] = Some(result);
+    }
+
+    results.into_iter().flatten().collect()
+}
 
-    // Copy files from each source
-    let mut total_files = 0u64;
+/// Hyva jobs planned, staged and compared with their manifests, ready to run
+struct PreparedJobs {
+    /// Index of each prepared job in the caller's job list
+    idx: Vec<usize>,
+    plans: Vec<DeployPlan>,
+    /// Live output of each job (its plan may target a staging directory)
+    live: Vec<PathBuf>,
+    staging_errors: Vec<Option<DeployError>>,
+    manifests: Vec<PathBuf>,
+    /// `output_settings` the manifests are loaded and saved with
+    settings: String,
+    incremental: Vec<IncrementalPlan>,
+    fingerprints: Vec<u64>,
+    /// Completed by the interrupted run being resumed; nothing to do
+    resumed: Vec<bool>,
+}
 
-    for source in sources {
-        if shutdown.load(Ordering::Relaxed) {
-            return DeployResult {
-                job: job.clone(),
-                status: DeployStatus::Cancelled,
-                file_count: total_files,
-                duration: start.elapsed(),
+fn prepare_jobs(jobs: &[DeployJob], idx: Vec<usize>, ctx: &DeployContext) -> PreparedJobs {
+    let hyva_jobs: Vec<DeployJob> = idx.iter().map(|&i| jobs[i].clone()).collect();
+    let mut plans = plan_jobs(&hyva_jobs, ctx.all_themes, ctx.magento_root, ctx.options);
+    let live: Vec<PathBuf> = plans.iter().map(|p| p.output().to_path_buf()).collect();
+    let settings = output_settings(ctx.options);
+    let fingerprints: Vec<u64> = plans
+        .iter()
+        .map(|plan| sources_fingerprint(plan, &settings))
+        .collect();
+    let resumed: Vec<bool> = hyva_jobs
+        .iter()
+        .zip(&fingerprints)
+        .map(|(job, &fingerprint)| {
+            ctx.journal
+                .is_some_and(|journal| journal.is_done(job, fingerprint))
+        })
+        .collect();
+
+    let mut staging_errors: Vec<Option<DeployError>> = (0..plans.len()).map(|_| None).collect();
+    if ctx.atomic {
+        staging_errors = plans
+            .par_iter_mut()
+            .zip(&resumed)
+            .map(|(plan, &resumed)| {
+                if resumed {
+                    return None;
+                }
+                match prepare_staging(plan.output(), true, &ctx.options.attrs, ctx.shutdown) {
+                    Ok(staging) => {
+                        plan.retarget(staging);
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            })
+            .collect();
+    }
+    let manifests: Vec<PathBuf> = hyva_jobs
+        .iter()
+        .map(|job| manifest_path(ctx.magento_root, &job.theme, &job.locale))
+        .collect();
+    let incremental: Vec<IncrementalPlan> = plans
+        .par_iter()
+        .zip(&manifests)
+        .zip(hyva_jobs.par_iter().zip(&resumed))
+        .map(|((plan, path), (job, &resumed))| {
+            if resumed {
+                return IncrementalPlan::default();
+            }
+            let previous = if ctx.full {
+                None
+            } else {
+                Manifest::load(path, &settings)
             };
-        }
+            let mut sync = compare(plan, previous.as_ref());
+            // Interrupted mid-copy: skip what already made it to the output
+            // (staging starts over, so only for in-place deploys)
+            if !ctx.atomic && ctx.journal.is_some_and(|journal| journal.was_begun(job)) {
+                verify_outputs(plan, &mut sync);
+            }
+            sync
+        })
+        .collect();
+    // Only the CSS that is going to be written is read
+    plans
+        .par_iter_mut()
+        .zip(&incremental)
+        .for_each(|(plan, sync)| {
+            cssurl::mark_references(plan, |idx| sync.copy.get(idx).copied().unwrap_or(false));
+        });
+
+    PreparedJobs {
+        idx,
+        plans,
+        live,
+        staging_errors,
+        manifests,
+        settings,
+        incremental,
+        fingerprints,
+        resumed,
+    }
+}
+
+/// Drop files whose output already has the source's content from the copy
+/// set
+fn verify_outputs(plan: &DeployPlan, sync: &mut IncrementalPlan) {
+    let entries = plan.entries();
+    sync.copy
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(_, copy)| **copy)
+        .for_each(|(idx, copy)| {
+            let entry = &entries[idx];
+            *copy = !files_identical(&plan.source_path(entry), &plan.dest_path(entry));
+        });
+}
+
+impl PreparedJobs {
+    /// Space the prepared jobs will allocate. A replica whose output
+    /// already exists is expected to change what its primary changes; a
+    /// new one gets the whole output.
+    fn space_needed(&self, replicas: &[Vec<PathBuf>], options: &CopyOptions) -> SpaceEstimate {
+        let mut needed = SpaceEstimate::default();
 
-        let (src_path, dest_subpath) = match &source {
-            FileSource::ThemeWeb { path, .. } => (path.clone(), PathBuf::new()),
-            FileSource::Library { path } => (path.clone(), PathBuf::new()),
-            FileSource::VendorModule { module, path } => (path.clone(), PathBuf::from(module)),
-            FileSource::ThemeModuleOverride { module, path, .. } => {
-                (path.clone(), PathBuf::from(module))
+        for (i, (&idx, plan)) in self.idx.iter().zip(&self.plans).enumerate() {
+            if self.resumed[i] {
+                continue;
             }
-        };
+            let sync = &self.incremental[i];
+            let changed = space::estimate_plan(plan, &sync.next, Some(&sync.copy), options);
+            needed.add(&changed);
+
+            for output in replicas.get(idx).into_iter().flatten() {
+                if output.is_dir() {
+                    needed.add(&changed);
+                } else {
+                    needed.add(&space::estimate_plan(plan, &sync.next, None, options));
+                }
+            }
+        }
 
-        let dest = if dest_subpath.as_os_str().is_empty() {
-            output_path.clone()
-        } else {
-            output_path.join(dest_subpath)
-        };
+        needed
+    }
+}
 
-        match copy_directory_with_overrides(&src_path, &dest, shutdown, include_dev) {
-            Ok((files, bytes)) => {
-                total_files += files;
-                stats.files_copied.0.fetch_add(files, Ordering::Relaxed);
-                stats.bytes_copied.0.fetch_add(bytes, Ordering::Relaxed);
+/// Run the prepared jobs through the work queue, then prune, swap and save
+/// manifests per job
+fn run_prepared(
+    jobs: &[DeployJob],
+    prepared: PreparedJobs,
+    ctx: &DeployContext,
+    on_job_done: &(dyn Fn(&DeployJob) + Sync),
+) -> Vec<(usize, DeployResult)> {
+    let PreparedJobs {
+        idx: hyva,
+        plans,
+        live,
+        staging_errors,
+        manifests,
+        settings,
+        incremental,
+        fingerprints,
+        resumed,
+    } = prepared;
+
+    let mut queue = WorkQueue::build(&plans, ctx.options);
+    let deployable = |unit: &WorkUnit| {
+        let job = unit.job as usize;
+        !resumed[job] && staging_errors[job].is_none()
+    };
+    let changed = |unit: &WorkUnit| incremental[unit.job as usize].copy[unit.entry as usize];
+    queue.retain(|unit| deployable(unit) && changed(unit));
+    if ctx.options.precompress.is_some() {
+        // Unchanged files may still lack sidecars, or have stale ones
+        queue.refresh_sidecars(|unit| deployable(unit) && !changed(unit));
+    }
+    let outcomes = queue.execute(ctx.shutdown, ctx.options, ctx.stats, &|job| {
+        on_job_done(&jobs[hyva[job]])
+    });
+
+    // Module requirejs-config.js files, once per area
+    let mut module_configs: Vec<(Area, Vec<PathBuf>)> = Vec::new();
+    if ctx.options.requirejs_config {
+        for &idx in &hyva {
+            let area = jobs[idx].theme.area;
+            if !module_configs.iter().any(|(a, _)| *a == area) {
+                module_configs.push((area, module_config_files(ctx.magento_root, area.as_str())));
             }
-            Err(DeployError::Cancelled) => {
-                return DeployResult {
-                    job: job.clone(),
-                    status: DeployStatus::Cancelled,
-                    file_count: total_files,
-                    duration: start.elapsed(),
+        }
+    }
+
+    hyva.iter()
+        .zip(outcomes)
+        .zip(incremental)
+        .zip(staging_errors)
+        .enumerate()
+        .map(|(i, (((&idx, outcome), mut sync), staging_error))| {
+            if resumed[i] {
+                return (idx, resumed_result(&jobs[idx]));
+            }
+            let (plan, live, path) = (&plans[i], &live[i], &manifests[i]);
+            let mut result = job_result(&jobs[idx], outcome);
+            if let Some(e) = staging_error {
+                result.status = match e {
+                    DeployError::Cancelled => DeployStatus::Cancelled,
+                    e => {
+                        ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+                        DeployStatus::Failed(e)
+                    }
                 };
+                result.delta = sync.delta;
+                return (idx, result);
             }
-            Err(e) => {
-                stats.errors.0.fetch_add(1, Ordering::Relaxed);
-                return DeployResult {
-                    job: job.clone(),
-                    status: DeployStatus::Failed(e),
-                    file_count: total_files,
-                    duration: start.elapsed(),
+            if let Some((_, modules)) = module_configs
+                .iter()
+                .find(|(area, _)| *area == jobs[idx].theme.area)
+            {
+                generate_requirejs_config(&mut result, plan.output(), modules, ctx);
+            }
+            if ctx.prune {
+                let planned = |rel: &Path| {
+                    plan.get(rel).is_some()
+                        || (ctx.options.requirejs_config
+                            && requirejs::is_generated(rel, ctx.options))
                 };
+                prune_job(
+                    &mut result,
+                    plan.output(),
+                    |rel| {
+                        planned(rel)
+                            || ctx
+                                .options
+                                .precompress
+                                .as_ref()
+                                .is_some_and(|p| p.is_sidecar(rel, planned))
+                    },
+                    ctx,
+                );
+            }
+            if ctx.atomic {
+                finish_staging(&mut result, plan.output(), live, ctx);
+            }
+            if result.status.is_deployed() {
+                // Failed files must be copied again next time
+                for failure in &result.failures {
+                    if let Ok(relative) = failure.dst.strip_prefix(live) {
+                        sync.next.remove(relative);
+                    }
+                }
+                if let Err(e) = sync.next.save(path, &settings) {
+                    eprintln!("Warning: failed to write {}: {e}", path.display());
+                }
+            }
+            if matches!(result.status, DeployStatus::Success) {
+                if let Some(journal) = ctx.journal {
+                    journal.done(&jobs[idx], fingerprints[i]);
+                }
+            }
+            result.delta = sync.delta;
+            (idx, result)
+        })
+        .collect()
+}
+
+/// Fail every job without copying anything: the outputs won't fit
+fn refuse_jobs(
+    jobs: &[DeployJob],
+    prepared: PreparedJobs,
+    shortage: &Shortage,
+    ctx: &DeployContext,
+) -> Vec<DeployResult> {
+    if ctx.atomic {
+        for ((plan, error), &resumed) in prepared
+            .plans
+            .iter()
+            .zip(&prepared.staging_errors)
+            .zip(&prepared.resumed)
+        {
+            if error.is_none() && !resumed {
+                discard_staging(plan.output());
+            }
+        }
+    }
+
+    jobs.iter()
+        .map(|job| {
+            ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+            DeployResult::failed(job, shortage.to_error())
+        })
+        .collect()
+}
+
+/// Build the resolved deploy plan for each job, in job order.
+/// Source directories shared between jobs are listed once. With
+/// `options.minify`, the plans include the minified files to write and
+/// mark the templates minified in place. CSS files with module notation
+/// `url()`s are left for `cssurl::mark_references` to mark, once it is
+/// known which files will be written.
+pub fn plan_jobs(
+    jobs: &[DeployJob],
+    all_themes: &[Theme],
+    magento_root: &Path,
+    options: &CopyOptions,
+) -> Vec<DeployPlan> {
+    let job_sources: Vec<_> = jobs
+        .par_iter()
+        .map(|job| {
+            let parent_chain = resolve_parent_chain(&job.theme, all_themes);
+            (
+                output_path_for_theme(magento_root, &job.theme, &job.locale),
+                collect_file_sources(&job.theme, &parent_chain, magento_root),
+            )
+        })
+        .collect();
+
+    let listings = SourceListings::collect(
+        job_sources.iter().flat_map(|(_, sources)| sources),
+        options.include_dev,
+    );
+
+    job_sources
+        .into_par_iter()
+        .zip(jobs)
+        .map(|((output, sources), job)| {
+            let mut plan = DeployPlan::from_listings(output, sources, &listings);
+            if let Some(minify) = &options.minify {
+                let asset_dir = asset_dir(job);
+                plan.transform(|entry| minify.in_place(&entry.dest, &asset_dir));
+                plan.derive(|entry| minify.derive(&entry.dest, &asset_dir));
             }
+            plan
+        })
+        .collect()
+}
+
+/// A job's output relative to pub/static (`area/Vendor/theme/locale`), the
+/// form Magento matches minify excludes against
+fn asset_dir(job: &DeployJob) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        job.theme.area.as_str(),
+        job.theme.vendor,
+        job.theme.name,
+        job.locale.as_str()
+    )
+}
+
+/// Prune a successfully deployed output, keeping the files `keep` accepts.
+/// A failed prune fails the job.
+fn prune_job(
+    result: &mut DeployResult,
+    output: &Path,
+    keep: impl Fn(&Path) -> bool,
+    ctx: &DeployContext,
+) {
+    if !result.status.is_deployed() {
+        return;
+    }
+
+    match prune_output(output, keep, ctx.shutdown) {
+        Ok(report) => result.pruned = report,
+        Err(DeployError::Cancelled) => result.status = DeployStatus::Cancelled,
+        Err(e) => {
+            ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+            result.status = DeployStatus::Failed(e);
+        }
+    }
+}
+
+/// Write a deployed job's merged requirejs-config.js from the modules'
+/// `module_files` and its themes' ones. A failure fails the job.
+fn generate_requirejs_config(
+    result: &mut DeployResult,
+    output: &Path,
+    module_files: &[PathBuf],
+    ctx: &DeployContext,
+) {
+    if !result.status.is_deployed() {
+        return;
+    }
+
+    let theme = &result.job.theme;
+    let mut files = module_files.to_vec();
+    files.extend(theme_config_files(
+        theme,
+        &resolve_parent_chain(theme, ctx.all_themes),
+    ));
+    let written = write_config(&files, output, ctx.options).and_then(|_| {
+        let Some(precompress) = &ctx.options.precompress else {
+            return Ok(());
+        };
+        [requirejs::CONFIG_FILE, requirejs::MIN_CONFIG_FILE]
+            .into_iter()
+            .filter(|name| requirejs::is_generated(Path::new(name), ctx.options))
+            .try_for_each(|name| {
+                write_sidecars(
+                    &output.join(name),
+                    precompress,
+                    &ctx.options.attrs,
+                    &ctx.stats.sidecars,
+                )
+            })
+    });
+    if let Err(e) = written {
+        ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+        result.status = DeployStatus::Failed(e);
+    }
+}
+
+/// Swap a job's staging directory in if the job succeeded (or partly
+/// succeeded with `--keep-going`), discard it otherwise (the live output
+/// stays untouched). A failed swap fails the job.
+fn finish_staging(result: &mut DeployResult, staging: &Path, live: &Path, ctx: &DeployContext) {
+    for failure in &mut result.failures {
+        if let Ok(relative) = failure.dst.strip_prefix(staging) {
+            failure.dst = live.join(relative);
+        }
+    }
+
+    if !result.status.is_deployed() {
+        discard_staging(staging);
+        return;
+    }
+
+    if let Err(e) = swap_in(staging, live) {
+        ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+        result.status = DeployStatus::Failed(e);
+        discard_staging(staging);
+    }
+}
+
+/// Add the minified files bin/magento didn't write to a delegated job's
+/// output. A failure fails the job.
+fn minify_delegated(
+    result: &mut DeployResult,
+    magento_root: &Path,
+    options: &CopyOptions,
+    stats: &DeployStats,
+    shutdown: &AtomicBool,
+) {
+    if options.minify.is_none() || !matches!(result.status, DeployStatus::Delegated) {
+        return;
+    }
+
+    let job = &result.job;
+    let output = output_path_for_theme(magento_root, &job.theme, &job.locale);
+    match minify_output(&output, &asset_dir(job), options, &stats.minified, shutdown) {
+        Ok(saved) => result.minified = saved,
+        Err(DeployError::Cancelled) => result.status = DeployStatus::Cancelled,
+        Err(e) => {
+            stats.errors.0.fetch_add(1, Ordering::Relaxed);
+            result.status = DeployStatus::Failed(e);
         }
     }
+}
+
+/// Result for a job the resumed run had already completed
+fn resumed_result(job: &DeployJob) -> DeployResult {
+    DeployResult::new(job, DeployStatus::Resumed)
+}
+
+/// Turn a queue outcome into the job's result
+fn job_result(job: &DeployJob, outcome: JobOutcome) -> DeployResult {
+    let status = match outcome.error {
+        Some(e) => DeployStatus::Failed(e),
+        None if outcome.cancelled => DeployStatus::Cancelled,
+        None if !outcome.failures.is_empty() => DeployStatus::PartialSuccess,
+        None => DeployStatus::Success,
+    };
 
     DeployResult {
-        job: job.clone(),
-        status: DeployStatus::Success,
-        file_count: total_files,
+        file_count: outcome.files,
+        duration: outcome.duration,
+        failures: outcome.failures,
+        minified: outcome.minified,
+        unresolved_urls: outcome.unresolved,
+        ..DeployResult::new(job, status)
+    }
+}
+
+/// Deploy a locale by replicating the output of an already deployed locale
+/// of the same theme.
+///
+/// File resolution doesn't depend on the locale (there are no i18n source
+/// overrides), so the primary output is exactly what a full deploy of this
+/// locale would produce. Each locale directory is served on its own, so the
+/// whole output is mirrored, but only files that differ from the primary's
+/// are written: a rerun after an incremental primary deploy touches just
+/// what the primary changed. Copies are cloned where the filesystem allows.
+/// `output` is the job's output directory (or its staging directory).
+pub fn replicate_theme(
+    job: &DeployJob,
+    primary_output: &Path,
+    output: &Path,
+    shutdown: &AtomicBool,
+    stats: &DeployStats,
+    options: &CopyOptions,
+) -> DeployResult {
+    let start = Instant::now();
+
+    let (status, file_count, failures) = match replicate_directory_keep_going(
+        primary_output,
+        output,
+        shutdown,
+        options,
+        &stats.methods,
+    ) {
+        Ok((files, bytes, failures)) => {
+            stats.files_copied.0.fetch_add(files, Ordering::Relaxed);
+            stats.bytes_copied.0.fetch_add(bytes, Ordering::Relaxed);
+            stats
+                .errors
+                .0
+                .fetch_add(failures.len() as u64, Ordering::Relaxed);
+            let status = if failures.is_empty() {
+                DeployStatus::Success
+            } else {
+                DeployStatus::PartialSuccess
+            };
+            (status, files, failures)
+        }
+        Err(DeployError::Cancelled) => (DeployStatus::Cancelled, 0, Vec::new()),
+        Err(e) => {
+            stats.errors.0.fetch_add(1, Ordering::Relaxed);
+            (DeployStatus::Failed(e), 0, Vec::new())
+        }
+    };
+
+    DeployResult {
+        file_count,
         duration: start.elapsed(),
+        failures,
+        ..DeployResult::new(job, status)
+    }
+}
+
+/// For each job, the index of the job whose output it can be replicated
+/// from under the quick strategy (`None` for primaries).
+/// The first locale of each theme is its primary.
+pub fn quick_primaries(jobs: &[DeployJob]) -> Vec<Option<usize>> {
+    let mut primaries = Vec::with_capacity(jobs.len());
+
+    for (idx, job) in jobs.iter().enumerate() {
+        let primary = jobs[..idx]
+            .iter()
+            .position(|other| same_theme(&other.theme, &job.theme));
+        primaries.push(primary);
+    }
+
+    primaries
+}
+
+fn same_theme(a: &Theme, b: &Theme) -> bool {
+    a.area == b.area && a.vendor == b.vendor && a.name == b.name
+}
+
+/// Run the job matrix with the quick strategy.
+///
+/// Primaries are deployed first through the work queue. Every other job is
+/// then replicated from its primary's output when that primary was a
+/// successful Hyva deploy, or deployed in full otherwise (e.g. Luma themes,
+/// which are delegated as usual). Results are returned in job order.
+pub fn deploy_quick(
+    jobs: &[DeployJob],
+    ctx: &DeployContext,
+    on_job_done: &(dyn Fn(&DeployJob) + Sync),
+) -> Vec<DeployResult> {
+    let primaries = quick_primaries(jobs);
+    let settings = settings_fingerprint(&output_settings(ctx.options));
+    let (primary_idx, replica_idx): (Vec<usize>, Vec<usize>) =
+        (0..jobs.len()).partition(|&idx| primaries[idx].is_none());
+
+    let primary_jobs: Vec<DeployJob> = primary_idx.iter().map(|&i| jobs[i].clone()).collect();
+    let replica_outputs: Vec<Vec<PathBuf>> = primary_idx
+        .iter()
+        .map(|&primary| {
+            replica_idx
+                .iter()
+                .filter(|&&idx| primaries[idx] == Some(primary))
+                .map(|&idx| {
+                    output_path_for_theme(ctx.magento_root, &jobs[idx].theme, &jobs[idx].locale)
+                })
+                .collect()
+        })
+        .collect();
+    let mut results: Vec<Option<DeployResult>> = (0..jobs.len()).map(|_| None).collect();
+    for (idx, result) in primary_idx.iter().zip(deploy_jobs_with_replicas(
+        &primary_jobs,
+        &replica_outputs,
+        ctx,
+        on_job_done,
+    )) {
+        results[*idx] = Some(result);
+    }
+
+    // Replicate only from primaries that produced a complete output
+    let replica_source = |idx: usize| -> Option<PathBuf> {
+        let primary = results[primaries[idx]?].as_ref()?;
+        (primary.job.theme.theme_type == ThemeType::Hyva
+            && matches!(
+                primary.status,
+                DeployStatus::Success | DeployStatus::Resumed
+            ))
+        .then(|| output_path_for_theme(ctx.magento_root, &primary.job.theme, &primary.job.locale))
+    };
+    let replicas: Vec<(usize, Option<PathBuf>)> = replica_idx
+        .iter()
+        .map(|&idx| (idx, replica_source(idx)))
+        .collect();
+    let (replicable, full): (Vec<_>, Vec<_>) = replicas
+        .into_iter()
+        .partition(|(_, source)| source.is_some());
+
+    let full_jobs: Vec<DeployJob> = full.iter().map(|(i, _)| jobs[*i].clone()).collect();
+    let (replicated, deployed) = rayon::join(
+        || {
+            replicable
+                .par_iter()
+                .filter_map(|(idx, source)| {
+                    let job = &jobs[*idx];
+                    // Replicas of an unchanged primary can be resumed too
+                    let primary_resumed = primaries[*idx]
+                        .and_then(|primary| results[primary].as_ref())
+                        .is_some_and(|primary| matches!(primary.status, DeployStatus::Resumed));
+                    let result = if primary_resumed
+                        && ctx
+                            .journal
+                            .is_some_and(|journal| journal.is_done(job, settings))
+                    {
+                        resumed_result(job)
+                    } else {
+                        replicate_job(job, source.as_deref()?, ctx)
+                    };
+                    on_job_done(job);
+                    Some((*idx, result))
+                })
+                .collect::<Vec<_>>()
+        },
+        || deploy_jobs(&full_jobs, ctx, on_job_done),
+    );
+
+    for (idx, result) in replicated {
+        results[idx] = Some(result);
     }
+    for ((idx, _), result) in full.iter().zip(deployed) {
+        results[*idx] = Some(result);
+    }
+
+    results.into_iter().flatten().collect()
+}
+
+/// Replicate one quick-strategy job, through a staging directory in atomic
+/// mode, pruning what its primary's output doesn't hold
+fn replicate_job(job: &DeployJob, primary_output: &Path, ctx: &DeployContext) -> DeployResult {
+    let live = output_path_for_theme(ctx.magento_root, &job.theme, &job.locale);
+    let output = if ctx.atomic {
+        match prepare_staging(&live, false, &ctx.options.attrs, ctx.shutdown) {
+            Ok(staging) => staging,
+            Err(e) => {
+                ctx.stats.errors.0.fetch_add(1, Ordering::Relaxed);
+                return DeployResult::failed(job, e);
+            }
+        }
+    } else {
+        live.clone()
+    };
+
+    let mut result = replicate_theme(
+        job,
+        primary_output,
+        &output,
+        ctx.shutdown,
+        ctx.stats,
+        ctx.options,
+    );
+    if ctx.prune {
+        prune_job(
+            &mut result,
+            &output,
+            |rel| fs::symlink_metadata(primary_output.join(rel)).is_ok(),
+            ctx,
+        );
+    }
+    if ctx.atomic {
+        finish_staging(&mut result, &output, &live, ctx);
+    }
+    if let (Some(journal), DeployStatus::Success) = (ctx.journal, &result.status) {
+        // Replicas aren't planned; they follow their primary's sources
+        journal.done(job, settings_fingerprint(&output_settings(ctx.options)));
+    }
+    result
 }
 
 /// Delegate Luma theme to bin/magento
@@ -236,29 +1070,24 @@ fn delegate_to_magento(
 
             if output.status.success() {
                 DeployResult {
-                    job: job.clone(),
-                    status: DeployStatus::Delegated,
-                    file_count: 0,
                     duration: start.elapsed(),
+                    ..DeployResult::new(job, DeployStatus::Delegated)
                 }
             } else {
                 let code = output.status.code().unwrap_or(-1);
+                let error = DeployError::MagentoFailed {
+                    code,
+                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                };
                 DeployResult {
-                    job: job.clone(),
-                    status: DeployStatus::Failed(DeployError::MagentoFailed {
-                        code,
-                        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    }),
-                    file_count: 0,
                     duration: start.elapsed(),
+                    ..DeployResult::failed(job, error)
                 }
             }
         }
         Err(e) => DeployResult {
-            job: job.clone(),
-            status: DeployStatus::Failed(DeployError::Io(e)),
-            file_count: 0,
             duration: start.elapsed(),
+            ..DeployResult::failed(job, DeployError::Io(e))
         },
     }
 }
@@ -289,7 +1118,13 @@ pub fn collect_results(results: Vec<DeployResult>) -> (Vec<DeployResult>, bool,
 
     for result in results {
         match &result.status {
-            DeployStatus::Success | DeployStatus::Delegated => has_success = true,
+            DeployStatus::Success | DeployStatus::Delegated | DeployStatus::Resumed => {
+                has_success = true
+            }
+            DeployStatus::PartialSuccess => {
+                has_success = true;
+                has_failure = true;
+            }
             DeployStatus::Failed(_) => has_failure = true,
             DeployStatus::Cancelled => {}
         }
@@ -302,11 +1137,23 @@ pub fn collect_results(results: Vec<DeployResult>) -> (Vec<DeployResult>, bool,
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::atomic::{previous_path, staging_path};
+    use crate::compress::{Codec, Precompress};
+    use crate::copier::LinkMode;
+    use crate::minify::Minify;
     use crate::theme::{Area, Theme, ThemeType};
+    use std::io::Read;
     use std::sync::atomic::Ordering;
     use std::time::Duration;
     use tempfile::TempDir;
 
+    fn dev_options() -> CopyOptions {
+        CopyOptions {
+            include_dev: true,
+            ..CopyOptions::default()
+        }
+    }
+
     // ==================== CacheAlignedAtomic tests ====================
 
     #[test]
@@ -514,14 +1361,13 @@ mod tests {
             parent: None,
             theme_type: ThemeType::Hyva,
         };
+        let job = DeployJob {
+            theme: Arc::new(theme),
+            locale: LocaleCode::new("en_US"),
+        };
         DeployResult {
-            job: DeployJob {
-                theme: Arc::new(theme),
-                locale: LocaleCode::new("en_US"),
-            },
-            status,
-            file_count: 0,
             duration: Duration::from_secs(1),
+            ..DeployResult::new(&job, status)
         }
     }
 
@@ -567,6 +1413,16 @@ mod tests {
         assert!(has_failure);
     }
 
+    #[test]
+    fn test_collect_results_partial_success_is_both() {
+        let (_, has_success, has_failure) =
+            collect_results(vec![make_result(DeployStatus::PartialSuccess)]);
+
+        // Exit code 1, like a partial failure
+        assert!(has_success);
+        assert!(has_failure);
+    }
+
     #[test]
     fn test_collect_results_delegated_counts_as_success() {
         let results = vec![make_result(DeployStatus::Delegated)];
@@ -645,6 +1501,1129 @@ mod tests {
         assert_eq!(result, Some("version123".to_string()));
     }
 
+    // ==================== DeployStrategy tests ====================
+
+    #[test]
+    fn test_deploy_strategy_parse() {
+        assert_eq!(
+            DeployStrategy::parse("standard"),
+            Some(DeployStrategy::Standard)
+        );
+        assert_eq!(DeployStrategy::parse("quick"), Some(DeployStrategy::Quick));
+        assert_eq!(DeployStrategy::parse("compact"), None);
+    }
+
+    #[test]
+    fn test_deploy_strategy_as_str_roundtrip() {
+        for strategy in [DeployStrategy::Standard, DeployStrategy::Quick] {
+            assert_eq!(DeployStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(DeployStrategy::default(), DeployStrategy::Standard);
+    }
+
+    // ==================== quick strategy tests ====================
+
+    fn hyva_theme(temp: &TempDir, name: &str) -> Theme {
+        let theme_path = temp.path().join(name);
+        let web_path = theme_path.join("web");
+        std::fs::create_dir_all(&web_path).unwrap();
+        std::fs::write(web_path.join("app.js"), "content").unwrap();
+
+        Theme {
+            vendor: "Test".to_string(),
+            name: name.to_string(),
+            area: Area::Frontend,
+            path: theme_path,
+            parent: None,
+            theme_type: ThemeType::Hyva,
+        }
+    }
+
+    #[test]
+    fn test_quick_primaries_first_locale_per_theme() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "one"), hyva_theme(&temp, "two")];
+        let locales = vec![LocaleCode::new("en_US"), LocaleCode::new("nl_NL")];
+
+        let jobs = job_matrix(&themes, &locales);
+        let primaries = quick_primaries(&jobs);
+
+        assert_eq!(primaries, vec![None, Some(0), None, Some(2)]);
+    }
+
+    #[test]
+    fn test_replicate_theme_mirrors_primary() {
+        let temp = TempDir::new().unwrap();
+        let theme = hyva_theme(&temp, "theme");
+        let jobs = job_matrix(
+            std::slice::from_ref(&theme),
+            &[LocaleCode::new("en_US"), LocaleCode::new("de_DE")],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        deploy_theme(
+            &jobs[0],
+            std::slice::from_ref(&theme),
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
+
+        let primary = output_path_for_theme(temp.path(), &theme, &jobs[0].locale);
+        let replica = output_path_for_theme(temp.path(), &theme, &jobs[1].locale);
+        let result = replicate_theme(
+            &jobs[1],
+            &primary,
+            &replica,
+            &shutdown,
+            &stats,
+            &dev_options(),
+        );
+
+        assert!(matches!(result.status, DeployStatus::Success));
+        assert_eq!(result.file_count, 1);
+        assert!(replica.join("app.js").exists());
+        assert_eq!(stats.files_copied.0.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_deploy_quick_replicates_from_primary() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(
+            &themes,
+            &[
+                LocaleCode::new("en_US"),
+                LocaleCode::new("de_DE"),
+                LocaleCode::new("fr_FR"),
+            ],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            link_mode: crate::copier::LinkMode::Hardlink,
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+        let done = AtomicU64::new(0);
+
+        let results = deploy_quick(&jobs, &ctx, &|_| {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].job.locale.as_str(), "de_DE");
+        assert!(results
+            .iter()
+            .all(|r| matches!(r.status, DeployStatus::Success)));
+        assert_eq!(done.load(Ordering::Relaxed), 3);
+
+        for locale in ["en_US", "de_DE", "fr_FR"] {
+            let out = output_path_for_theme(temp.path(), &themes[0], &LocaleCode::new(locale));
+            assert!(out.join("app.js").exists());
+        }
+        // Primary links to the source, replicas link to the primary's files
+        assert_eq!(stats.methods.get(crate::copier::CopyMethod::Hardlink), 3);
+    }
+
+    #[test]
+    fn test_deploy_quick_rerun_leaves_current_replicas() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("de_DE")],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+        let first = deploy_quick(&jobs, &ctx, &|_| {});
+        assert_eq!(first[1].file_count, 1);
+
+        let second = deploy_quick(&jobs, &ctx, &|_| {});
+        assert!(matches!(second[1].status, DeployStatus::Success));
+        assert_eq!(second[0].file_count, 0);
+        assert_eq!(second[1].file_count, 0);
+        assert_eq!(stats.methods.identical.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_deploy_quick_full_deploy_when_primary_fails() {
+        let temp = TempDir::new().unwrap();
+        let mut theme = hyva_theme(&temp, "theme");
+        theme.theme_type = ThemeType::Luma;
+        let themes = vec![theme];
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("de_DE")],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &dev_options(),
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        // No bin/magento: both locales are delegated and fail, none replicated
+        let results = deploy_quick(&jobs, &ctx, &|_| {});
+
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r.status, DeployStatus::Failed(_))));
+        let replica = output_path_for_theme(temp.path(), &themes[0], &jobs[1].locale);
+        assert!(!replica.exists());
+    }
+
+    // ==================== plan_jobs tests ====================
+
+    #[test]
+    fn test_plan_jobs_theme_shadows_library() {
+        let temp = TempDir::new().unwrap();
+        let theme = hyva_theme(&temp, "theme");
+        let lib_path = temp.path().join("lib").join("web");
+        std::fs::create_dir_all(&lib_path).unwrap();
+        std::fs::write(lib_path.join("app.js"), "library").unwrap();
+        std::fs::write(lib_path.join("lib.js"), "library").unwrap();
+        let themes = vec![theme];
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("nl_NL")],
+        );
+
+        let plans = plan_jobs(&jobs, &themes, temp.path(), &CopyOptions::default());
+
+        assert_eq!(plans.len(), 2);
+        assert_eq!(
+            plans[1].output(),
+            output_path_for_theme(temp.path(), &themes[0], &jobs[1].locale)
+        );
+        let app = std::path::Path::new("app.js");
+        assert!(matches!(
+            plans[0].winner(app),
+            Some(crate::scanner::FileSource::ThemeWeb { .. })
+        ));
+        assert!(matches!(
+            plans[0].shadowed(app).as_slice(),
+            [crate::scanner::FileSource::Library { .. }]
+        ));
+        assert_eq!(plans[0].len(), 2);
+    }
+
+    // ==================== deploy_jobs tests ====================
+
+    #[test]
+    fn test_deploy_jobs_results_in_job_order() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "one"), hyva_theme(&temp, "two")];
+        std::fs::write(themes[1].path.join("web").join("extra.js"), "extra").unwrap();
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("nl_NL")],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &dev_options(),
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+        let done = AtomicU64::new(0);
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let counts: Vec<u64> = results.iter().map(|r| r.file_count).collect();
+        assert_eq!(counts, vec![1, 1, 2, 2]);
+        assert_eq!(results[3].job.theme.name, "two");
+        assert_eq!(results[3].job.locale.as_str(), "nl_NL");
+        assert_eq!(done.load(Ordering::Relaxed), 4);
+        assert_eq!(stats.files_copied.0.load(Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn test_space_needed_counts_replicas() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &dev_options(),
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: true,
+            journal: None,
+        };
+
+        // One 7-byte file in one directory
+        let prepared = prepare_jobs(&jobs, vec![0], &ctx);
+        let one = SpaceEstimate {
+            bytes: 4096,
+            inodes: 2,
+        };
+        assert_eq!(prepared.space_needed(&[], ctx.options), one);
+
+        let existing = temp.path().join("existing");
+        std::fs::create_dir_all(&existing).unwrap();
+        let replicas = vec![vec![temp.path().join("missing"), existing]];
+        assert_eq!(prepared.space_needed(&replicas, ctx.options), one.times(3));
+
+        // The space check passes and the deploy goes ahead
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(results[0].file_count, 1);
+    }
+
+    #[test]
+    fn test_deploy_jobs_theme_overrides_library() {
+        let temp = TempDir::new().unwrap();
+        let theme = hyva_theme(&temp, "theme");
+        let lib_path = temp.path().join("lib").join("web");
+        std::fs::create_dir_all(&lib_path).unwrap();
+        std::fs::write(lib_path.join("app.js"), "library").unwrap();
+        let themes = vec![theme];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &dev_options(),
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+
+        assert_eq!(results[0].file_count, 1);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        assert_eq!(
+            std::fs::read_to_string(out.join("app.js")).unwrap(),
+            "content"
+        );
+    }
+
+    #[test]
+    fn test_deploy_jobs_incremental_second_run() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        std::fs::write(themes[0].path.join("web").join("other.js"), "other").unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let mut ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let first = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(first[0].file_count, 2);
+        assert_eq!(first[0].delta.added, 2);
+        assert!(manifest_path(temp.path(), &themes[0], &jobs[0].locale).exists());
+
+        std::fs::write(themes[0].path.join("web").join("other.js"), "changed!").unwrap();
+        let second = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(second[0].file_count, 1);
+        assert_eq!(second[0].delta.unchanged, 1);
+        assert_eq!(second[0].delta.changed, 1);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        assert_eq!(
+            std::fs::read_to_string(out.join("other.js")).unwrap(),
+            "changed!"
+        );
+
+        ctx.full = true;
+        let full = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(full[0].file_count, 2);
+    }
+
+    #[test]
+    fn test_deploy_jobs_other_output_settings_redeploy_everything() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        std::fs::write(themes[0].path.join("web").join("other.js"), "other").unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let mut ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+        assert_eq!(deploy_jobs(&jobs, &ctx, &|_| {})[0].file_count, 2);
+
+        // Nothing changed but the link mode: the copies must become links
+        let hardlinks = CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..dev_options()
+        };
+        ctx.options = &hardlinks;
+        let relinked = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(relinked[0].file_count, 2);
+        assert_eq!(relinked[0].delta.unchanged, 0);
+
+        let again = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(again[0].file_count, 0);
+        assert_eq!(again[0].delta.unchanged, 2);
+    }
+
+    #[test]
+    fn test_deploy_jobs_keep_going_partial_success() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        std::fs::write(themes[0].path.join("web").join("other.js"), "other").unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        // A directory in the way of app.js
+        std::fs::create_dir_all(out.join("app.js/dir")).unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            keep_going: true,
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::PartialSuccess));
+        assert_eq!(results[0].file_count, 1);
+        assert_eq!(results[0].failures.len(), 1);
+        assert_eq!(results[0].failures[0].dst, out.join("app.js"));
+        assert_eq!(
+            std::fs::read_to_string(out.join("other.js")).unwrap(),
+            "other"
+        );
+
+        // The failed file isn't recorded as deployed, so it is retried
+        std::fs::remove_dir_all(out.join("app.js")).unwrap();
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(results[0].file_count, 1);
+        assert!(out.join("app.js").is_file());
+    }
+
+    #[test]
+    fn test_deploy_jobs_resume_skips_done_and_verifies_in_flight() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        std::fs::write(themes[0].path.join("web").join("other.js"), "other").unwrap();
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("nl_NL")],
+        );
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let journal = Journal::open(temp.path(), false).unwrap();
+        journal.begin(&jobs).unwrap();
+        let mut ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: Some(&journal),
+        };
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(results
+            .iter()
+            .all(|r| matches!(r.status, DeployStatus::Success)));
+
+        // Interrupt nl_NL: begun, not done, manifest never written, one
+        // output file left half-copied
+        let journal_file = crate::journal::journal_path(temp.path());
+        let content = std::fs::read_to_string(&journal_file).unwrap();
+        let interrupted = format!("done\t{}\t", crate::journal::job_key(&jobs[1]));
+        let content: String = content
+            .lines()
+            .filter(|line| !line.starts_with(&interrupted))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        std::fs::write(&journal_file, content).unwrap();
+        std::fs::remove_file(manifest_path(temp.path(), &themes[0], &jobs[1].locale)).unwrap();
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[1].locale);
+        std::fs::write(out.join("other.js"), "ot").unwrap();
+
+        let resumed = Journal::open(temp.path(), true).unwrap();
+        ctx.journal = Some(&resumed);
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Resumed));
+        assert_eq!(results[0].file_count, 0);
+        // Only the file that doesn't match is copied again
+        assert!(matches!(results[1].status, DeployStatus::Success));
+        assert_eq!(results[1].file_count, 1);
+        assert_eq!(
+            std::fs::read_to_string(out.join("other.js")).unwrap(),
+            "other"
+        );
+
+        // Both are recorded as done now
+        let reopened = Journal::open(temp.path(), true).unwrap();
+        ctx.journal = Some(&reopened);
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(results
+            .iter()
+            .all(|r| matches!(r.status, DeployStatus::Resumed)));
+    }
+
+    #[test]
+    fn test_deploy_jobs_resume_redeploys_after_options_change() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let journal = Journal::open(temp.path(), false).unwrap();
+        journal.begin(&jobs).unwrap();
+        let mut ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: Some(&journal),
+        };
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+
+        // Resumed with --minify-js added: the job is done again, not skipped
+        let minify = CopyOptions {
+            minify: Some(Minify {
+                js: true,
+                ..Minify::default()
+            }),
+            ..dev_options()
+        };
+        let resumed = Journal::open(temp.path(), true).unwrap();
+        ctx.options = &minify;
+        ctx.journal = Some(&resumed);
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        assert!(out.join("app.min.js").is_file());
+
+        // With the same options it is resumed
+        let resumed = Journal::open(temp.path(), true).unwrap();
+        ctx.journal = Some(&resumed);
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Resumed));
+    }
+
+    #[test]
+    fn test_deploy_jobs_prune_removes_orphans() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        std::fs::create_dir_all(out.join("Old_Module")).unwrap();
+        std::fs::write(out.join("Old_Module/removed.js"), "old").unwrap();
+        std::fs::write(out.join(".htaccess"), "rewrite").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let mut ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(results[0].pruned.is_empty());
+        assert!(out.join("Old_Module/removed.js").exists());
+
+        ctx.prune = true;
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(
+            results[0].pruned.files,
+            vec![PathBuf::from("Old_Module/removed.js")]
+        );
+        assert_eq!(results[0].pruned.dirs, vec![PathBuf::from("Old_Module")]);
+        assert!(!out.join("Old_Module").exists());
+        assert!(out.join("app.js").exists());
+        assert!(out.join(".htaccess").exists());
+    }
+
+    #[test]
+    fn test_deploy_jobs_precompress_keeps_sidecars_in_sync() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let web = themes[0].path.join("web");
+        let big = "require(['jquery'], function ($) { $('body').show(); });\n".repeat(40);
+        std::fs::write(web.join("big.js"), &big).unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(out.join("gone.js.gz"), "orphan").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            precompress: Some(Precompress {
+                codecs: vec![Codec::Gzip],
+                extensions: vec!["js".to_string()],
+                min_size: 100,
+            }),
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+        let gunzip = |path: PathBuf| {
+            let mut out = String::new();
+            flate2::read::GzDecoder::new(std::fs::File::open(path).unwrap())
+                .read_to_string(&mut out)
+                .unwrap();
+            out
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(gunzip(out.join("big.js.gz")), big);
+        // Below the minimum size
+        assert!(!out.join("app.js.gz").exists());
+        // Sidecars of planned files survive pruning, others don't
+        assert_eq!(results[0].pruned.files, vec![PathBuf::from("gone.js.gz")]);
+
+        // A changed source gets a new sidecar
+        let changed = big.replace("show", "hide");
+        std::fs::write(web.join("big.js"), &changed).unwrap();
+        deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(gunzip(out.join("big.js.gz")), changed);
+
+        // An unchanged file whose sidecar went missing gets it back
+        std::fs::remove_file(out.join("big.js.gz")).unwrap();
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(results[0].file_count, 0);
+        assert_eq!(gunzip(out.join("big.js.gz")), changed);
+    }
+
+    #[test]
+    fn test_deploy_jobs_minify_js() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let web = themes[0].path.join("web");
+        std::fs::create_dir_all(web.join("tiny_mce")).unwrap();
+        std::fs::write(web.join("main.js"), "// Entry\nvar a = 1;\n").unwrap();
+        std::fs::write(web.join("tiny_mce/tinymce.js"), "var b = 2;\n").unwrap();
+        std::fs::write(web.join("lib.js"), "var c = 3;\n").unwrap();
+        std::fs::write(web.join("lib.min.js"), "shipped").unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            minify: Some(Minify {
+                js: true,
+                js_exclude: vec![regex::Regex::new("^frontend/Test/theme/en_US/tiny_mce/").unwrap()],
+                ..Minify::default()
+            }),
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("main.js")).unwrap(),
+            "// Entry\nvar a = 1;\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(out.join("main.min.js")).unwrap(),
+            "var a=1;"
+        );
+        assert_eq!(
+            std::fs::read_to_string(out.join("lib.min.js")).unwrap(),
+            "shipped"
+        );
+        assert!(out.join("tiny_mce/tinymce.js").exists());
+        assert!(!out.join("tiny_mce/tinymce.min.js").exists());
+        assert_eq!(stats.minified.js.saved.load(Ordering::Relaxed), 12);
+        assert_eq!(
+            results[0].minified,
+            MinifySaved {
+                js: 12,
+                ..MinifySaved::default()
+            }
+        );
+
+        // Derived files are tracked like any other: nothing changed, nothing written
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(results[0].file_count, 0);
+        std::fs::write(web.join("main.js"), "var a = 2;\n").unwrap();
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert_eq!(results[0].file_count, 2);
+        assert_eq!(
+            std::fs::read_to_string(out.join("main.min.js")).unwrap(),
+            "var a=2;"
+        );
+
+        // Without minification they are orphans
+        let plain = dev_options();
+        let ctx = DeployContext {
+            options: &plain,
+            ..ctx
+        };
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(results[0]
+            .pruned
+            .files
+            .contains(&PathBuf::from("main.min.js")));
+        assert!(!out.join("main.min.js").exists());
+    }
+
+    #[test]
+    fn test_deploy_jobs_resolves_css_module_urls() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let web = themes[0].path.join("web");
+        std::fs::create_dir_all(web.join("css")).unwrap();
+        std::fs::create_dir_all(themes[0].path.join("Magento_Catalog/web/images")).unwrap();
+        std::fs::write(
+            themes[0].path.join("Magento_Catalog/web/images/foo.png"),
+            "png",
+        )
+        .unwrap();
+        std::fs::write(
+            web.join("css/styles.css"),
+            ".a { background: url('Magento_Catalog::images/foo.png'); }\n.b { background: url(Magento_Theme::missing.png); }\n",
+        )
+        .unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            minify: Some(Minify {
+                css: true,
+                ..Minify::default()
+            }),
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("css/styles.css")).unwrap(),
+            ".a { background: url('../Magento_Catalog/images/foo.png'); }\n.b { background: url(../Magento_Theme/missing.png); }\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(out.join("css/styles.min.css")).unwrap(),
+            ".a{background:url('../Magento_Catalog/images/foo.png')}.b{background:url(../Magento_Theme/missing.png)}"
+        );
+        // Counted per kind for the job's summary line
+        assert!(results[0].minified.css > 0);
+        assert_eq!(
+            results[0].minified,
+            MinifySaved {
+                css: stats.minified.css.saved.load(Ordering::Relaxed),
+                ..MinifySaved::default()
+            }
+        );
+        let unresolved: Vec<(&Path, &str)> = results[0]
+            .unresolved_urls
+            .iter()
+            .map(|url| (url.css.as_path(), url.reference.as_str()))
+            .collect();
+        assert_eq!(
+            unresolved,
+            vec![
+                (Path::new("css/styles.css"), "Magento_Theme::missing.png"),
+                (
+                    Path::new("css/styles.min.css"),
+                    "Magento_Theme::missing.png"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deploy_jobs_requirejs_config() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let module = temp.path().join("vendor/magento/module-catalog");
+        std::fs::create_dir_all(module.join("etc")).unwrap();
+        std::fs::create_dir_all(module.join("view/frontend")).unwrap();
+        std::fs::write(
+            module.join("etc/module.xml"),
+            r#"<config><module name="Magento_Catalog"/></config>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            module.join("view/frontend/requirejs-config.js"),
+            "var config = {catalog: 1};",
+        )
+        .unwrap();
+        std::fs::write(
+            themes[0].path.join("requirejs-config.js"),
+            "var config = {theme: 1};",
+        )
+        .unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            requirejs_config: true,
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: true,
+            check_space: false,
+            journal: None,
+        };
+
+        let expected = "(function(require){\n(function() {\nvar config = {catalog: 1};\nrequire.config(config);\n})();\n(function() {\nvar config = {theme: 1};\nrequire.config(config);\n})();\n\n\n\n})(require);";
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("requirejs-config.js")).unwrap(),
+            expected
+        );
+
+        // Kept by --prune, rewritten on the next run
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(results[0].pruned.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(out.join("requirejs-config.js")).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_deploy_jobs_minify_html_in_place() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let web = themes[0].path.join("web");
+        std::fs::create_dir_all(web.join("template")).unwrap();
+        std::fs::write(
+            web.join("template/list.html"),
+            "<!-- List -->\n<ul>\n    <!-- ko foreach: items -->\n    <li></li>\n    <!-- /ko -->\n</ul>\n",
+        )
+        .unwrap();
+        std::fs::write(web.join("page.html"), "<!-- Page -->\n").unwrap();
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = CopyOptions {
+            minify: Some(Minify {
+                html: true,
+                ..Minify::default()
+            }),
+            ..dev_options()
+        };
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("template/list.html")).unwrap(),
+            "<ul> <!-- ko foreach: items --> <li></li> <!-- /ko --> </ul>"
+        );
+        assert!(!out.join("template/list.min.html").exists());
+        assert_eq!(
+            std::fs::read_to_string(out.join("page.html")).unwrap(),
+            "<!-- Page -->\n"
+        );
+        assert_eq!(stats.minified.html.files.load(Ordering::Relaxed), 1);
+        assert_eq!(results[0].minified.html, 27);
+    }
+
+    #[test]
+    fn test_deploy_jobs_atomic_swaps_and_keeps_previous() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        let source = themes[0].path.join("web").join("app.js");
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: false,
+            atomic: true,
+            check_space: false,
+            journal: None,
+        };
+
+        let first = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(first[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("app.js")).unwrap(),
+            "content"
+        );
+
+        std::fs::write(&source, "updated").unwrap();
+        let second = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(second[0].status, DeployStatus::Success));
+        assert_eq!(
+            std::fs::read_to_string(out.join("app.js")).unwrap(),
+            "updated"
+        );
+        assert_eq!(
+            std::fs::read_to_string(previous_path(&out).join("app.js")).unwrap(),
+            "content"
+        );
+        assert!(!staging_path(&out).exists());
+    }
+
+    #[test]
+    fn test_deploy_jobs_atomic_cancelled_leaves_live_untouched() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(&themes, &[LocaleCode::new("en_US")]);
+        let out = output_path_for_theme(temp.path(), &themes[0], &jobs[0].locale);
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(out.join("app.js"), "live").unwrap();
+
+        let shutdown = AtomicBool::new(true);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: true,
+            prune: false,
+            atomic: true,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_jobs(&jobs, &ctx, &|_| {});
+        assert!(matches!(results[0].status, DeployStatus::Cancelled));
+        assert_eq!(std::fs::read_to_string(out.join("app.js")).unwrap(), "live");
+        assert!(!staging_path(&out).exists());
+        assert!(!previous_path(&out).exists());
+    }
+
+    #[test]
+    fn test_deploy_quick_prune_replicas() {
+        let temp = TempDir::new().unwrap();
+        let themes = vec![hyva_theme(&temp, "theme")];
+        let jobs = job_matrix(
+            &themes,
+            &[LocaleCode::new("en_US"), LocaleCode::new("de_DE")],
+        );
+        let replica = output_path_for_theme(temp.path(), &themes[0], &jobs[1].locale);
+        std::fs::create_dir_all(&replica).unwrap();
+        std::fs::write(replica.join("stale.js"), "old").unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let options = dev_options();
+        let ctx = DeployContext {
+            all_themes: &themes,
+            magento_root: temp.path(),
+            shutdown: &shutdown,
+            stats: &stats,
+            verbose: false,
+            options: &options,
+            full: false,
+            prune: true,
+            atomic: false,
+            check_space: false,
+            journal: None,
+        };
+
+        let results = deploy_quick(&jobs, &ctx, &|_| {});
+        assert_eq!(results[1].pruned.files, vec![PathBuf::from("stale.js")]);
+        assert!(replica.join("app.js").exists());
+        assert!(!replica.join("stale.js").exists());
+    }
+
     // ==================== deploy_theme tests ====================
 
     #[test]
@@ -672,7 +2651,15 @@ mod tests {
         let shutdown = AtomicBool::new(false);
         let stats = DeployStats::new();
 
-        let result = deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        let result = deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert!(matches!(result.status, DeployStatus::Success));
         assert!(result.file_count > 0);
@@ -704,7 +2691,15 @@ mod tests {
         let shutdown = AtomicBool::new(true);
         let stats = DeployStats::new();
 
-        let result = deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        let result = deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert!(matches!(result.status, DeployStatus::Cancelled));
     }
@@ -733,7 +2728,15 @@ mod tests {
         let shutdown = AtomicBool::new(false);
         let stats = DeployStats::new();
 
-        let result = deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        let result = deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert!(matches!(result.status, DeployStatus::Success));
         assert_eq!(result.file_count, 0);
@@ -767,7 +2770,15 @@ mod tests {
         let shutdown = AtomicBool::new(false);
         let stats = DeployStats::new();
 
-        let result = deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        let result = deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert!(matches!(result.status, DeployStatus::Success));
         assert_eq!(result.file_count, 1);
@@ -798,7 +2809,15 @@ mod tests {
         let shutdown = AtomicBool::new(false);
         let stats = DeployStats::new();
 
-        let result = deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        let result = deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert!(matches!(result.status, DeployStatus::Success));
         assert_eq!(result.file_count, 1);
@@ -842,7 +2861,15 @@ mod tests {
         let shutdown = AtomicBool::new(false);
         let stats = DeployStats::new();
 
-        deploy_theme(&job, &[theme], temp.path(), &shutdown, &stats, false, true);
+        deploy_theme(
+            &job,
+            &[theme],
+            temp.path(),
+            &shutdown,
+            &stats,
+            false,
+            &dev_options(),
+        );
 
         assert_eq!(stats.files_copied.0.load(Ordering::Relaxed), 1);
         assert!(stats.bytes_copied.0.load(Ordering::Relaxed) > 0);
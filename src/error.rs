@@ -16,6 +16,14 @@ pub enum DeployError {
     #[error("No space left on device for {path}")]
     DiskFull { path: PathBuf },
 
+    #[error("Not enough space on {path}: need {needed} {resource}, {available} available (use --force to deploy anyway)")]
+    InsufficientSpace {
+        path: PathBuf,
+        resource: &'static str,
+        needed: u64,
+        available: u64,
+    },
+
     #[error("Theme not found: {theme}")]
     ThemeNotFound { theme: String },
 
@@ -34,6 +42,13 @@ pub enum DeployError {
         source: std::io::Error,
     },
 
+    #[error("Permission denied: {path}")]
+    PermissionDenied {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("Failed to create directory: {path}")]
     CreateDirFailed {
         path: PathBuf,
@@ -41,12 +56,44 @@ pub enum DeployError {
         source: std::io::Error,
     },
 
+    #[error("Failed to remove {path}")]
+    PruneFailed {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to swap {staging} into {output}")]
+    SwapFailed {
+        staging: PathBuf,
+        output: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to precompress {path}")]
+    CompressFailed {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("bin/magento setup:static-content:deploy failed with exit code {code}: {stderr}")]
     MagentoFailed { code: i32, stderr: String },
 
     #[error("Invalid locale format '{locale}': expected xx_YY (e.g., en_US)")]
     InvalidLocale { locale: String },
 
+    #[error("Another deploy is running on this Magento root ({holder}; lock {path}); use --wait to queue behind it")]
+    Locked { path: PathBuf, holder: String },
+
+    #[error("Gave up after {waited}s waiting for the deploy lock {path} ({holder})")]
+    LockTimeout {
+        path: PathBuf,
+        holder: String,
+        waited: u64,
+    },
+
     #[error("Deployment cancelled")]
     Cancelled,
 
@@ -54,6 +101,22 @@ pub enum DeployError {
     Io(#[from] std::io::Error),
 }
 
+impl DeployError {
+    /// Underlying I/O error, if any
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            DeployError::CopyFailed { source, .. }
+            | DeployError::PermissionDenied { source, .. }
+            | DeployError::CreateDirFailed { source, .. }
+            | DeployError::PruneFailed { source, .. }
+            | DeployError::SwapFailed { source, .. }
+            | DeployError::CompressFailed { source, .. }
+            | DeployError::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,6 +137,71 @@ mod tests {
         assert!(format!("{}", err).contains("No space left on device"));
     }
 
+    #[test]
+    fn test_error_prune_failed_display() {
+        let err = DeployError::PruneFailed {
+            path: PathBuf::from("/pub/static/old.js"),
+            source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        };
+        assert!(format!("{}", err).contains("Failed to remove /pub/static/old.js"));
+    }
+
+    #[test]
+    fn test_error_swap_failed_display() {
+        let err = DeployError::SwapFailed {
+            staging: PathBuf::from("/static/.en_US.staging"),
+            output: PathBuf::from("/static/en_US"),
+            source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        };
+        assert!(format!("{}", err).contains("Failed to swap /static/.en_US.staging into"));
+    }
+
+    #[test]
+    fn test_error_permission_denied_display() {
+        let err = DeployError::PermissionDenied {
+            path: PathBuf::from("/pub/static/frontend"),
+            source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Permission denied: /pub/static/frontend"
+        );
+    }
+
+    #[test]
+    fn test_error_io_error() {
+        let err = DeployError::CreateDirFailed {
+            path: PathBuf::from("/dir"),
+            source: std::io::Error::from(std::io::ErrorKind::Interrupted),
+        };
+        assert_eq!(
+            err.io_error().map(|e| e.kind()),
+            Some(std::io::ErrorKind::Interrupted)
+        );
+        assert!(DeployError::Cancelled.io_error().is_none());
+    }
+
+    #[test]
+    fn test_error_insufficient_space_display() {
+        let err = DeployError::InsufficientSpace {
+            path: PathBuf::from("/pub/static"),
+            resource: "inodes",
+            needed: 10,
+            available: 5,
+        };
+        assert!(format!("{}", err).contains("need 10 inodes, 5 available"));
+    }
+
+    #[test]
+    fn test_error_locked_display() {
+        let err = DeployError::Locked {
+            path: PathBuf::from("/var/static_deploy/deploy.lock"),
+            holder: "PID 42, started 0m 3s ago".to_string(),
+        };
+        assert!(format!("{}", err).contains("Another deploy is running"));
+        assert!(format!("{}", err).contains("PID 42"));
+    }
+
     #[test]
     fn test_error_theme_not_found_display() {
         let err = DeployError::ThemeNotFound {
@@ -126,7 +254,7 @@ mod tests {
 
     #[test]
     fn test_error_io_display() {
-        let io_err = std::io::Error::new(std::io::ErrorKind::Other, "io error");
+        let io_err = std::io::Error::other("io error");
         let err = DeployError::Io(io_err);
         assert!(format!("{}", err).contains("IO error"));
     }
@@ -140,7 +268,7 @@ mod tests {
 
     #[test]
     fn test_error_io_from() {
-        let io_err = std::io::Error::new(std::io::ErrorKind::Other, "test");
+        let io_err = std::io::Error::other("test");
         let err: DeployError = io_err.into();
         assert!(matches!(err, DeployError::Io(_)));
     }
@@ -0,0 +1,258 @@
+//! Run journal for resuming interrupted deploys.
+//!
+//! Every run appends to `var/static_deploy/journal.tsv`: a `begin` line for
+//! each job before anything is copied, and a `done` line with a fingerprint
+//! of the job's sources and output settings once its output is complete. A run that finishes
+//! with every job deployed removes the journal. After a cancelled or failed
+//! run, `--resume` skips jobs recorded as done (when their sources and the
+//! options shaping the output are unchanged) and re-verifies jobs that were begun but not finished,
+//! copying only the files whose output doesn't match yet.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::deployer::DeployJob;
+use crate::plan::DeployPlan;
+
+/// First line of every journal; bump when the format changes
+const JOURNAL_HEADER: &str = "# magento-static-deploy journal v1";
+
+/// Where the journal of a Magento installation lives
+pub fn journal_path(magento_root: &Path) -> PathBuf {
+    magento_root
+        .join("var")
+        .join("static_deploy")
+        .join("journal.tsv")
+}
+
+/// Journal key of a job (`area/Vendor/theme/locale`)
+pub fn job_key(job: &DeployJob) -> String {
+    format!(
+        "{}/{}/{}",
+        job.theme.area.as_str(),
+        job.theme.full_name(),
+        job.locale.as_str()
+    )
+}
+
+/// Fingerprint of the sources a plan resolves from, in priority order, and
+/// of the `output_settings` it is deployed with. Changes when a module or
+/// theme is added, removed or reordered, or when the options change.
+pub fn sources_fingerprint(plan: &DeployPlan, settings: &str) -> u64 {
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    hasher.update(settings.as_bytes());
+    hasher.update(b"\0");
+    for source in plan.sources() {
+        hasher.update(source.origin().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(source.path().as_os_str().as_encoded_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.digest()
+}
+
+/// Fingerprint of the `output_settings` alone, for jobs that aren't planned
+/// (delegated to Magento or replicated from another locale)
+pub fn settings_fingerprint(settings: &str) -> u64 {
+    xxhash_rust::xxh3::xxh3_64(settings.as_bytes())
+}
+
+/// The journal of the current run, plus what a resumed run recorded
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: Mutex<File>,
+    /// Jobs a previous run completed, with their fingerprint
+    done: HashMap<String, u64>,
+    /// Jobs a previous run began
+    begun: HashSet<String>,
+}
+
+impl Journal {
+    /// Start a journal. With `resume`, the existing journal's entries are
+    /// loaded and the run appends to it; otherwise it starts empty.
+    pub fn open(magento_root: &Path, resume: bool) -> io::Result<Self> {
+        let path = journal_path(magento_root);
+        let mut done = HashMap::new();
+        let mut begun = HashSet::new();
+
+        let previous = if resume {
+            fs::read_to_string(&path).ok()
+        } else {
+            None
+        };
+        let previous = previous.filter(|content| content.lines().next() == Some(JOURNAL_HEADER));
+        if let Some(content) = &previous {
+            for line in content.lines().skip(1) {
+                let mut fields = line.split('\t');
+                match (fields.next(), fields.next(), fields.next()) {
+                    (Some("begin"), Some(key), None) => {
+                        begun.insert(key.to_string());
+                    }
+                    (Some("done"), Some(key), Some(fingerprint)) => {
+                        if let Ok(fingerprint) = u64::from_str_radix(fingerprint, 16) {
+                            done.insert(key.to_string(), fingerprint);
+                        }
+                    }
+                    // Torn last line of a killed run
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = if let Some(content) = &previous {
+            let mut file = OpenOptions::new().append(true).open(&path)?;
+            if !content.ends_with('\n') {
+                // Terminate a torn line so new entries start on their own
+                file.write_all(b"\n")?;
+            }
+            file
+        } else {
+            let mut file = File::create(&path)?;
+            writeln!(file, "{JOURNAL_HEADER}")?;
+            file
+        };
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            done,
+            begun,
+        })
+    }
+
+    /// Whether a previous run completed `job` with the same `fingerprint`
+    pub fn is_done(&self, job: &DeployJob, fingerprint: u64) -> bool {
+        self.done.get(&job_key(job)) == Some(&fingerprint)
+    }
+
+    /// Whether a previous run began `job` (done or not)
+    pub fn was_begun(&self, job: &DeployJob) -> bool {
+        self.begun.contains(&job_key(job))
+    }
+
+    /// Record that `jobs` are about to be deployed
+    pub fn begin(&self, jobs: &[DeployJob]) -> io::Result<()> {
+        let lines: String = jobs
+            .iter()
+            .map(|job| format!("begin\t{}\n", job_key(job)))
+            .collect();
+        self.append(&lines)
+    }
+
+    /// Record that `job`'s output is complete
+    pub fn done(&self, job: &DeployJob, fingerprint: u64) {
+        let line = format!("done\t{}\t{:016x}\n", job_key(job), fingerprint);
+        if let Err(e) = self.append(&line) {
+            eprintln!("Warning: failed to write {}: {e}", self.path.display());
+        }
+    }
+
+    fn append(&self, lines: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        file.write_all(lines.as_bytes())?;
+        file.flush()
+    }
+
+    /// The run deployed everything: nothing left to resume
+    pub fn remove(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deployer::job_matrix;
+    use crate::theme::{Area, LocaleCode, Theme, ThemeType};
+    use tempfile::TempDir;
+
+    fn jobs() -> Vec<DeployJob> {
+        let theme = Theme {
+            vendor: "Vendor".to_string(),
+            name: "theme".to_string(),
+            area: Area::Frontend,
+            path: PathBuf::from("/theme"),
+            parent: None,
+            theme_type: ThemeType::Hyva,
+        };
+        job_matrix(
+            &[theme],
+            &[LocaleCode::new("en_US"), LocaleCode::new("nl_NL")],
+        )
+    }
+
+    // ==================== Journal tests ====================
+
+    #[test]
+    fn test_job_key() {
+        assert_eq!(job_key(&jobs()[1]), "frontend/Vendor/theme/nl_NL");
+    }
+
+    #[test]
+    fn test_journal_resume_reads_previous_run() {
+        let temp = TempDir::new().unwrap();
+        let jobs = jobs();
+
+        let journal = Journal::open(temp.path(), false).unwrap();
+        journal.begin(&jobs).unwrap();
+        journal.done(&jobs[0], 42);
+        drop(journal);
+
+        let resumed = Journal::open(temp.path(), true).unwrap();
+        assert!(resumed.is_done(&jobs[0], 42));
+        assert!(!resumed.is_done(&jobs[0], 7));
+        assert!(!resumed.is_done(&jobs[1], 42));
+        assert!(resumed.was_begun(&jobs[1]));
+
+        // A run that doesn't resume starts over
+        let fresh = Journal::open(temp.path(), false).unwrap();
+        assert!(!fresh.is_done(&jobs[0], 42));
+        assert!(!fresh.was_begun(&jobs[1]));
+    }
+
+    #[test]
+    fn test_journal_ignores_torn_lines_and_unknown_format() {
+        let temp = TempDir::new().unwrap();
+        let path = journal_path(temp.path());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            format!("{JOURNAL_HEADER}\ndone\tfrontend/Vendor/theme/en_US\t2a\ndone\tfront"),
+        )
+        .unwrap();
+        let journal = Journal::open(temp.path(), true).unwrap();
+        assert!(journal.is_done(&jobs()[0], 42));
+        journal.done(&jobs()[1], 1);
+        drop(journal);
+        let journal = Journal::open(temp.path(), true).unwrap();
+        assert!(journal.is_done(&jobs()[1], 1));
+
+        fs::write(
+            &path,
+            "something else\ndone\tfrontend/Vendor/theme/en_US\t2a\n",
+        )
+        .unwrap();
+        let journal = Journal::open(temp.path(), true).unwrap();
+        assert!(!journal.is_done(&jobs()[0], 42));
+    }
+
+    #[test]
+    fn test_journal_remove() {
+        let temp = TempDir::new().unwrap();
+        let journal = Journal::open(temp.path(), false).unwrap();
+        assert!(journal_path(temp.path()).exists());
+
+        journal.remove().unwrap();
+        assert!(!journal_path(temp.path()).exists());
+    }
+}
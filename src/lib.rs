@@ -22,18 +22,69 @@
 //! let themes = discover_themes(&magento_root, Area::Frontend)?;
 //! ```
 
+/// Atomic deploys through staging directories
+pub mod atomic;
+
+/// Permissions, ownership and timestamps of outputs
+pub mod attrs;
+
+/// Removal of deployed static content (`clean`)
+pub mod clean;
+
+/// Precompressed gzip/brotli/zstd sidecars
+pub mod compress;
+
 /// CLI configuration and argument parsing
 pub mod config;
 
 /// File copying operations with buffered I/O
 pub mod copier;
 
+/// Module notation in CSS `url()` references
+pub mod cssurl;
+
 /// Theme deployment orchestration
 pub mod deployer;
 
 /// Error types for deployment operations
 pub mod error;
 
+/// Run journal for resuming interrupted deploys
+pub mod journal;
+
+/// Exclusive deploy lock on a Magento root
+pub mod lock;
+
+/// Previous-deploy manifests for incremental deploys
+pub mod manifest;
+
+/// JS, CSS and HTML template minification
+pub mod minify;
+
+/// Resolved destination → source deploy plans
+pub mod plan;
+
+/// CPU and I/O scheduling priority
+pub mod priority;
+
+/// Global file-level work queue
+pub mod queue;
+
+/// Removal of orphaned files from earlier deploys
+pub mod prune;
+
+/// Bytes/sec and files/sec copy limits
+pub mod ratelimit;
+
+/// Merged `requirejs-config.js` generation
+pub mod requirejs;
+
+/// Retries for transient I/O errors, file descriptor back-off
+pub mod retry;
+
+/// Pre-flight disk space check
+pub mod space;
+
 /// Theme and module scanning
 pub mod scanner;
 
@@ -0,0 +1,270 @@
+//! Exclusive deploy lock on a Magento root.
+//!
+//! Two deploys writing the same `pub/static` interleave their overrides, so
+//! every run takes an `flock(2)` on `var/static_deploy/deploy.lock` before
+//! touching any output and holds it until it exits. The holder writes its
+//! PID and start time into the file so a contending run can say who it is
+//! waiting for. The kernel releases the lock when the holder exits, even if
+//! it is killed, so a stale file never blocks a deploy.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::DeployError;
+
+/// How often a waiting run retries the lock
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where the deploy lock of a Magento installation lives
+pub fn lock_path(magento_root: &Path) -> PathBuf {
+    magento_root
+        .join("var")
+        .join("static_deploy")
+        .join("deploy.lock")
+}
+
+/// The run holding the lock, as recorded in the lock file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// Start time in seconds since the Unix epoch
+    pub started: u64,
+}
+
+impl LockHolder {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            started: unix_now(),
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut fields = content.trim_end().split('\t');
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(pid), Some(started), None) => Some(Self {
+                pid: pid.parse().ok()?,
+                started: started.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let age = unix_now().saturating_sub(self.started);
+        write!(
+            f,
+            "PID {}, started {}m {}s ago",
+            self.pid,
+            age / 60,
+            age % 60
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Describe the holder recorded in `file` (it may not have written it yet)
+fn read_holder(file: &mut File) -> String {
+    let mut content = String::new();
+    let read = file
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_string(&mut content));
+    match read.ok().and_then(|_| LockHolder::parse(&content)) {
+        Some(holder) => holder.to_string(),
+        None => "unknown holder".to_string(),
+    }
+}
+
+/// Try to take the lock without blocking; `false` when another run holds it
+#[cfg(target_os = "linux")]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor is owned by `file` and stays open for the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+/// Locking needs `flock(2)`; elsewhere deploys aren't serialized
+#[cfg(not(target_os = "linux"))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Held for as long as the deploy runs; dropping it releases the lock
+#[derive(Debug)]
+pub struct DeployLock {
+    _file: File,
+}
+
+impl DeployLock {
+    /// Take the lock of `magento_root`. When another run holds it, fail
+    /// right away with `wait` unset, otherwise retry until it is released,
+    /// `wait` elapses (`Duration::MAX` waits indefinitely) or `shutdown` is
+    /// set.
+    pub fn acquire(
+        magento_root: &Path,
+        wait: Option<Duration>,
+        shutdown: &AtomicBool,
+    ) -> Result<Self, DeployError> {
+        let path = lock_path(magento_root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| DeployError::CreateDirFailed {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        // Never truncate on open: the holder's details are in there
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let start = Instant::now();
+        let deadline = wait.and_then(|wait| start.checked_add(wait));
+        let mut announced = false;
+        while !try_lock(&file)? {
+            let holder = read_holder(&mut file);
+            let Some(wait) = wait else {
+                return Err(DeployError::Locked { path, holder });
+            };
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(DeployError::LockTimeout {
+                    path,
+                    holder,
+                    waited: wait.as_secs(),
+                });
+            }
+            if shutdown.load(Ordering::Relaxed) {
+                return Err(DeployError::Cancelled);
+            }
+            if !announced {
+                eprintln!("Waiting for the deploy lock ({holder})...");
+                announced = true;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        // Reading the previous holder moved the cursor
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        let holder = LockHolder::current();
+        writeln!(file, "{}\t{}", holder.pid, holder.started)?;
+        file.flush()?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // ==================== LockHolder tests ====================
+
+    #[test]
+    fn test_lock_holder_parse() {
+        assert_eq!(
+            LockHolder::parse("1234\t1700000000\n"),
+            Some(LockHolder {
+                pid: 1234,
+                started: 1_700_000_000
+            })
+        );
+        assert_eq!(LockHolder::parse(""), None);
+        assert_eq!(LockHolder::parse("1234"), None);
+        assert_eq!(LockHolder::parse("pid\t1700000000"), None);
+    }
+
+    #[test]
+    fn test_lock_holder_display() {
+        let holder = LockHolder {
+            pid: 42,
+            started: unix_now() - 125,
+        };
+        assert_eq!(holder.to_string(), "PID 42, started 2m 5s ago");
+    }
+
+    // ==================== DeployLock tests ====================
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lock_is_exclusive() {
+        let temp = TempDir::new().unwrap();
+        let shutdown = AtomicBool::new(false);
+
+        let lock = DeployLock::acquire(temp.path(), None, &shutdown).unwrap();
+        let content = fs::read_to_string(lock_path(temp.path())).unwrap();
+        assert_eq!(
+            LockHolder::parse(&content).map(|h| h.pid),
+            Some(std::process::id())
+        );
+
+        let err = DeployLock::acquire(temp.path(), None, &shutdown).unwrap_err();
+        assert!(matches!(err, DeployError::Locked { .. }));
+        assert!(err
+            .to_string()
+            .contains(&format!("PID {}", std::process::id())));
+
+        let err = DeployLock::acquire(temp.path(), Some(Duration::from_millis(150)), &shutdown)
+            .unwrap_err();
+        assert!(matches!(err, DeployError::LockTimeout { .. }));
+
+        drop(lock);
+        DeployLock::acquire(temp.path(), None, &shutdown).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lock_wait_until_released() {
+        let temp = TempDir::new().unwrap();
+        let shutdown = AtomicBool::new(false);
+        let lock = DeployLock::acquire(temp.path(), None, &shutdown).unwrap();
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+        let _lock = DeployLock::acquire(temp.path(), Some(Duration::MAX), &shutdown).unwrap();
+        release.join().unwrap();
+
+        // The previous holder's details are replaced, not appended to
+        let content = fs::read_to_string(lock_path(temp.path())).unwrap();
+        assert_eq!(
+            LockHolder::parse(&content).map(|h| h.pid),
+            Some(std::process::id())
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lock_wait_cancelled() {
+        let temp = TempDir::new().unwrap();
+        let _lock = DeployLock::acquire(temp.path(), None, &AtomicBool::new(false)).unwrap();
+
+        let shutdown = AtomicBool::new(true);
+        let err = DeployLock::acquire(temp.path(), Some(Duration::MAX), &shutdown).unwrap_err();
+        assert!(matches!(err, DeployError::Cancelled));
+    }
+}
@@ -1,17 +1,26 @@
+use std::path::Path;
 use std::process::ExitCode;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use rayon::prelude::*;
 
-use magento_static_deploy::config::{Cli, Config};
+use magento_static_deploy::atomic::rollback;
+use magento_static_deploy::clean::{clean_targets, remove_target};
+use magento_static_deploy::config::{CleanArgs, Cli, Command, Config};
+use magento_static_deploy::copier::{CopyMethod, CopyMode, LinkMode};
 use magento_static_deploy::deployer::{
-    collect_results, deploy_theme, job_matrix, DeployStats, DeployStatus,
+    collect_results, deploy_jobs, deploy_quick, job_matrix, output_path_for_theme, DeployContext,
+    DeployJob, DeployResult, DeployStats, DeployStatus, DeployStrategy,
 };
+use magento_static_deploy::error::DeployError;
+use magento_static_deploy::journal::Journal;
+use magento_static_deploy::lock::DeployLock;
+use magento_static_deploy::manifest::{manifest_path, ManifestDelta};
+use magento_static_deploy::priority::{set_io_priority, set_nice};
 use magento_static_deploy::scanner::discover_themes;
 use magento_static_deploy::theme::Theme;
 
@@ -26,20 +35,21 @@ fn main() -> ExitCode {
 }
 
 fn run() -> Result<ExitCode> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    if let Some(Command::Clean(args)) = cli.command.take() {
+        return clean(&args);
+    }
     let config = Config::from_cli(cli)?;
+    validate_magento_root(&config.magento_root)?;
 
-    // Validate Magento root
-    if !config.magento_root.exists() {
-        bail!("Magento root not found: {}", config.magento_root.display());
+    // Lower priorities before any thread is started, so every worker
+    // (and bin/magento) inherits them
+    if let Some(nice) = config.nice {
+        set_nice(nice).with_context(|| format!("Failed to set --nice {nice}"))?;
     }
-
-    let env_php = config.magento_root.join("app").join("etc").join("env.php");
-    if !env_php.exists() {
-        bail!(
-            "Not a Magento installation: {} (app/etc/env.php not found)",
-            config.magento_root.display()
-        );
+    if let Some(priority) = config.ionice {
+        set_io_priority(priority)
+            .with_context(|| format!("Failed to set --ionice {}", priority.as_str()))?;
     }
 
     // Setup Ctrl+C handler
@@ -50,6 +60,16 @@ fn run() -> Result<ExitCode> {
     })
     .context("Failed to set Ctrl+C handler")?;
 
+    // One deploy per Magento root at a time; held until we exit
+    let _lock = match DeployLock::acquire(&config.magento_root, config.lock_wait, &shutdown) {
+        Ok(lock) => lock,
+        Err(DeployError::Cancelled) => {
+            eprintln!("Deployment cancelled");
+            return Ok(ExitCode::from(130));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
     // Configure Rayon thread pool
     if let Err(e) = rayon::ThreadPoolBuilder::new()
         .num_threads(config.jobs)
@@ -95,6 +115,10 @@ fn run() -> Result<ExitCode> {
         &config.locales,
     );
 
+    if config.rollback {
+        return Ok(rollback_jobs(&jobs, &config.magento_root));
+    }
+
     let total_jobs = jobs.len();
     if config.verbose {
         eprintln!(
@@ -106,8 +130,16 @@ fn run() -> Result<ExitCode> {
         );
     }
 
+    // Record the run so an interrupted deploy can be resumed
+    let journal = Journal::open(&config.magento_root, config.resume)
+        .context("Failed to open deploy journal")?;
+    journal
+        .begin(&jobs)
+        .context("Failed to write deploy journal")?;
+
     let start = Instant::now();
     let stats = DeployStats::new();
+    let copy_options = config.copy_options();
 
     // Setup progress bars (only in verbose mode)
     let multi_progress = MultiProgress::new();
@@ -125,40 +157,45 @@ fn run() -> Result<ExitCode> {
         None
     };
 
-    // Execute jobs in parallel
-    let results: Vec<_> = jobs
-        .par_iter()
-        .enumerate()
-        .map(|(idx, job)| {
-            if config.verbose {
-                eprintln!(
-                    "[{}/{}] Deploying {}/{}/{}...",
-                    idx + 1,
-                    total_jobs,
-                    job.theme.area.as_str(),
-                    job.theme.full_name(),
-                    job.locale
-                );
-            }
+    let ctx = DeployContext {
+        all_themes: &all_themes,
+        magento_root: &config.magento_root,
+        shutdown: &shutdown,
+        stats: &stats,
+        verbose: config.verbose,
+        options: &copy_options,
+        full: config.full,
+        prune: config.prune,
+        atomic: config.atomic,
+        check_space: !config.force,
+        journal: Some(&journal),
+    };
 
-            let result = deploy_theme(
-                job,
-                &all_themes,
-                &config.magento_root,
-                &shutdown,
-                &stats,
-                config.verbose,
-                config.include_dev,
+    let jobs_done = AtomicUsize::new(0);
+    let on_job_done = |job: &DeployJob| {
+        let done = jobs_done.fetch_add(1, Ordering::Relaxed) + 1;
+        if config.verbose {
+            eprintln!(
+                "[{}/{}] Deployed {}/{}/{}",
+                done,
+                total_jobs,
+                job.theme.area.as_str(),
+                job.theme.full_name(),
+                job.locale
             );
+        }
 
-            // Update progress bar
-            if let Some(ref pb) = main_progress {
-                pb.inc(1);
-            }
+        // Update progress bar
+        if let Some(ref pb) = main_progress {
+            pb.inc(1);
+        }
+    };
 
-            result
-        })
-        .collect();
+    // Execute all jobs through one file-level work queue
+    let results = match config.strategy {
+        DeployStrategy::Standard => deploy_jobs(&jobs, &ctx, &on_job_done),
+        DeployStrategy::Quick => deploy_quick(&jobs, &ctx, &on_job_done),
+    };
 
     // Finish progress bar
     if let Some(pb) = main_progress {
@@ -167,12 +204,19 @@ fn run() -> Result<ExitCode> {
 
     // Check for cancellation
     if shutdown.load(Ordering::Relaxed) {
-        eprintln!("\nDeployment cancelled");
+        eprintln!("\nDeployment cancelled (continue with --resume)");
         return Ok(ExitCode::from(130));
     }
 
     // Aggregate results
     let (results, has_success, has_failure) = collect_results(results);
+
+    // Nothing left to resume once every job is deployed
+    if !has_failure {
+        if let Err(e) = journal.remove() {
+            eprintln!("Warning: failed to remove deploy journal: {e}");
+        }
+    }
     let duration = start.elapsed();
     let total_files = stats.files_copied.0.load(Ordering::Relaxed);
     let throughput = if duration.as_secs_f64() > 0.0 {
@@ -189,22 +233,171 @@ fn run() -> Result<ExitCode> {
         throughput
     );
 
+    if config.copy_mode != CopyMode::Copy {
+        println!(
+            "Cloned {} file(s), copied {}",
+            stats.methods.get(CopyMethod::Reflink),
+            stats.methods.copied()
+        );
+    }
+
+    if config.link_mode != LinkMode::Copy {
+        println!(
+            "Linked {} file(s) ({}), copied {}",
+            stats.methods.linked(),
+            config.link_mode.as_str(),
+            stats.methods.copied() + stats.methods.get(CopyMethod::Reflink)
+        );
+    }
+
+    let retries = stats.retries.0.load(Ordering::Relaxed);
+    let fd_backoffs = stats.fd_backoffs.0.load(Ordering::Relaxed);
+    if retries > 0 || fd_backoffs > 0 {
+        println!(
+            "Retried {} file(s) after transient I/O errors; reduced concurrency {} time(s) on EMFILE",
+            retries, fd_backoffs
+        );
+    }
+
+    if let Some(limit) = &copy_options.rate_limit {
+        println!(
+            "Rate limited: workers waited {:.2}s in total",
+            limit.waited().as_secs_f64()
+        );
+    }
+
+    if config.precompress.is_some() {
+        let sidecars = &stats.sidecars;
+        println!(
+            "Precompressed: {} sidecar(s) written ({:.1} MB saved), {} skipped (no gain)",
+            sidecars.written.load(Ordering::Relaxed),
+            sidecars.saved.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            sidecars.no_gain.load(Ordering::Relaxed)
+        );
+    }
+
+    if let Some(minify) = &config.minify {
+        let kinds = [
+            (minify.js, "JS", &stats.minified.js),
+            (minify.css, "CSS", &stats.minified.css),
+            (minify.html, "HTML", &stats.minified.html),
+        ];
+        for (_, kind, counts) in kinds.iter().filter(|(enabled, ..)| *enabled) {
+            println!(
+                "Minified: {} {} file(s) ({:.1} KB saved), {} left as is",
+                counts.files.load(Ordering::Relaxed),
+                kind,
+                counts.saved.load(Ordering::Relaxed) as f64 / 1_000.0,
+                counts.unchanged.load(Ordering::Relaxed)
+            );
+        }
+    }
+
+    if config.skip_identical {
+        println!(
+            "Identical: {} file(s) left in place",
+            stats.methods.identical.load(Ordering::Relaxed)
+        );
+    }
+
+    if config.verbose {
+        let methods: Vec<String> = CopyMethod::ALL
+            .iter()
+            .map(|m| format!("{} {}", stats.methods.get(*m), m.as_str()))
+            .collect();
+        println!("Copy methods: {}", methods.join(", "));
+    }
+
+    // Delta against the previous deploy
+    let mut delta = ManifestDelta::default();
+    for result in &results {
+        delta.add(&result.delta);
+    }
+    if !config.full && (delta.unchanged > 0 || delta.changed > 0 || delta.removed > 0) {
+        println!(
+            "Incremental: {} added, {} changed, {} unchanged, {} no longer deployed",
+            delta.added, delta.changed, delta.unchanged, delta.removed
+        );
+    }
+
+    // Files removed by --prune
+    let pruned: Vec<&DeployResult> = results.iter().filter(|r| !r.pruned.is_empty()).collect();
+    if config.prune {
+        let files: usize = pruned.iter().map(|r| r.pruned.files.len()).sum();
+        let dirs: usize = pruned.iter().map(|r| r.pruned.dirs.len()).sum();
+        println!("Pruned {} orphaned file(s), {} empty dir(s)", files, dirs);
+    }
+    for result in &pruned {
+        let output =
+            output_path_for_theme(&config.magento_root, &result.job.theme, &result.job.locale);
+        for file in &result.pruned.files {
+            println!("  removed {}", output.join(file).display());
+        }
+    }
+
+    let unresolved: usize = results.iter().map(|r| r.unresolved_urls.len()).sum();
+    if unresolved > 0 {
+        println!("Unresolved module url() references in CSS: {}", unresolved);
+    }
+
     // Per-job breakdown
     for result in &results {
         let status_str = match &result.status {
+            DeployStatus::Success if result.delta.unchanged > 0 => format!(
+                "{} files ({} unchanged)",
+                result.file_count, result.delta.unchanged
+            ),
             DeployStatus::Success => format!("{} files", result.file_count),
+            DeployStatus::PartialSuccess => format!(
+                "PARTIAL: {} files, {} failed",
+                result.file_count,
+                result.failures.len()
+            ),
             DeployStatus::Delegated => "delegated to bin/magento".to_string(),
+            DeployStatus::Resumed => "already deployed by the interrupted run".to_string(),
             DeployStatus::Failed(e) => format!("FAILED: {e}"),
             DeployStatus::Cancelled => "cancelled".to_string(),
         };
 
+        let saved = &result.minified;
+        let minified = if saved.total() > 0 {
+            let kinds: Vec<String> = [("JS", saved.js), ("CSS", saved.css), ("HTML", saved.html)]
+                .iter()
+                .filter(|(_, bytes)| *bytes > 0)
+                .map(|(kind, bytes)| format!("{kind} {:.1} KB", *bytes as f64 / 1_000.0))
+                .collect();
+            format!(" (minified: {} saved)", kinds.join(", "))
+        } else {
+            String::new()
+        };
+
         println!(
-            "  {}/{}/{}: {}",
+            "  {}/{}/{}: {}{}",
             result.job.theme.area.as_str(),
             result.job.theme.full_name(),
             result.job.locale,
-            status_str
+            status_str,
+            minified
         );
+        for failure in &result.failures {
+            let cause = match failure.error.io_error() {
+                Some(e) => e.to_string(),
+                None => failure.error.to_string(),
+            };
+            println!(
+                "    failed {} -> {}: {}",
+                failure.src.display(),
+                failure.dst.display(),
+                cause
+            );
+        }
+        for url in &result.unresolved_urls {
+            println!(
+                "    unresolved url({}) in {}",
+                url.reference,
+                url.css.display()
+            );
+        }
     }
 
     // Determine exit code
@@ -216,3 +409,101 @@ fn run() -> Result<ExitCode> {
         Ok(ExitCode::SUCCESS)
     }
 }
+
+/// Check that `magento_root` is a Magento installation
+fn validate_magento_root(magento_root: &Path) -> Result<()> {
+    if !magento_root.exists() {
+        bail!("Magento root not found: {}", magento_root.display());
+    }
+
+    let env_php = magento_root.join("app").join("etc").join("env.php");
+    if !env_php.exists() {
+        bail!(
+            "Not a Magento installation: {} (app/etc/env.php not found)",
+            magento_root.display()
+        );
+    }
+    Ok(())
+}
+
+/// `clean`: remove deployed static content and preprocessed caches
+fn clean(args: &CleanArgs) -> Result<ExitCode> {
+    let magento_root = args.magento_root();
+    validate_magento_root(&magento_root)?;
+    let options = args.options()?;
+
+    // Never pull outputs from under a running deploy
+    let _lock = if args.dry_run {
+        None
+    } else {
+        Some(DeployLock::acquire(
+            &magento_root,
+            args.lock_wait(),
+            &AtomicBool::new(false),
+        )?)
+    };
+
+    let targets = clean_targets(&magento_root, &options);
+    let mut failed = false;
+    for target in &targets {
+        if args.dry_run {
+            println!("  would remove {}", target.display());
+            continue;
+        }
+        match remove_target(target) {
+            Ok(()) => println!("  removed {}", target.display()),
+            Err(e) => {
+                failed = true;
+                let cause = e.io_error().map(|e| e.to_string()).unwrap_or_default();
+                println!("  FAILED {}: {cause}", target.display());
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        println!("Nothing to clean");
+    } else if args.dry_run {
+        println!("Dry run: {} path(s) would be removed", targets.len());
+    }
+
+    if failed {
+        Ok(ExitCode::from(1))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Swap the previous atomic deploy of each job's output back in
+fn rollback_jobs(jobs: &[DeployJob], magento_root: &Path) -> ExitCode {
+    let mut failed = false;
+
+    for job in jobs {
+        let output = output_path_for_theme(magento_root, &job.theme, &job.locale);
+        let status = match rollback(&output) {
+            Ok(true) => {
+                // The manifest describes the tree that was just swapped out
+                let _ = std::fs::remove_file(manifest_path(magento_root, &job.theme, &job.locale));
+                "rolled back".to_string()
+            }
+            Ok(false) => "no previous deploy".to_string(),
+            Err(e) => {
+                failed = true;
+                format!("FAILED: {e}")
+            }
+        };
+
+        println!(
+            "  {}/{}/{}: {}",
+            job.theme.area.as_str(),
+            job.theme.full_name(),
+            job.locale,
+            status
+        );
+    }
+
+    if failed {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,533 @@
+//! Deploy manifests for incremental deploys.
+//!
+//! After a job succeeds, a manifest recording the source path, size, mtime
+//! and winning source of every deployed file is written under
+//! `var/static_deploy/manifests/`. The next run compares its plan against
+//! it and only copies files whose source changed (or whose output is gone).
+//! The manifest also records the options that shape the output; a run with
+//! different ones ignores it and copies everything again.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rayon::prelude::*;
+
+use crate::copier::CopyOptions;
+use crate::plan::DeployPlan;
+use crate::theme::{LocaleCode, Theme};
+
+/// First line of every manifest; bump when the format changes
+const MANIFEST_HEADER: &str = "# magento-static-deploy manifest v2";
+
+/// Prefix of the second line, which records [`output_settings`]
+const SETTINGS_PREFIX: &str = "# settings ";
+
+/// The options that decide what an unchanged source deploys to: link mode,
+/// output attributes, sidecars, minification and the generated
+/// requirejs-config.js. Files deployed under other settings can't be skipped.
+pub fn output_settings(options: &CopyOptions) -> String {
+    let mut settings = format!(
+        "link={} attrs={:?}",
+        options.link_mode.as_str(),
+        options.attrs
+    );
+    if let Some(precompress) = &options.precompress {
+        let codecs: Vec<&str> = precompress.codecs.iter().map(|c| c.as_str()).collect();
+        settings.push_str(&format!(
+            " precompress={}:{}:{}",
+            codecs.join(","),
+            precompress.extensions.join(","),
+            precompress.min_size
+        ));
+    }
+    if options.requirejs_config {
+        settings.push_str(" requirejs-config");
+    }
+    if let Some(minify) = &options.minify {
+        for (name, enabled, excludes) in [
+            ("js", minify.js, &minify.js_exclude),
+            ("css", minify.css, &minify.css_exclude),
+            ("html", minify.html, &minify.html_exclude),
+        ] {
+            if enabled {
+                let patterns: Vec<&str> = excludes.iter().map(|re| re.as_str()).collect();
+                settings.push_str(&format!(" minify-{name}={patterns:?}"));
+            }
+        }
+        if minify.css {
+            settings.push_str(&format!(" css-min-size={}", minify.css_min_size));
+        }
+    }
+    settings
+}
+
+/// Where the manifest for a theme/locale output lives
+pub fn manifest_path(magento_root: &Path, theme: &Theme, locale: &LocaleCode) -> PathBuf {
+    magento_root
+        .join("var")
+        .join("static_deploy")
+        .join("manifests")
+        .join(theme.area.as_str())
+        .join(&theme.vendor)
+        .join(&theme.name)
+        .join(format!("{}.tsv", locale.as_str()))
+}
+
+/// What a deployed file was produced from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Source file that won this destination
+    pub source: PathBuf,
+    /// Source size in bytes
+    pub size: u64,
+    /// Source modification time, nanoseconds since the Unix epoch
+    pub mtime_ns: u128,
+    /// Winning source (`FileSource::origin`)
+    pub origin: String,
+}
+
+impl ManifestEntry {
+    /// Stat `source` to describe it; `None` if it can't be read
+    pub fn from_source(source: PathBuf, origin: String) -> Option<Self> {
+        let metadata = fs::metadata(&source).ok()?;
+        let mtime_ns = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+
+        Some(Self {
+            source,
+            size: metadata.len(),
+            mtime_ns,
+            origin,
+        })
+    }
+}
+
+/// Deployed files of one output, keyed by destination relative to it
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    entries: HashMap<PathBuf, ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a manifest; `None` if it is missing, not in a known format or
+    /// written with other [`output_settings`] than `settings`
+    pub fn load(path: &Path, settings: &str) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let mut lines = content.lines();
+        if lines.next()? != MANIFEST_HEADER
+            || lines.next()?.strip_prefix(SETTINGS_PREFIX)? != settings
+        {
+            return None;
+        }
+
+        let mut entries = HashMap::new();
+        for line in lines {
+            let mut fields = line.split('\t');
+            let (Some(dest), Some(source), Some(size), Some(mtime), Some(origin), None) = (
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+                fields.next(),
+            ) else {
+                return None;
+            };
+
+            entries.insert(
+                PathBuf::from(dest),
+                ManifestEntry {
+                    source: PathBuf::from(source),
+                    size: size.parse().ok()?,
+                    mtime_ns: mtime.parse().ok()?,
+                    origin: origin.to_string(),
+                },
+            );
+        }
+
+        Some(Self { entries })
+    }
+
+    /// Write the manifest atomically (temp file + rename).
+    /// Entries whose paths can't be stored as a single line are left out,
+    /// which just means they are copied again next time.
+    pub fn save(&self, path: &Path, settings: &str) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut dests: Vec<&PathBuf> = self.entries.keys().collect();
+        dests.sort_unstable();
+
+        let mut out = String::with_capacity(64 + self.entries.len() * 128);
+        out.push_str(MANIFEST_HEADER);
+        out.push('\n');
+        out.push_str(SETTINGS_PREFIX);
+        out.push_str(settings);
+        out.push('\n');
+        for dest in dests {
+            let entry = &self.entries[dest];
+            let (Some(dest_str), Some(source_str)) = (dest.to_str(), entry.source.to_str()) else {
+                continue;
+            };
+            if [dest_str, source_str, &entry.origin]
+                .iter()
+                .any(|s| s.contains(['\t', '\n', '\r']))
+            {
+                continue;
+            }
+            out.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                dest_str, source_str, entry.size, entry.mtime_ns, entry.origin
+            ));
+        }
+
+        let tmp = path.with_extension("tsv.tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(out.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn get(&self, dest: &Path) -> Option<&ManifestEntry> {
+        self.entries.get(dest)
+    }
+
+    pub fn insert(&mut self, dest: PathBuf, entry: ManifestEntry) {
+        self.entries.insert(dest, entry);
+    }
+
+    pub fn remove(&mut self, dest: &Path) -> Option<ManifestEntry> {
+        self.entries.remove(dest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Destinations recorded in the manifest
+    pub fn dests(&self) -> impl Iterator<Item = &Path> {
+        self.entries.keys().map(PathBuf::as_path)
+    }
+}
+
+/// How a deploy differs from the previous one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ManifestDelta {
+    /// Files not in the previous manifest
+    pub added: u64,
+    /// Files whose source changed (or whose output went missing)
+    pub changed: u64,
+    /// Files skipped because nothing changed
+    pub unchanged: u64,
+    /// Files in the previous manifest that are no longer planned
+    pub removed: u64,
+}
+
+impl ManifestDelta {
+    pub fn add(&mut self, other: &ManifestDelta) {
+        self.added += other.added;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+        self.removed += other.removed;
+    }
+}
+
+/// A plan compared against the previous manifest
+#[derive(Debug, Default)]
+pub struct IncrementalPlan {
+    /// Per plan entry: whether it has to be copied
+    pub copy: Vec<bool>,
+    pub delta: ManifestDelta,
+    /// Manifest to save once the deploy succeeded
+    pub next: Manifest,
+    /// Destinations that were in the previous manifest but aren't planned
+    pub removed: Vec<PathBuf>,
+}
+
+/// Decide which plan entries need copying.
+/// Without a previous manifest (or with `--full`) every entry is copied.
+pub fn compare(plan: &DeployPlan, previous: Option<&Manifest>) -> IncrementalPlan {
+    let current: Vec<Option<ManifestEntry>> = plan
+        .entries()
+        .par_iter()
+        .map(|entry| {
+            let origin = plan.sources()[entry.source].origin();
+            ManifestEntry::from_source(plan.source_path(entry), origin)
+        })
+        .collect();
+
+    let mut result = IncrementalPlan {
+        copy: Vec::with_capacity(current.len()),
+        ..IncrementalPlan::default()
+    };
+
+    for (entry, stat) in plan.entries().iter().zip(current) {
+        let before = previous.and_then(|m| m.get(&entry.dest));
+        let unchanged = match (&stat, before) {
+            (Some(now), Some(before)) => {
+                now == before && fs::symlink_metadata(plan.dest_path(entry)).is_ok()
+            }
+            _ => false,
+        };
+
+        if unchanged {
+            result.delta.unchanged += 1;
+        } else if before.is_some() {
+            result.delta.changed += 1;
+        } else {
+            result.delta.added += 1;
+        }
+        result.copy.push(!unchanged);
+
+        if let Some(stat) = stat {
+            result.next.insert(entry.dest.clone(), stat);
+        }
+    }
+
+    if let Some(previous) = previous {
+        let planned: HashSet<&Path> = plan.entries().iter().map(|e| e.dest.as_path()).collect();
+        result.removed = previous
+            .dests()
+            .filter(|dest| !planned.contains(dest))
+            .map(Path::to_path_buf)
+            .collect();
+        result.removed.sort_unstable();
+        result.delta.removed = result.removed.len() as u64;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copier::LinkMode;
+    use crate::minify::Minify;
+    use crate::scanner::FileSource;
+    use crate::theme::Area;
+    use tempfile::TempDir;
+
+    fn library_plan(temp: &TempDir) -> DeployPlan {
+        DeployPlan::build(
+            temp.path().join("out"),
+            vec![FileSource::Library {
+                path: temp.path().join("lib"),
+            }],
+            false,
+        )
+    }
+
+    fn deploy(plan: &DeployPlan) {
+        for entry in plan.entries() {
+            let dest = plan.dest_path(entry);
+            fs::create_dir_all(dest.parent().unwrap()).unwrap();
+            fs::copy(plan.source_path(entry), dest).unwrap();
+        }
+    }
+
+    // ==================== manifest_path tests ====================
+
+    #[test]
+    fn test_manifest_path_under_var() {
+        let theme = Theme {
+            vendor: "Hyva".to_string(),
+            name: "default".to_string(),
+            area: Area::Frontend,
+            path: PathBuf::from("/theme"),
+            parent: None,
+            theme_type: crate::theme::ThemeType::Hyva,
+        };
+
+        let path = manifest_path(Path::new("/magento"), &theme, &LocaleCode::new("en_US"));
+
+        assert_eq!(
+            path,
+            PathBuf::from("/magento/var/static_deploy/manifests/frontend/Hyva/default/en_US.tsv")
+        );
+    }
+
+    // ==================== Manifest save/load tests ====================
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("var/manifest.tsv");
+
+        let mut manifest = Manifest::new();
+        let entry = ManifestEntry {
+            source: PathBuf::from("/lib/web/app.js"),
+            size: 42,
+            mtime_ns: 1_700_000_000_123_456_789,
+            origin: "library".to_string(),
+        };
+        manifest.insert(PathBuf::from("app.js"), entry.clone());
+        manifest.insert(
+            PathBuf::from("bad\tname.js"),
+            ManifestEntry {
+                source: PathBuf::from("/lib/web/bad\tname.js"),
+                ..entry.clone()
+            },
+        );
+        manifest.save(&path, "link=copy").unwrap();
+
+        let loaded = Manifest::load(&path, "link=copy").unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(Path::new("app.js")), Some(&entry));
+    }
+
+    #[test]
+    fn test_manifest_load_rejects_unknown_format() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("manifest.tsv");
+
+        assert!(Manifest::load(&path, "link=copy").is_none());
+
+        fs::write(&path, "something else\n").unwrap();
+        assert!(Manifest::load(&path, "link=copy").is_none());
+
+        fs::write(
+            &path,
+            format!("{MANIFEST_HEADER}\n{SETTINGS_PREFIX}link=copy\napp.js\t/src\tnot-a-number\t1\tlib\n"),
+        )
+        .unwrap();
+        assert!(Manifest::load(&path, "link=copy").is_none());
+
+        // The previous format
+        fs::write(
+            &path,
+            "# magento-static-deploy manifest v1\napp.js\t/src\t1\t1\tlib\n",
+        )
+        .unwrap();
+        assert!(Manifest::load(&path, "link=copy").is_none());
+    }
+
+    #[test]
+    fn test_manifest_load_rejects_other_settings() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("manifest.tsv");
+        Manifest::new().save(&path, "link=copy").unwrap();
+
+        assert!(Manifest::load(&path, "link=copy").is_some());
+        assert!(Manifest::load(&path, "link=hardlink").is_none());
+    }
+
+    // ==================== output_settings tests ====================
+
+    #[test]
+    fn test_output_settings_follow_output_options() {
+        let base = CopyOptions::default();
+        let settings = output_settings(&base);
+
+        let hardlink = CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..CopyOptions::default()
+        };
+        assert_ne!(output_settings(&hardlink), settings);
+
+        let minify = |css_min_size| CopyOptions {
+            minify: Some(Minify {
+                css: true,
+                css_min_size,
+                ..Minify::default()
+            }),
+            ..CopyOptions::default()
+        };
+        assert_ne!(output_settings(&minify(0)), settings);
+        assert_ne!(output_settings(&minify(0)), output_settings(&minify(1024)));
+
+        // Options that don't change the output's content don't count
+        let other = CopyOptions {
+            keep_going: true,
+            skip_identical: true,
+            ..CopyOptions::default()
+        };
+        assert_eq!(output_settings(&other), settings);
+        assert!(!settings.contains('\n'));
+    }
+
+    // ==================== compare tests ====================
+
+    #[test]
+    fn test_compare_without_manifest_copies_everything() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("lib")).unwrap();
+        fs::write(temp.path().join("lib/a.js"), "a").unwrap();
+        fs::write(temp.path().join("lib/b.js"), "b").unwrap();
+        let plan = library_plan(&temp);
+
+        let result = compare(&plan, None);
+
+        assert_eq!(result.copy, vec![true, true]);
+        assert_eq!(result.delta.added, 2);
+        assert_eq!(result.next.len(), 2);
+        assert_eq!(
+            result.next.get(Path::new("a.js")).unwrap().origin,
+            "library"
+        );
+    }
+
+    #[test]
+    fn test_compare_skips_unchanged() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("lib")).unwrap();
+        fs::write(temp.path().join("lib/a.js"), "a").unwrap();
+        fs::write(temp.path().join("lib/b.js"), "b").unwrap();
+        let plan = library_plan(&temp);
+        deploy(&plan);
+        let first = compare(&plan, None);
+
+        // b.js changes size, c.js is new
+        fs::write(temp.path().join("lib/b.js"), "bigger").unwrap();
+        fs::write(temp.path().join("lib/c.js"), "c").unwrap();
+        let plan = library_plan(&temp);
+        let second = compare(&plan, Some(&first.next));
+
+        assert_eq!(second.copy, vec![false, true, true]);
+        assert_eq!(
+            second.delta,
+            ManifestDelta {
+                added: 1,
+                changed: 1,
+                unchanged: 1,
+                removed: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compare_recopies_missing_output_and_reports_removed() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("lib")).unwrap();
+        fs::write(temp.path().join("lib/a.js"), "a").unwrap();
+        fs::write(temp.path().join("lib/old.js"), "old").unwrap();
+        let plan = library_plan(&temp);
+        deploy(&plan);
+        let first = compare(&plan, None);
+
+        fs::remove_file(temp.path().join("out/a.js")).unwrap();
+        fs::remove_file(temp.path().join("lib/old.js")).unwrap();
+        let plan = library_plan(&temp);
+        let second = compare(&plan, Some(&first.next));
+
+        assert_eq!(second.copy, vec![true]);
+        assert_eq!(second.delta.changed, 1);
+        assert_eq!(second.delta.removed, 1);
+        assert_eq!(second.removed, vec![PathBuf::from("old.js")]);
+    }
+}
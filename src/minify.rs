@@ -0,0 +1,1607 @@
+//! Minification of deployed assets (`--minify-js`, `--minify-css`,
+//! `--minify-html`).
+//!
+//! With `dev/js/minify_files` on, Magento's RequireJS resolver requests
+//! `foo.min.js` wherever a module asks for `foo.js`, and with
+//! `dev/css/minify_files` pages link `styles.min.css` instead of
+//! `styles.css`. Every planned `.js` (`.css`) file therefore gets a derived
+//! `.min.js` (`.min.css`) entry, written minified from the same source next
+//! to the unminified copy. Files that already are `.min.*`, or whose `.min.*`
+//! ships alongside them (as in `lib/web`), are left alone, and so are paths
+//! matching Magento's `dev/{js,css}/minify_exclude` patterns. Sources that
+//! look minified already are written as they are.
+//!
+//! Knockout `.html` templates (under a `template/` directory) have no
+//! `.min` variant: like Magento's `dev/template/minify_html`, they are
+//! minified in place, keeping the `<!-- ko -->` comment bindings.
+//!
+//! The minifiers only strip comments and whitespace and keep `/*! */`
+//! license comments. The JS one keeps a line break wherever removing it
+//! could change automatic semicolon insertion. A file the minifier can't
+//! tokenize, or with a `/` that may be a division or a regex literal, is
+//! written unminified.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::copier::{list_files, write_file, CopyOptions};
+use crate::error::DeployError;
+
+/// How a derived plan entry is produced from its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Minified JavaScript
+    MinifyJs,
+    /// Minified CSS
+    MinifyCss,
+    /// Minified Knockout template, written in place of the copy
+    MinifyHtml,
+    /// CSS whose module notation `url()`s are rewritten (see `cssurl`),
+    /// written in place of the copy
+    ResolveCssUrls,
+}
+
+impl Transform {
+    /// Extension of the files it applies to, and Magento's config section
+    fn extension(&self) -> &'static str {
+        match self {
+            Transform::MinifyJs => "js",
+            Transform::MinifyCss | Transform::ResolveCssUrls => "css",
+            Transform::MinifyHtml => "html",
+        }
+    }
+}
+
+/// Average line length beyond which a file is taken as minified already
+const MINIFIED_LINE_LENGTH: usize = 500;
+
+/// What gets minified
+#[derive(Debug, Clone, Default)]
+pub struct Minify {
+    /// Write `.min.js` files next to `.js` files
+    pub js: bool,
+    /// Asset paths (`area/Vendor/theme/locale/file`) not to minify
+    pub js_exclude: Vec<Regex>,
+    /// Write `.min.css` files next to `.css` files
+    pub css: bool,
+    /// Asset paths not to minify
+    pub css_exclude: Vec<Regex>,
+    /// Smaller CSS files are written as is
+    pub css_min_size: u64,
+    /// Minify Knockout `.html` templates in place
+    pub html: bool,
+    /// Asset paths not to minify
+    pub html_exclude: Vec<Regex>,
+}
+
+impl Minify {
+    /// The file derived from `dest`, deployed under `asset_dir`
+    /// (`area/Vendor/theme/locale`), if it gets one
+    pub fn derive(&self, dest: &Path, asset_dir: &str) -> Option<(PathBuf, Transform)> {
+        let name = dest.file_name()?.to_str()?;
+        let (transform, excludes) = if self.js && name.ends_with(".js") {
+            (Transform::MinifyJs, &self.js_exclude)
+        } else if self.css && name.ends_with(".css") {
+            (Transform::MinifyCss, &self.css_exclude)
+        } else {
+            return None;
+        };
+
+        let ext = transform.extension();
+        let stem = &name[..name.len() - ext.len() - 1];
+        if stem.is_empty() || stem.ends_with(".min") {
+            return None;
+        }
+        let asset = format!("{asset_dir}/{}", dest.to_string_lossy());
+        if excludes.iter().any(|exclude| exclude.is_match(&asset)) {
+            return None;
+        }
+        Some((dest.with_file_name(format!("{stem}.min.{ext}")), transform))
+    }
+
+    /// How `dest`, deployed under `asset_dir`, is written instead of a
+    /// plain copy, if it is
+    pub fn in_place(&self, dest: &Path, asset_dir: &str) -> Option<Transform> {
+        let is_template = dest.extension().is_some_and(|ext| ext == "html")
+            && dest
+                .parent()
+                .is_some_and(|dir| dir.components().any(|c| c.as_os_str() == "template"));
+        if !self.html || !is_template {
+            return None;
+        }
+        let asset = format!("{asset_dir}/{}", dest.to_string_lossy());
+        (!self
+            .html_exclude
+            .iter()
+            .any(|exclude| exclude.is_match(&asset)))
+        .then_some(Transform::MinifyHtml)
+    }
+
+    /// Whether `data` is worth minifying for `transform`
+    fn wants(&self, transform: Transform, data: &[u8]) -> bool {
+        let big_enough = match transform {
+            Transform::MinifyJs => true,
+            Transform::MinifyCss => data.len() as u64 >= self.css_min_size,
+            Transform::MinifyHtml => true,
+            Transform::ResolveCssUrls => false,
+        };
+        big_enough && !looks_minified(data)
+    }
+}
+
+/// Whether `data` has the long lines of minified output (e.g. Tailwind's)
+fn looks_minified(data: &[u8]) -> bool {
+    let lines = data.iter().filter(|&&b| b == b'\n').count() + 1;
+    data.len() / lines > MINIFIED_LINE_LENGTH
+}
+
+/// Counters of one kind of minified file
+#[derive(Debug, Default)]
+pub struct MinifyCounts {
+    /// Files written minified
+    pub files: AtomicU64,
+    /// Files written as is: minified already, too small, not tokenizable
+    /// or nothing to strip
+    pub unchanged: AtomicU64,
+    /// Bytes the minified files save over their sources
+    pub saved: AtomicU64,
+}
+
+/// Minification counters, shared by all workers
+#[derive(Debug, Default)]
+pub struct MinifyStats {
+    pub js: MinifyCounts,
+    pub css: MinifyCounts,
+    pub html: MinifyCounts,
+}
+
+impl MinifyStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counters of `transform`, if it minifies
+    pub(crate) fn counts(&self, transform: Transform) -> Option<&MinifyCounts> {
+        match transform {
+            Transform::MinifyJs => Some(&self.js),
+            Transform::MinifyCss => Some(&self.css),
+            Transform::MinifyHtml => Some(&self.html),
+            Transform::ResolveCssUrls => None,
+        }
+    }
+
+    /// Count a file written for `transform` that is `saved` bytes smaller
+    /// than its source
+    pub fn record(&self, transform: Transform, saved: u64) {
+        if let Some(counts) = self.counts(transform) {
+            if saved > 0 {
+                counts.files.fetch_add(1, Ordering::Relaxed);
+                counts.saved.fetch_add(saved, Ordering::Relaxed);
+            } else {
+                counts.unchanged.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Bytes saved so far, per kind
+    pub fn saved(&self) -> MinifySaved {
+        MinifySaved {
+            js: self.js.saved.load(Ordering::Relaxed),
+            css: self.css.saved.load(Ordering::Relaxed),
+            html: self.html.saved.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Bytes saved by minification, per kind of file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MinifySaved {
+    pub js: u64,
+    pub css: u64,
+    pub html: u64,
+}
+
+impl MinifySaved {
+    pub fn add(&mut self, transform: Transform, saved: u64) {
+        match transform {
+            Transform::MinifyJs => self.js += saved,
+            Transform::MinifyCss => self.css += saved,
+            Transform::MinifyHtml => self.html += saved,
+            Transform::ResolveCssUrls => {}
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.js + self.css + self.html
+    }
+}
+
+/// A file written by [`install_transformed`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transformed {
+    /// Bytes written
+    pub bytes: u64,
+    /// Bytes saved over the source
+    pub saved: u64,
+}
+
+/// Write `dst` transformed from `src`
+pub fn install_transformed(
+    src: &Path,
+    dst: &Path,
+    transform: Transform,
+    options: &CopyOptions,
+    stats: &MinifyStats,
+) -> Result<Transformed, DeployError> {
+    let (contents, saved) = transform_file(src, dst, transform, options)?;
+    let bytes = write_file(src, dst, &contents, options)?;
+    stats.record(transform, saved);
+    Ok(Transformed { bytes, saved })
+}
+
+/// Contents of `src` (to be installed at `dst`) minified for `transform`,
+/// with the bytes saved. `ResolveCssUrls` returns them as they are: the
+/// caller rewrites them. Nothing is counted until the caller wrote them
+/// (`MinifyStats::record`).
+pub fn transform_file(
+    src: &Path,
+    dst: &Path,
+    transform: Transform,
+    options: &CopyOptions,
+) -> Result<(Vec<u8>, u64), DeployError> {
+    let data = fs::read(src).map_err(|source| DeployError::CopyFailed {
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        source,
+    })?;
+    let wanted = options
+        .minify
+        .as_ref()
+        .is_some_and(|minify| minify.wants(transform, &data));
+    let minified = match transform {
+        _ if !wanted => None,
+        Transform::MinifyJs => minify_js(&data),
+        Transform::MinifyCss => minify_css(&data),
+        Transform::MinifyHtml => minify_html(&data),
+        Transform::ResolveCssUrls => None,
+    };
+
+    Ok(match minified {
+        Some(minified) if minified.len() < data.len() => {
+            let saved = (data.len() - minified.len()) as u64;
+            (minified, saved)
+        }
+        _ => (data, 0),
+    })
+}
+
+/// Write the minified files derived from an output deployed by someone
+/// else (bin/magento), where they don't exist yet, and minify its templates
+/// in place. Returns the bytes saved.
+pub fn minify_output(
+    output: &Path,
+    asset_dir: &str,
+    options: &CopyOptions,
+    stats: &MinifyStats,
+    shutdown: &AtomicBool,
+) -> Result<MinifySaved, DeployError> {
+    let Some(minify) = &options.minify else {
+        return Ok(MinifySaved::default());
+    };
+    let files = list_files(output, true);
+    let existing: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+
+    let derived: Vec<(&PathBuf, PathBuf, Transform)> = files
+        .iter()
+        .filter_map(|file| {
+            if let Some(transform) = minify.in_place(file, asset_dir) {
+                return Some((file, file.clone(), transform));
+            }
+            let (dest, transform) = minify.derive(file, asset_dir)?;
+            (!existing.contains(dest.as_path())).then_some((file, dest, transform))
+        })
+        .collect();
+
+    derived
+        .par_iter()
+        .map(|(file, dest, transform)| {
+            if shutdown.load(Ordering::Relaxed) {
+                return Err(DeployError::Cancelled);
+            }
+            let src = output.join(file);
+            install_transformed(&src, &output.join(dest), *transform, options, stats)
+                .map(|written| (*transform, written.saved))
+        })
+        .collect::<Result<Vec<_>, DeployError>>()
+        .map(|written| {
+            let mut saved = MinifySaved::default();
+            for (transform, bytes) in written {
+                saved.add(transform, bytes);
+            }
+            saved
+        })
+}
+
+// ---------------------------------------------------------------------------
+// Magento configuration
+// ---------------------------------------------------------------------------
+
+/// Modules' `etc/config.xml` files, where the `dev/*/minify_exclude`
+/// defaults are declared
+fn module_configs(magento_root: &Path) -> Vec<PathBuf> {
+    let mut configs = Vec::new();
+    for (base, inner) in [
+        ("vendor", &["etc", "src/etc"][..]),
+        ("app/code", &["etc"][..]),
+    ] {
+        let Ok(vendors) = fs::read_dir(magento_root.join(base)) else {
+            continue;
+        };
+        for vendor in vendors.filter_map(|entry| entry.ok()) {
+            let Ok(modules) = fs::read_dir(vendor.path()) else {
+                continue;
+            };
+            for module in modules.filter_map(|entry| entry.ok()) {
+                configs.extend(
+                    inner
+                        .iter()
+                        .map(|dir| module.path().join(dir).join("config.xml"))
+                        .filter(|config| config.is_file()),
+                );
+            }
+        }
+    }
+    configs.sort();
+    configs
+}
+
+/// `dev/{content_type}/minify_exclude` patterns in effect: those declared
+/// by the installed modules, overridden by app/etc/config.php, then
+/// app/etc/env.php. An override replaces the entries of the same name; an
+/// empty one removes them.
+pub fn magento_excludes(magento_root: &Path, content_type: &str) -> Vec<String> {
+    let mut by_name: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for xml in module_configs(magento_root)
+        .iter()
+        .filter_map(|config| fs::read_to_string(config).ok())
+    {
+        for (name, pattern) in parse_minify_excludes(&xml, content_type) {
+            by_name.entry(name).or_default().push(pattern);
+        }
+    }
+    for file in ["app/etc/config.php", "app/etc/env.php"] {
+        let Ok(php) = fs::read_to_string(magento_root.join(file)) else {
+            continue;
+        };
+        for (name, pattern) in parse_php_minify_excludes(&php, content_type) {
+            by_name.insert(name, vec![pattern]);
+        }
+    }
+
+    let mut excludes: Vec<String> = by_name
+        .into_values()
+        .flatten()
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+    excludes.sort();
+    excludes.dedup();
+    excludes
+}
+
+/// `config/default/dev/{content_type}/minify_exclude` entries of a
+/// config.xml as (name, pattern): one per child element, or one per line of
+/// the element's own text (older Magento versions, named by the pattern)
+pub fn parse_minify_excludes(xml: &str, content_type: &str) -> Vec<(String, String)> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let target: [&[u8]; 5] = [
+        b"config",
+        b"default",
+        b"dev",
+        content_type.as_bytes(),
+        b"minify_exclude",
+    ];
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut excludes = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => path.push(e.name().as_ref().to_vec()),
+            Ok(Event::End(_)) => {
+                path.pop();
+            }
+            Ok(Event::Text(e))
+                if path.len() >= target.len()
+                    && path.len() <= target.len() + 1
+                    && path.iter().zip(target).all(|(name, want)| name == want) =>
+            {
+                let Ok(text) = e.unescape() else {
+                    continue;
+                };
+                if path.len() > target.len() {
+                    let name = path.last().map(|name| String::from_utf8_lossy(name));
+                    excludes.push((name.unwrap_or_default().into_owned(), text.into_owned()));
+                } else {
+                    excludes.extend(
+                        text.lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty())
+                            .map(|line| (line.to_string(), line.to_string())),
+                    );
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return excludes,
+            _ => {}
+        }
+    }
+}
+
+/// `['system']['default']['dev'][content_type]['minify_exclude']` entries
+/// of an app/etc/config.php or env.php as (name, pattern). A string value
+/// holds one pattern per line, named by the pattern.
+pub fn parse_php_minify_excludes(php: &str, content_type: &str) -> Vec<(String, String)> {
+    let target = ["system", "default", "dev", content_type, "minify_exclude"];
+    let src = php.as_bytes();
+    // Key of each open array, `None` for list items and the returned one
+    let mut path: Vec<Option<String>> = Vec::new();
+    let mut key: Option<String> = None;
+    let mut value: Option<String> = None;
+    let mut excludes = Vec::new();
+    let mut i = 0;
+
+    let at = |path: &[Option<String>], len: usize| {
+        path.len() == len + 1
+            && path[1..]
+                .iter()
+                .zip(target)
+                .all(|(key, want)| key.as_deref() == Some(want))
+    };
+    let mut finish = |path: &[Option<String>], key: Option<String>, value: Option<String>| {
+        let Some(value) = value else {
+            return;
+        };
+        if at(path, target.len()) {
+            let name = key.unwrap_or_else(|| value.clone());
+            excludes.push((name, value));
+        } else if at(path, target.len() - 1) && key.as_deref() == Some(target[4]) {
+            excludes.extend(
+                value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|line| (line.to_string(), line.to_string())),
+            );
+        }
+    };
+
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\'' | b'"' => {
+                let (string, end) = php_string(src, i);
+                value = Some(string);
+                i = end;
+                continue;
+            }
+            b'#' => {
+                while src.get(i).is_some_and(|&b| !is_newline(b)) {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if src.get(i + 1) == Some(&b'/') => {
+                while src.get(i).is_some_and(|&b| !is_newline(b)) {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if src.get(i + 1) == Some(&b'*') => {
+                i = src[i + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(src.len(), |len| i + 2 + len + 2);
+                continue;
+            }
+            b'=' if src.get(i + 1) == Some(&b'>') => {
+                key = value.take();
+                i += 1;
+            }
+            b'[' | b'(' => {
+                path.push(key.take());
+                value = None;
+            }
+            b']' | b')' => {
+                finish(&path, key.take(), value.take());
+                path.pop();
+            }
+            b',' => finish(&path, key.take(), value.take()),
+            _ => {}
+        }
+        i += 1;
+    }
+    excludes
+}
+
+/// Contents and end of the PHP string literal starting at `start`. Only
+/// the escapes of the quote and of the backslash are decoded.
+fn php_string(src: &[u8], start: usize) -> (String, usize) {
+    let quote = src[start];
+    let mut out = Vec::new();
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\\'
+                if src
+                    .get(i + 1)
+                    .is_some_and(|&next| next == quote || next == b'\\') =>
+            {
+                out.push(src[i + 1]);
+                i += 2;
+            }
+            b if b == quote => {
+                i += 1;
+                break;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    (String::from_utf8_lossy(&out).into_owned(), i)
+}
+
+/// Compile exclude patterns the way Magento applies them (`preg_match`
+/// with `/` delimiters)
+pub fn compile_excludes(patterns: &[String]) -> Result<Vec<Regex>, regex::Error> {
+    patterns.iter().map(|pattern| Regex::new(pattern)).collect()
+}
+
+// ---------------------------------------------------------------------------
+// JavaScript
+// ---------------------------------------------------------------------------
+
+/// Whitespace seen between two tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Gap {
+    None,
+    Space,
+    Newline,
+}
+
+/// The previous token, as far as telling a regex literal from a division
+/// goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prev {
+    Start,
+    Punct(u8),
+    /// `)` closing the head of `if`, `for`, `while` or `with`: a statement,
+    /// which may start with a regex literal, follows
+    Condition,
+    /// Identifier or keyword; `true` if a regex literal may follow it
+    Word(bool),
+    /// `++` or `--`: a `/` after it divides (`i++ / 2`), since a prefix one
+    /// can't apply to a regex literal
+    Update,
+    /// Number, string, template or regex literal
+    Operand,
+}
+
+impl Prev {
+    fn allows_regex(self) -> bool {
+        match self {
+            Prev::Start | Prev::Condition => true,
+            Prev::Punct(b')') | Prev::Punct(b']') => false,
+            Prev::Punct(_) => true,
+            Prev::Word(allows) => allows,
+            Prev::Update | Prev::Operand => false,
+        }
+    }
+}
+
+/// Keywords an expression (and so a regex literal) may follow
+const REGEX_KEYWORDS: [&[u8]; 14] = [
+    b"return",
+    b"typeof",
+    b"instanceof",
+    b"in",
+    b"of",
+    b"new",
+    b"delete",
+    b"void",
+    b"throw",
+    b"case",
+    b"do",
+    b"else",
+    b"yield",
+    b"await",
+];
+
+/// Keywords whose parenthesized head is followed by a statement
+const CONDITION_KEYWORDS: [&[u8]; 4] = [b"if", b"for", b"while", b"with"];
+
+fn is_newline(b: u8) -> bool {
+    b == b'\n' || b == b'\r'
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | 0x0b | 0x0c)
+}
+
+/// Identifier and number characters. Non-ASCII bytes count too, so
+/// Unicode identifiers and exotic whitespace are kept as they are.
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'$' | b'\\' | b'#') || b >= 0x80
+}
+
+/// Whether two tokens must stay apart to not merge into one
+fn needs_space(last: u8, first: u8) -> bool {
+    (is_word(last) && (is_word(first) || first == b'.'))
+        || (last == first && matches!(first, b'+' | b'-' | b'/'))
+        || (last == b'<' && first == b'!')
+}
+
+/// Whether a line break between `last` and the token `next` can go:
+/// the line is unfinished after `last`, or `next` can only continue it.
+/// Neither side can be part of a restricted production (`return`,
+/// postfix `++`, ...).
+fn can_join(last: u8, next: &[u8]) -> bool {
+    b"{;,([:?=&|<>!~*%^".contains(&last) || (next.len() == 1 && b")]},;.".contains(&next[0]))
+}
+
+/// End of the string literal starting at `start`
+fn scan_string(src: &[u8], start: usize) -> Option<usize> {
+    let quote = src[start];
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\\' => {
+                // An escaped CRLF is one line continuation
+                i += if src.get(i + 1) == Some(&b'\r') && src.get(i + 2) == Some(&b'\n') {
+                    3
+                } else {
+                    2
+                };
+            }
+            b if b == quote => return Some(i + 1),
+            b if is_newline(b) => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// End of the template literal starting at `start`, substitutions included
+fn scan_template(src: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\\' => i += 2,
+            b'`' => return Some(i + 1),
+            b'$' if src.get(i + 1) == Some(&b'{') => i = scan_substitution(src, i + 2)?,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// End of a `${...}` substitution whose expression starts at `start`
+fn scan_substitution(src: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = start;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' if depth == 0 => return Some(i + 1),
+            b'}' => {
+                depth -= 1;
+                i += 1;
+            }
+            b'\'' | b'"' => i = scan_string(src, i)?,
+            b'`' => i = scan_template(src, i)?,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// End of the regex literal starting at `start`, flags included
+fn scan_regex(src: &[u8], start: usize) -> Option<usize> {
+    let mut in_class = false;
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\\' => i += 2,
+            b if is_newline(b) => return None,
+            b'[' => {
+                in_class = true;
+                i += 1;
+            }
+            b']' => {
+                in_class = false;
+                i += 1;
+            }
+            b'/' if !in_class => {
+                i += 1;
+                while src.get(i).is_some_and(|&b| is_word(b)) {
+                    i += 1;
+                }
+                return Some(i);
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// End of the identifier, keyword or number starting at `start`
+fn scan_word(src: &[u8], start: usize) -> usize {
+    let number = src[start].is_ascii_digit() || src[start] == b'.';
+    let hex = number && src.get(start + 1).is_some_and(|b| matches!(b, b'x' | b'X'));
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        let exponent_sign =
+            number && !hex && matches!(b, b'+' | b'-') && matches!(src[i - 1], b'e' | b'E');
+        if is_word(b) || (number && b == b'.') || exponent_sign {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+/// Strip comments and whitespace from JavaScript. `None` if the source
+/// doesn't tokenize (unterminated literal or comment, unbalanced
+/// parentheses) or has a `/` that may be a division or a regex literal
+/// (after `}`: a block or an object literal).
+pub fn minify_js(src: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut prev = Prev::Start;
+    // Per open `(`: whether it starts the head of an `if`, `for`, ...
+    let mut parens: Vec<bool> = Vec::new();
+    let mut condition_keyword = false;
+    let mut gap = Gap::None;
+    let mut i = 0;
+
+    while let Some(&b) = src.get(i) {
+        let next = src.get(i + 1).copied();
+        if is_space(b) {
+            gap = gap.max(Gap::Space);
+            i += 1;
+            continue;
+        }
+        if is_newline(b) {
+            gap = Gap::Newline;
+            i += 1;
+            continue;
+        }
+        if b == b'/' && next == Some(b'/') {
+            while src.get(i).is_some_and(|&b| !is_newline(b)) {
+                i += 1;
+            }
+            continue;
+        }
+        if b == b'/' && next == Some(b'*') {
+            let len = src[i + 2..].windows(2).position(|w| w == b"*/")?;
+            let end = i + 2 + len + 2;
+            let comment = &src[i..end];
+            if comment.starts_with(b"/*!") {
+                emit_gap(&mut out, gap, comment);
+                out.extend_from_slice(comment);
+                gap = Gap::None;
+            } else if comment.iter().any(|&b| is_newline(b)) {
+                gap = Gap::Newline;
+            } else {
+                gap = gap.max(Gap::Space);
+            }
+            i = end;
+            continue;
+        }
+
+        let end = match b {
+            b'\'' | b'"' => scan_string(src, i)?,
+            b'`' => scan_template(src, i)?,
+            b'/' if prev == Prev::Punct(b'}') => return None,
+            b'/' if prev.allows_regex() => scan_regex(src, i)?,
+            b'.' if next.is_some_and(|b| b.is_ascii_digit()) => scan_word(src, i),
+            b if is_word(b) => scan_word(src, i),
+            _ => i + 1,
+        };
+        let token = &src[i..end];
+        emit_gap(&mut out, gap, token);
+        out.extend_from_slice(token);
+
+        let closes_condition = match b {
+            b'(' => {
+                parens.push(condition_keyword);
+                false
+            }
+            b')' => parens.pop()?,
+            _ => false,
+        };
+        // After `.` a keyword is a property name (`a.return / 2`)
+        let keyword = is_word(b) && !b.is_ascii_digit() && prev != Prev::Punct(b'.');
+        condition_keyword = keyword && CONDITION_KEYWORDS.contains(&token);
+
+        prev = match b {
+            b'\'' | b'"' | b'`' | b'/' | b'.' if token.len() > 1 => Prev::Operand,
+            b if b.is_ascii_digit() => Prev::Operand,
+            b if is_word(b) => Prev::Word(keyword && REGEX_KEYWORDS.contains(&token)),
+            b')' if closes_condition => Prev::Condition,
+            b'+' | b'-' if prev == Prev::Punct(b) && gap == Gap::None => Prev::Update,
+            b => Prev::Punct(b),
+        };
+        gap = Gap::None;
+        i = end;
+    }
+
+    Some(out)
+}
+
+/// Separate `token` from the output so far as little as `gap` allows
+fn emit_gap(out: &mut Vec<u8>, gap: Gap, token: &[u8]) {
+    let Some(&last) = out.last() else {
+        return;
+    };
+    let first = token[0];
+    match gap {
+        Gap::None => {}
+        Gap::Newline if !can_join(last, token) => out.push(b'\n'),
+        _ => {
+            if needs_space(last, first) {
+                out.push(b' ');
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CSS
+// ---------------------------------------------------------------------------
+
+/// Whitespace can go after these
+const CSS_OPEN: &[u8] = b"{};,>~:(!/";
+/// Whitespace can go before these. Not `(` (`and (` in media queries) nor
+/// `+`/`-` (`calc()`), and not `:` (`a :hover` differs from `a:hover`).
+const CSS_CLOSE: &[u8] = b"{};,>~)!";
+
+/// Strip comments and whitespace from CSS. `None` if the source doesn't
+/// tokenize (unterminated string or comment).
+pub fn minify_css(src: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut space = false;
+    let mut i = 0;
+
+    while let Some(&b) = src.get(i) {
+        if b.is_ascii_whitespace() {
+            space = true;
+            i += 1;
+            continue;
+        }
+        if b == b'/' && src.get(i + 1) == Some(&b'*') {
+            let len = src[i + 2..].windows(2).position(|w| w == b"*/")?;
+            let end = i + 2 + len + 2;
+            if src[i..].starts_with(b"/*!") {
+                emit_css_space(&mut out, space, b'/');
+                out.extend_from_slice(&src[i..end]);
+                space = false;
+            } else if out.last().is_some_and(|&b| is_css_word(b))
+                && src.get(end).is_some_and(|&b| is_css_word(b))
+            {
+                // `a/**/b` is two identifiers
+                space = true;
+            }
+            i = end;
+            continue;
+        }
+
+        let end = match b {
+            b'"' | b'\'' => scan_css_string(src, i)?,
+            _ if is_url_start(src, i) => scan_css_url(src, i)?,
+            b'\\' => (i + 2).min(src.len()),
+            _ => i + 1,
+        };
+        emit_css_space(&mut out, space, b);
+        // The last declaration of a block needs no `;`
+        if b == b'}' && out.last() == Some(&b';') {
+            out.pop();
+        }
+        out.extend_from_slice(&src[i..end]);
+        space = false;
+        i = end;
+    }
+
+    Some(out)
+}
+
+fn is_css_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_') || b >= 0x80
+}
+
+fn emit_css_space(out: &mut Vec<u8>, space: bool, next: u8) {
+    if let Some(&last) = out.last() {
+        if space && !CSS_OPEN.contains(&last) && !CSS_CLOSE.contains(&next) {
+            out.push(b' ');
+        }
+    }
+}
+
+/// End of the CSS string starting at `start`
+fn scan_css_string(src: &[u8], start: usize) -> Option<usize> {
+    let quote = src[start];
+    let mut i = start + 1;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'\\' => i += 2,
+            b if b == quote => return Some(i + 1),
+            b'\n' => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Whether an unquoted `url(` starts at `i`, whose contents are kept as
+/// they are (`//` and `/*` are no comments in there)
+fn is_url_start(src: &[u8], i: usize) -> bool {
+    src.len() >= i + 4
+        && src[i..i + 4].eq_ignore_ascii_case(b"url(")
+        && (i == 0 || !is_css_word(src[i - 1]))
+}
+
+/// End of the `url(...)` starting at `start`
+fn scan_css_url(src: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 4;
+    while let Some(&b) = src.get(i) {
+        match b {
+            b'"' | b'\'' => i = scan_css_string(src, i)?,
+            b'\\' => i += 2,
+            b')' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// ---------------------------------------------------------------------------
+// HTML
+// ---------------------------------------------------------------------------
+
+/// Elements whose contents are kept as they are
+const HTML_RAW_ELEMENTS: [&[u8]; 4] = [b"pre", b"script", b"style", b"textarea"];
+
+/// Minify a Knockout template: drop comments other than `<!-- ko -->`
+/// bindings and conditional comments, and collapse whitespace runs to a
+/// single space. Returns `None` if a comment or tag isn't closed.
+pub fn minify_html(src: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut space = false;
+    let mut i = 0;
+
+    while let Some(&b) = src.get(i) {
+        if b.is_ascii_whitespace() {
+            space = true;
+            i += 1;
+            continue;
+        }
+        if src[i..].starts_with(b"<!--") {
+            let len = src[i + 4..].windows(3).position(|w| w == b"-->")?;
+            let end = i + 4 + len + 3;
+            if is_kept_comment(&src[i + 4..end - 3]) {
+                emit_html_space(&mut out, space);
+                out.extend_from_slice(&src[i..end]);
+                space = false;
+            }
+            i = end;
+            continue;
+        }
+
+        emit_html_space(&mut out, space);
+        space = false;
+        let is_tag = b == b'<'
+            && src
+                .get(i + 1)
+                .is_some_and(|&c| c.is_ascii_alphabetic() || c == b'/' || c == b'!');
+        if !is_tag {
+            out.push(b);
+            i += 1;
+            continue;
+        }
+
+        let end = scan_html_tag(src, i, &mut out)?;
+        if let Some(name) = raw_element(&src[i..end]) {
+            let close = find_closing_tag(src, end, name)?;
+            out.extend_from_slice(&src[end..close]);
+            i = close;
+        } else {
+            i = end;
+        }
+    }
+
+    Some(out)
+}
+
+/// Whether a comment's contents make it one to keep: a Knockout virtual
+/// element (`ko if: x`, `/ko`) or an IE conditional comment
+fn is_kept_comment(contents: &[u8]) -> bool {
+    let start = contents.iter().position(|b| !b.is_ascii_whitespace());
+    let end = contents.iter().rposition(|b| !b.is_ascii_whitespace());
+    let contents = match (start, end) {
+        (Some(start), Some(end)) => &contents[start..=end],
+        _ => return false,
+    };
+    contents == b"/ko"
+        || contents == b"ko"
+        || (contents.starts_with(b"ko") && contents.get(2).is_some_and(u8::is_ascii_whitespace))
+        || contents.starts_with(b"[if")
+        || contents.starts_with(b"<![endif]")
+}
+
+fn emit_html_space(out: &mut Vec<u8>, space: bool) {
+    if space && !out.is_empty() {
+        out.push(b' ');
+    }
+}
+
+/// Copy the tag starting at `start` to `out` with its whitespace
+/// collapsed, attribute values verbatim. Returns the end of the tag.
+fn scan_html_tag(src: &[u8], start: usize, out: &mut Vec<u8>) -> Option<usize> {
+    let mut space = false;
+    let mut i = start;
+    while let Some(&b) = src.get(i) {
+        match b {
+            _ if b.is_ascii_whitespace() => {
+                space = true;
+                i += 1;
+                continue;
+            }
+            b'"' | b'\'' => {
+                let len = src[i + 1..].iter().position(|&c| c == b)?;
+                if space && out.last() != Some(&b'=') {
+                    out.push(b' ');
+                }
+                out.extend_from_slice(&src[i..i + len + 2]);
+                i += len + 2;
+            }
+            b'>' => {
+                out.push(b);
+                return Some(i + 1);
+            }
+            _ => {
+                if space && !matches!(out.last(), Some(b'=')) && b != b'=' {
+                    out.push(b' ');
+                }
+                out.push(b);
+                i += 1;
+            }
+        }
+        space = false;
+    }
+    None
+}
+
+/// Name of the raw text element `tag` opens, if it does
+fn raw_element(tag: &[u8]) -> Option<&'static [u8]> {
+    if tag.ends_with(b"/>") {
+        return None;
+    }
+    let name_end = tag
+        .iter()
+        .skip(1)
+        .position(|b| !b.is_ascii_alphanumeric())
+        .map_or(tag.len(), |len| len + 1);
+    let name = &tag[1..name_end];
+    HTML_RAW_ELEMENTS
+        .into_iter()
+        .find(|raw| raw.eq_ignore_ascii_case(name))
+}
+
+/// Start of the `</name` closing tag at or after `from`
+fn find_closing_tag(src: &[u8], from: usize, name: &[u8]) -> Option<usize> {
+    (from..src.len().saturating_sub(name.len() + 1)).find(|&i| {
+        src[i] == b'<'
+            && src[i + 1] == b'/'
+            && src[i + 2..i + 2 + name.len()].eq_ignore_ascii_case(name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn minify(src: &str) -> String {
+        String::from_utf8(minify_js(src.as_bytes()).unwrap()).unwrap()
+    }
+
+    fn js_only() -> Minify {
+        Minify {
+            js: true,
+            ..Minify::default()
+        }
+    }
+
+    // ==================== minify_js tests ====================
+
+    #[test]
+    fn test_minify_js_strips_comments_and_indentation() {
+        let src = "/**\n * Doc\n */\ndefine([\n    'jquery'\n], function ($) {\n    'use strict';\n\n    // Greet\n    return function (name) {\n        return 'Hi ' + name; /* inline */\n    };\n});\n";
+        assert_eq!(
+            minify(src),
+            "define(['jquery'],function($){'use strict';return function(name){return'Hi '+name;};});"
+        );
+    }
+
+    #[test]
+    fn test_minify_js_keeps_line_breaks_asi_depends_on() {
+        // No semicolons: each line break ends a statement
+        assert_eq!(
+            minify("var a = 1\nvar b = a\n++b\n"),
+            "var a=1\nvar b=a\n++b"
+        );
+        assert_eq!(
+            minify("function f() {\n  return\n  42\n}"),
+            "function f(){return\n42}"
+        );
+        assert_eq!(minify("a = b\n(c || d).e()"), "a=b\n(c||d).e()");
+        // Unfinished lines join
+        assert_eq!(minify("x = [\n  1,\n  2\n]\n.length"), "x=[1,2].length");
+    }
+
+    #[test]
+    fn test_minify_js_keeps_tokens_apart() {
+        assert_eq!(minify("a + +b - -c"), "a+ +b- -c");
+        assert_eq!(minify("x = a / /re/.exec(s)[0]"), "x=a/ /re/.exec(s)[0]");
+        assert_eq!(minify("typeof x === 'y'"), "typeof x==='y'");
+        assert_eq!(minify("1 .toString()"), "1 .toString()");
+        assert_eq!(minify("a/**/b"), "a b");
+    }
+
+    #[test]
+    fn test_minify_js_literals_verbatim() {
+        let src =
+            "s = 'a  // b' + \"c /* d */\"\nr = /[/'\"]  +/g.test(s)\nt = `x  ${ {a: '}'}.a }  y`";
+        assert_eq!(
+            minify(src),
+            "s='a  // b'+\"c /* d */\"\nr=/[/'\"]  +/g.test(s)\nt=`x  ${ {a: '}'}.a }  y`"
+        );
+        // Division, not a regex, after an operand
+        assert_eq!(minify("x = a / 2 / b"), "x=a/2/b");
+        assert_eq!(minify("x = 1.5e-3 + .5"), "x=1.5e-3+.5");
+    }
+
+    #[test]
+    fn test_minify_js_keeps_license_comments() {
+        assert_eq!(
+            minify("/*! jQuery | MIT */\n(function () {})();"),
+            "/*! jQuery | MIT */\n(function(){})();"
+        );
+    }
+
+    #[test]
+    fn test_minify_js_untokenizable() {
+        assert_eq!(minify_js(b"var s = 'unterminated\n"), None);
+        assert_eq!(minify_js(b"/* open"), None);
+        assert_eq!(minify_js(b"x = `open"), None);
+        assert_eq!(minify_js(b"f(a));"), None);
+    }
+
+    #[test]
+    fn test_minify_js_regex_or_division_after_parenthesis() {
+        // The head of a statement: a regex literal follows
+        assert_eq!(minify("if (ok) /a  b/.test(s);"), "if(ok)/a  b/.test(s);");
+        assert_eq!(
+            minify("while (f(x)) /[ ]+/g.exec(s)"),
+            "while(f(x))/[ ]+/g.exec(s)"
+        );
+        // Any other `)` ends an operand
+        assert_eq!(minify("x = (a + b) / 2 / c"), "x=(a+b)/2/c");
+        assert_eq!(minify("if (a) x = f(b) / 2"), "if(a)x=f(b)/2");
+    }
+
+    #[test]
+    fn test_minify_js_division_after_postfix_update() {
+        assert_eq!(minify("x = i++ / 2 / c"), "x=i++/2/c");
+        assert_eq!(minify("x = i-- / 2; // it's"), "x=i--/2;");
+        // `a++ + /b/` and `a + +/b/.length` start a regex after the last `+`
+        assert_eq!(minify("s = a++ + /b  c/.source"), "s=a++ +/b  c/.source");
+        assert_eq!(minify("n = a + +/b  c/.flags"), "n=a+ +/b  c/.flags");
+    }
+
+    #[test]
+    fn test_minify_js_keyword_property_is_an_operand() {
+        assert_eq!(minify("x = a.return / 2 / b"), "x=a.return/2/b");
+        assert_eq!(minify("x = a?.in / 2 / b"), "x=a?.in/2/b");
+        assert_eq!(minify("return /a  b/"), "return/a  b/");
+    }
+
+    #[test]
+    fn test_minify_js_ambiguous_slash_after_brace() {
+        // A block then a regex, or an object literal then a division
+        assert_eq!(minify_js(b"{}\n/a  b/.test(s)"), None);
+        assert_eq!(minify_js(b"x = {} / 2"), None);
+        assert_eq!(minify("function f() {}\n// done"), "function f(){}");
+    }
+
+    // ==================== minify_css tests ====================
+
+    fn minify_style(src: &str) -> String {
+        String::from_utf8(minify_css(src.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_minify_css_strips_comments_and_whitespace() {
+        let src = "/*! Vendor | MIT */\n/* Buttons */\n.btn ,\n.btn > span {\n    color : red !important;\n    margin: 0 auto;\n}\n\n@media screen and (max-width: 768px) {\n    .btn { display: none; }\n}\n";
+        assert_eq!(
+            minify_style(src),
+            "/*! Vendor | MIT */.btn,.btn>span{color :red!important;margin:0 auto}@media screen and (max-width:768px){.btn{display:none}}"
+        );
+    }
+
+    #[test]
+    fn test_minify_css_keeps_meaningful_spaces() {
+        // Descendant pseudo-class, calc() operators
+        assert_eq!(minify_style("a :hover { }"), "a :hover{}");
+        assert_eq!(
+            minify_style("div { width: calc(100% - (2 * 10px)); }"),
+            "div{width:calc(100% - (2 * 10px))}"
+        );
+        assert_eq!(minify_style("a/* x */b{}"), "a b{}");
+        assert_eq!(minify_style("a/**/.b{}"), "a.b{}");
+    }
+
+    #[test]
+    fn test_minify_css_literals_verbatim() {
+        assert_eq!(
+            minify_style("a { content: \"  /* x */ \"; background: url(//cdn.example.com/a  b.png) no-repeat; }"),
+            "a{content:\"  /* x */ \";background:url(//cdn.example.com/a  b.png) no-repeat}"
+        );
+        assert_eq!(minify_css(b"a { content: 'open }"), None);
+        assert_eq!(minify_css(b"a { } /* open"), None);
+    }
+
+    #[test]
+    fn test_install_transformed_css_threshold_and_minified_sources() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("styles.css");
+        let dst = temp.path().join("styles.min.css");
+        let options = CopyOptions {
+            minify: Some(Minify {
+                css: true,
+                css_min_size: 32,
+                ..Minify::default()
+            }),
+            ..CopyOptions::default()
+        };
+        let stats = MinifyStats::new();
+        let install = || install_transformed(&src, &dst, Transform::MinifyCss, &options, &stats);
+
+        // Below the threshold
+        fs::write(&src, "a {  color: red; }\n").unwrap();
+        assert_eq!(install().unwrap().saved, 0);
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "a {  color: red; }\n");
+
+        let big = "a {\n    color: red;\n}\n".repeat(4);
+        fs::write(&src, &big).unwrap();
+        assert_eq!(install().unwrap().saved, (big.len() - 4 * 12) as u64);
+        assert_eq!(stats.css.files.load(Ordering::Relaxed), 1);
+
+        // Tailwind-style output: one long line
+        let tailwind = format!("/*! tailwindcss */\n{}\n", "a{color:red}  ".repeat(200));
+        fs::write(&src, &tailwind).unwrap();
+        assert_eq!(install().unwrap().saved, 0);
+        assert_eq!(fs::read_to_string(&dst).unwrap(), tailwind);
+        assert_eq!(stats.css.unchanged.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.js.files.load(Ordering::Relaxed), 0);
+    }
+
+    // ==================== minify_html tests ====================
+
+    fn minify_template(src: &str) -> String {
+        String::from_utf8(minify_html(src.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_minify_html_keeps_knockout_comments() {
+        let src = "<!-- Copyright Vendor -->\n<div class=\"field\">\n    <!-- ko if: visible -->\n    <span data-bind=\"text: label\"></span>\n    <!--/ko-->\n    <!-- ko i18n: 'Add to Cart' --><!-- /ko -->\n</div>\n";
+        assert_eq!(
+            minify_template(src),
+            "<div class=\"field\"> <!-- ko if: visible --> <span data-bind=\"text: label\"></span> <!--/ko--> <!-- ko i18n: 'Add to Cart' --><!-- /ko --> </div>"
+        );
+        assert_eq!(
+            minify_template("<!--[if IE]><p>IE</p><![endif]--><!-- kotlin -->"),
+            "<!--[if IE]><p>IE</p><![endif]-->"
+        );
+    }
+
+    #[test]
+    fn test_minify_html_collapses_tags() {
+        assert_eq!(
+            minify_template("<input\n    type = \"text\"\n    data-bind=\"value:  query,\n  attr: {id: 'q'}\"\n/>"),
+            "<input type=\"text\" data-bind=\"value:  query,\n  attr: {id: 'q'}\" />"
+        );
+        assert_eq!(minify_template("a <!-- x --> b<!-- y -->c"), "a bc");
+        assert_eq!(
+            minify_template("<p>\n  1 &lt; 2\n</p >"),
+            "<p> 1 &lt; 2 </p>"
+        );
+    }
+
+    #[test]
+    fn test_minify_html_raw_elements_verbatim() {
+        assert_eq!(
+            minify_template("<pre>\n  a  <!-- b -->\n</pre>\n<SCRIPT type=\"text/x-magento-template\">\n  <% if (x) { %>  <% } %>\n</Script>"),
+            "<pre>\n  a  <!-- b -->\n</pre> <SCRIPT type=\"text/x-magento-template\">\n  <% if (x) { %>  <% } %>\n</Script>"
+        );
+        assert_eq!(minify_html(b"<!-- open"), None);
+        assert_eq!(minify_html(b"<div class=\"open>"), None);
+        assert_eq!(minify_html(b"<textarea>x"), None);
+    }
+
+    // ==================== Minify tests ====================
+
+    #[test]
+    fn test_minify_derive() {
+        let minify = js_only();
+        let dir = "frontend/Vendor/theme/en_US";
+        assert_eq!(
+            minify.derive(Path::new("Magento_Ui/js/core/app.js"), dir),
+            Some((
+                PathBuf::from("Magento_Ui/js/core/app.min.js"),
+                Transform::MinifyJs
+            ))
+        );
+        assert_eq!(minify.derive(Path::new("jquery.min.js"), dir), None);
+        assert_eq!(minify.derive(Path::new("css/styles.css"), dir), None);
+        assert_eq!(minify.derive(Path::new(".js"), dir), None);
+        assert_eq!(Minify::default().derive(Path::new("app.js"), dir), None);
+
+        let minify = Minify {
+            js: true,
+            js_exclude: compile_excludes(&["/tiny_mce/".to_string()]).unwrap(),
+            ..Minify::default()
+        };
+        assert_eq!(minify.derive(Path::new("tiny_mce/tinymce.js"), dir), None);
+        assert!(minify.derive(Path::new("mage/tiny.js"), dir).is_some());
+        assert_eq!(minify.derive(Path::new("css/styles.css"), dir), None);
+
+        let minify = Minify {
+            css: true,
+            ..Minify::default()
+        };
+        assert_eq!(
+            minify.derive(Path::new("css/styles.css"), dir),
+            Some((PathBuf::from("css/styles.min.css"), Transform::MinifyCss))
+        );
+        assert_eq!(minify.derive(Path::new("css/styles.min.css"), dir), None);
+        assert_eq!(minify.derive(Path::new("js/app.js"), dir), None);
+    }
+
+    #[test]
+    fn test_minify_in_place_templates() {
+        let dir = "frontend/Vendor/theme/en_US";
+        let template = Path::new("Magento_Checkout/template/minicart/content.html");
+        assert_eq!(js_only().in_place(template, dir), None);
+
+        let minify = Minify {
+            html: true,
+            html_exclude: compile_excludes(&["/Vendor_Legacy/".to_string()]).unwrap(),
+            ..Minify::default()
+        };
+        assert_eq!(minify.in_place(template, dir), Some(Transform::MinifyHtml));
+        assert_eq!(
+            minify.in_place(Path::new("template/a.html"), dir),
+            Some(Transform::MinifyHtml)
+        );
+        assert_eq!(
+            minify.in_place(Path::new("Magento_Theme/page.html"), dir),
+            None
+        );
+        assert_eq!(
+            minify.in_place(Path::new("Magento_Ui/template/grid.js"), dir),
+            None
+        );
+        assert_eq!(
+            minify.in_place(Path::new("Vendor_Legacy/template/a.html"), dir),
+            None
+        );
+        assert_eq!(minify.derive(template, dir), None);
+    }
+
+    #[test]
+    fn test_install_transformed_counts_savings() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("app.js");
+        let dst = temp.path().join("out/app.min.js");
+        fs::write(&src, "var a = 1;   // one\n").unwrap();
+        let stats = MinifyStats::new();
+        let options = CopyOptions {
+            minify: Some(js_only()),
+            ..CopyOptions::default()
+        };
+
+        let written =
+            install_transformed(&src, &dst, Transform::MinifyJs, &options, &stats).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "var a=1;");
+        assert_eq!(
+            written,
+            Transformed {
+                bytes: 8,
+                saved: 12
+            }
+        );
+        assert_eq!(stats.js.files.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.js.saved.load(Ordering::Relaxed), 12);
+
+        // Not tokenizable: written as is
+        fs::write(&src, "var s = 'open\n").unwrap();
+        install_transformed(&src, &dst, Transform::MinifyJs, &options, &stats).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "var s = 'open\n");
+        assert_eq!(stats.js.unchanged.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_minify_output_adds_missing_min_files() {
+        let temp = TempDir::new().unwrap();
+        let output = temp.path();
+        fs::create_dir_all(output.join("js")).unwrap();
+        fs::write(output.join("js/app.js"), "var a = 1;\n").unwrap();
+        fs::write(output.join("js/lib.js"), "var b = 2;\n").unwrap();
+        fs::write(output.join("js/lib.min.js"), "shipped").unwrap();
+        let options = CopyOptions {
+            minify: Some(js_only()),
+            ..CopyOptions::default()
+        };
+        let stats = MinifyStats::new();
+
+        let saved = minify_output(
+            output,
+            "frontend/Magento/luma/en_US",
+            &options,
+            &stats,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(
+            saved,
+            MinifySaved {
+                js: 3,
+                ..MinifySaved::default()
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("js/app.min.js")).unwrap(),
+            "var a=1;"
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("js/lib.min.js")).unwrap(),
+            "shipped"
+        );
+    }
+
+    // ==================== exclude tests ====================
+
+    #[test]
+    fn test_parse_minify_excludes() {
+        let xml = r#"<?xml version="1.0"?>
+<config xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+    <default>
+        <dev>
+            <js>
+                <minify_exclude>
+                    <tiny_mce>/tiny_mce/</tiny_mce>
+                    <cardinal>/v1/songbird</cardinal>
+                </minify_exclude>
+            </js>
+            <css>
+                <minify_exclude>
+                    <css_ex>/ignored/</css_ex>
+                </minify_exclude>
+            </css>
+        </dev>
+    </default>
+</config>"#;
+        let pair = |name: &str, pattern: &str| (name.to_string(), pattern.to_string());
+        assert_eq!(
+            parse_minify_excludes(xml, "js"),
+            vec![
+                pair("tiny_mce", "/tiny_mce/"),
+                pair("cardinal", "/v1/songbird")
+            ]
+        );
+        assert_eq!(
+            parse_minify_excludes(xml, "css"),
+            vec![pair("css_ex", "/ignored/")]
+        );
+
+        let legacy = "<config><default><dev><js><minify_exclude>\n/tiny_mce/\n/other/\n</minify_exclude></js></dev></default></config>";
+        assert_eq!(
+            parse_minify_excludes(legacy, "js"),
+            vec![pair("/tiny_mce/", "/tiny_mce/"), pair("/other/", "/other/")]
+        );
+    }
+
+    #[test]
+    fn test_parse_php_minify_excludes() {
+        let php = r#"<?php
+return [
+    'modules' => ['Magento_Store' => 1],
+    'system' => [
+        'default' => [
+            'dev' => [
+                'js' => [
+                    'merge_files' => '1',
+                    // Replaces the module's
+                    'minify_exclude' => [
+                        'tiny_mce' => '/tiny_mce_v5/',
+                        "acme" => '/acme\'s/',
+                    ],
+                ],
+                'css' => array(
+                    'minify_exclude' => "/a/
+/b/"
+                ),
+            ],
+        ],
+        'stores' => ['default' => ['dev' => ['js' => ['minify_exclude' => ['x' => '/store/']]]]],
+    ],
+];
+"#;
+        let pair = |name: &str, pattern: &str| (name.to_string(), pattern.to_string());
+        assert_eq!(
+            parse_php_minify_excludes(php, "js"),
+            vec![pair("tiny_mce", "/tiny_mce_v5/"), pair("acme", "/acme's/")]
+        );
+        assert_eq!(
+            parse_php_minify_excludes(php, "css"),
+            vec![pair("/a/", "/a/"), pair("/b/", "/b/")]
+        );
+        assert!(parse_php_minify_excludes("<?php return [];", "js").is_empty());
+    }
+
+    #[test]
+    fn test_magento_excludes_from_modules() {
+        let temp = TempDir::new().unwrap();
+        let config = |rel: &str, pattern: &str| {
+            let path = temp.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(
+                path,
+                format!("<config><default><dev><js><minify_exclude><x>{pattern}</x></minify_exclude></js></dev></default></config>"),
+            )
+            .unwrap();
+        };
+        config("vendor/magento/module-store/etc/config.xml", "/tiny_mce/");
+        config("vendor/acme/module-pay/src/etc/config.xml", "/acme/sdk");
+        config("app/code/Local/Widget/etc/config.xml", "/tiny_mce/");
+
+        assert_eq!(
+            magento_excludes(temp.path(), "js"),
+            vec!["/acme/sdk", "/tiny_mce/"]
+        );
+
+        // config.php, then env.php, replace entries of the same name
+        fs::create_dir_all(temp.path().join("app/etc")).unwrap();
+        fs::write(
+            temp.path().join("app/etc/config.php"),
+            "<?php return ['system' => ['default' => ['dev' => ['js' => ['minify_exclude' => ['x' => '/config/', 'y' => '/extra/']]]]]];",
+        )
+        .unwrap();
+        fs::write(
+            temp.path().join("app/etc/env.php"),
+            "<?php return ['system' => ['default' => ['dev' => ['js' => ['minify_exclude' => ['y' => '']]]]]];",
+        )
+        .unwrap();
+        assert_eq!(magento_excludes(temp.path(), "js"), vec!["/config/"]);
+    }
+}
@@ -0,0 +1,450 @@
+//! Resolved deployment plans.
+//!
+//! A `DeployPlan` maps every destination file of one job to the source that
+//! wins it, plus the lower priority sources it shadows. Resolution happens
+//! up front from `collect_file_sources` order, so executing a plan needs no
+//! existence checks and the winning source of any file can be inspected.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::copier::list_files;
+use crate::minify::Transform;
+use crate::scanner::FileSource;
+
+/// File listings of source directories, shared across plans
+/// (lib/web and vendor modules appear in every job)
+#[derive(Debug, Default)]
+pub struct SourceListings {
+    files: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl SourceListings {
+    /// List every distinct source directory once, in parallel
+    pub fn collect<'a>(
+        sources: impl IntoIterator<Item = &'a FileSource>,
+        include_dev: bool,
+    ) -> Self {
+        let mut dirs: Vec<&Path> = sources.into_iter().map(FileSource::path).collect();
+        dirs.sort_unstable();
+        dirs.dedup();
+
+        let files = dirs
+            .par_iter()
+            .map(|dir| (dir.to_path_buf(), list_files(dir, include_dev)))
+            .collect();
+
+        Self { files }
+    }
+
+    /// Files under `dir`, relative to it
+    pub fn get(&self, dir: &Path) -> &[PathBuf] {
+        self.files.get(dir).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// One destination file and where it comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanEntry {
+    /// Destination relative to the plan's output directory
+    pub dest: PathBuf,
+    /// Path relative to the winning source's directory
+    pub relative: PathBuf,
+    /// Index of the winning source
+    pub source: usize,
+    /// Indices of lower priority sources that also provide this file
+    pub shadowed: Vec<usize>,
+    /// How the file is generated from the source; `None` for a plain copy
+    pub transform: Option<Transform>,
+}
+
+/// Resolved file → source mapping for one theme×locale output
+#[derive(Debug, Clone)]
+pub struct DeployPlan {
+    output: PathBuf,
+    sources: Vec<FileSource>,
+    /// Sorted by `dest`
+    entries: Vec<PlanEntry>,
+}
+
+impl DeployPlan {
+    /// Build a plan, listing the sources' directories
+    pub fn build(output: PathBuf, sources: Vec<FileSource>, include_dev: bool) -> Self {
+        let listings = SourceListings::collect(&sources, include_dev);
+        Self::from_listings(output, sources, &listings)
+    }
+
+    /// Build a plan from already collected listings.
+    /// `sources` must be in priority order, highest first.
+    pub fn from_listings(
+        output: PathBuf,
+        sources: Vec<FileSource>,
+        listings: &SourceListings,
+    ) -> Self {
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+        let mut entries: Vec<PlanEntry> = Vec::new();
+
+        for (source_idx, source) in sources.iter().enumerate() {
+            for relative in listings.get(source.path()) {
+                let dest = match source.module() {
+                    Some(module) => Path::new(module).join(relative),
+                    None => relative.clone(),
+                };
+
+                match index.get(&dest) {
+                    Some(&existing) => entries[existing].shadowed.push(source_idx),
+                    None => {
+                        index.insert(dest.clone(), entries.len());
+                        entries.push(PlanEntry {
+                            dest,
+                            relative: relative.clone(),
+                            source: source_idx,
+                            shadowed: Vec::new(),
+                            transform: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        entries.sort_unstable_by(|a, b| a.dest.cmp(&b.dest));
+
+        Self {
+            output,
+            sources,
+            entries,
+        }
+    }
+
+    /// Write the plain entries `transform` picks transformed rather than
+    /// copied (e.g. minified in place)
+    pub fn transform(&mut self, transform: impl Fn(&PlanEntry) -> Option<Transform>) {
+        for entry in &mut self.entries {
+            if entry.transform.is_none() {
+                entry.transform = transform(entry);
+            }
+        }
+    }
+
+    /// Add the files `derive` generates from plain entries (e.g. minified
+    /// copies). A planned file at the same destination is kept if it comes
+    /// from the same or a higher priority source, and shadowed otherwise
+    /// (a theme's `foo.js` beats a module's `foo.min.js`).
+    pub fn derive(&mut self, derive: impl Fn(&PlanEntry) -> Option<(PathBuf, Transform)>) {
+        let derived: Vec<PlanEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.transform.is_none())
+            .filter_map(|entry| {
+                let (dest, transform) = derive(entry)?;
+                Some(PlanEntry {
+                    dest,
+                    relative: entry.relative.clone(),
+                    source: entry.source,
+                    shadowed: Vec::new(),
+                    transform: Some(transform),
+                })
+            })
+            .collect();
+
+        let mut added = Vec::new();
+        for mut entry in derived {
+            match self.position(&entry.dest) {
+                Some(idx) => {
+                    let existing = &mut self.entries[idx];
+                    if existing.source > entry.source {
+                        entry.shadowed.push(existing.source);
+                        entry.shadowed.append(&mut existing.shadowed);
+                        *existing = entry;
+                    }
+                }
+                None => added.push(entry),
+            }
+        }
+        if added.is_empty() {
+            return;
+        }
+
+        self.entries.extend(added);
+        self.entries.sort_unstable_by(|a, b| a.dest.cmp(&b.dest));
+    }
+
+    /// Output directory the plan deploys into
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Deploy into another directory (e.g. a staging directory)
+    pub fn retarget(&mut self, output: PathBuf) {
+        self.output = output;
+    }
+
+    /// Sources in priority order
+    pub fn sources(&self) -> &[FileSource] {
+        &self.sources
+    }
+
+    /// All entries, sorted by destination
+    pub fn entries(&self) -> &[PlanEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entry for a destination relative to the output directory
+    pub fn get(&self, dest: &Path) -> Option<&PlanEntry> {
+        self.position(dest).map(|idx| &self.entries[idx])
+    }
+
+    /// Index of the entry for `dest`
+    fn position(&self, dest: &Path) -> Option<usize> {
+        self.entries
+            .binary_search_by(|entry| entry.dest.as_path().cmp(dest))
+            .ok()
+    }
+
+    /// Source that wins `dest`
+    pub fn winner(&self, dest: &Path) -> Option<&FileSource> {
+        self.get(dest).map(|entry| &self.sources[entry.source])
+    }
+
+    /// Sources shadowed at `dest`, highest priority first
+    pub fn shadowed(&self, dest: &Path) -> Vec<&FileSource> {
+        self.get(dest)
+            .map(|entry| entry.shadowed.iter().map(|&i| &self.sources[i]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Absolute source file for an entry
+    pub fn source_path(&self, entry: &PlanEntry) -> PathBuf {
+        self.sources[entry.source].path().join(&entry.relative)
+    }
+
+    /// Absolute destination file for an entry
+    pub fn dest_path(&self, entry: &PlanEntry) -> PathBuf {
+        self.output.join(&entry.dest)
+    }
+
+    /// Number of files provided by more than one source
+    pub fn shadowed_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.shadowed.is_empty())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    // ==================== SourceListings tests ====================
+
+    #[test]
+    fn test_source_listings_missing_dir_is_empty() {
+        let temp = TempDir::new().unwrap();
+        let source = FileSource::Library {
+            path: temp.path().join("missing"),
+        };
+        let listings = SourceListings::collect([&source], false);
+
+        assert!(listings.get(source.path()).is_empty());
+        assert!(listings.get(Path::new("/not/listed")).is_empty());
+    }
+
+    // ==================== DeployPlan tests ====================
+
+    #[test]
+    fn test_plan_winner_and_shadowed() {
+        let temp = TempDir::new().unwrap();
+        let theme_web = temp.path().join("theme/web");
+        let parent_web = temp.path().join("parent/web");
+        let lib = temp.path().join("lib/web");
+        write(theme_web.join("js/app.js"), "theme");
+        write(parent_web.join("js/app.js"), "parent");
+        write(lib.join("js/app.js"), "lib");
+        write(lib.join("js/lib.js"), "lib");
+
+        let sources = vec![
+            FileSource::ThemeWeb {
+                theme: "Test/child".to_string(),
+                path: theme_web.clone(),
+            },
+            FileSource::ThemeWeb {
+                theme: "Test/parent".to_string(),
+                path: parent_web.clone(),
+            },
+            FileSource::Library { path: lib.clone() },
+        ];
+        let plan = DeployPlan::build(temp.path().join("out"), sources, false);
+
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan.shadowed_count(), 1);
+
+        let app = Path::new("js/app.js");
+        assert_eq!(plan.winner(app).unwrap().path(), theme_web.as_path());
+        let shadowed: Vec<&Path> = plan.shadowed(app).iter().map(|s| s.path()).collect();
+        assert_eq!(shadowed, vec![parent_web.as_path(), lib.as_path()]);
+
+        let entry = plan.get(Path::new("js/lib.js")).unwrap();
+        assert!(entry.shadowed.is_empty());
+        assert_eq!(plan.source_path(entry), lib.join("js/lib.js"));
+        assert_eq!(
+            plan.dest_path(entry),
+            temp.path().join("out").join("js/lib.js")
+        );
+    }
+
+    #[test]
+    fn test_plan_module_sources_under_module_dir() {
+        let temp = TempDir::new().unwrap();
+        let override_web = temp.path().join("theme/Magento_Catalog/web");
+        let vendor_web = temp.path().join("vendor/catalog/view/frontend/web");
+        write(override_web.join("gallery.js"), "override");
+        write(vendor_web.join("gallery.js"), "vendor");
+        write(vendor_web.join("list.js"), "vendor");
+
+        let sources = vec![
+            FileSource::ThemeModuleOverride {
+                theme: "Test/theme".to_string(),
+                module: "Magento_Catalog".to_string(),
+                path: override_web.clone(),
+            },
+            FileSource::VendorModule {
+                module: "Magento_Catalog".to_string(),
+                path: vendor_web.clone(),
+            },
+        ];
+        let plan = DeployPlan::build(temp.path().join("out"), sources, false);
+
+        let dests: Vec<&Path> = plan.entries().iter().map(|e| e.dest.as_path()).collect();
+        assert_eq!(
+            dests,
+            vec![
+                Path::new("Magento_Catalog/gallery.js"),
+                Path::new("Magento_Catalog/list.js")
+            ]
+        );
+        assert_eq!(
+            plan.winner(Path::new("Magento_Catalog/gallery.js"))
+                .unwrap()
+                .path(),
+            override_web.as_path()
+        );
+        assert!(plan.get(Path::new("gallery.js")).is_none());
+    }
+
+    #[test]
+    fn test_plan_excludes_dev_files() {
+        let temp = TempDir::new().unwrap();
+        let web = temp.path().join("web");
+        write(web.join("app.js"), "js");
+        write(web.join("app.ts"), "ts");
+
+        let sources = vec![FileSource::Library { path: web }];
+
+        assert_eq!(
+            DeployPlan::build(temp.path().join("out"), sources.clone(), false).len(),
+            1
+        );
+        assert_eq!(
+            DeployPlan::build(temp.path().join("out"), sources, true).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_plan_derive_skips_planned_destinations() {
+        let temp = TempDir::new().unwrap();
+        let theme_web = temp.path().join("theme/web");
+        let lib = temp.path().join("lib/web");
+        write(theme_web.join("js/app.js"), "theme");
+        write(lib.join("jquery.js"), "lib");
+        write(lib.join("jquery.min.js"), "shipped");
+
+        let sources = vec![
+            FileSource::ThemeWeb {
+                theme: "Test/child".to_string(),
+                path: theme_web,
+            },
+            FileSource::Library { path: lib.clone() },
+        ];
+        let mut plan = DeployPlan::build(temp.path().join("out"), sources, false);
+        let minify = crate::minify::Minify {
+            js: true,
+            ..crate::minify::Minify::default()
+        };
+        plan.derive(|entry| minify.derive(&entry.dest, "frontend/Test/child/en_US"));
+        // Derived entries aren't derived from again
+        plan.derive(|entry| minify.derive(&entry.dest, "frontend/Test/child/en_US"));
+
+        let dests: Vec<&Path> = plan.entries().iter().map(|e| e.dest.as_path()).collect();
+        assert_eq!(
+            dests,
+            vec![
+                Path::new("jquery.js"),
+                Path::new("jquery.min.js"),
+                Path::new("js/app.js"),
+                Path::new("js/app.min.js"),
+            ]
+        );
+        let derived = plan.get(Path::new("js/app.min.js")).unwrap();
+        assert_eq!(derived.transform, Some(Transform::MinifyJs));
+        assert_eq!(derived.relative, Path::new("js/app.js"));
+        assert_eq!(derived.source, 0);
+        assert_eq!(
+            plan.get(Path::new("jquery.min.js")).unwrap().transform,
+            None
+        );
+        assert_eq!(
+            plan.source_path(plan.get(Path::new("jquery.min.js")).unwrap()),
+            lib.join("jquery.min.js")
+        );
+    }
+
+    #[test]
+    fn test_plan_derive_replaces_lower_priority_min_file() {
+        let temp = TempDir::new().unwrap();
+        let theme_web = temp.path().join("theme/web");
+        let lib = temp.path().join("lib/web");
+        write(theme_web.join("foo.js"), "theme override");
+        write(lib.join("foo.js"), "lib");
+        write(lib.join("foo.min.js"), "stale");
+
+        let sources = vec![
+            FileSource::ThemeWeb {
+                theme: "Test/child".to_string(),
+                path: theme_web.clone(),
+            },
+            FileSource::Library { path: lib },
+        ];
+        let mut plan = DeployPlan::build(temp.path().join("out"), sources, false);
+        let minify = crate::minify::Minify {
+            js: true,
+            ..crate::minify::Minify::default()
+        };
+        plan.derive(|entry| minify.derive(&entry.dest, "frontend/Test/child/en_US"));
+
+        assert_eq!(plan.entries().len(), 2);
+        let min = plan.get(Path::new("foo.min.js")).unwrap();
+        assert_eq!(min.transform, Some(Transform::MinifyJs));
+        assert_eq!(min.source, 0);
+        assert_eq!(min.shadowed, vec![1]);
+        assert_eq!(plan.source_path(min), theme_web.join("foo.js"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Removal of files left over from earlier deploys.
+//!
+//! Deploying only ever adds files, so a file dropped from a module keeps
+//! being served from `pub/static`. With `--prune`, every file in a job's
+//! output that the current deploy doesn't produce is deleted after the job
+//! succeeds, along with directories that end up empty. Protected files
+//! (`.htaccess` and friends) are never touched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use walkdir::WalkDir;
+
+use crate::error::DeployError;
+
+/// File names pruning never removes, wherever they are in the output
+pub const PROTECTED_FILES: &[&str] = &[
+    ".htaccess",            // Apache rewrites for static versioning
+    "web.config",           // IIS equivalent
+    ".user.ini",            // PHP-FPM per-directory settings
+    "deployed_version.txt", // Static content version stamp
+];
+
+/// Whether pruning must leave `path` alone
+pub fn is_protected(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| PROTECTED_FILES.contains(&name))
+}
+
+/// What a prune removed, relative to the pruned output directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Removed files and symlinks, sorted
+    pub files: Vec<PathBuf>,
+    /// Removed (empty) directories, deepest first
+    pub dirs: Vec<PathBuf>,
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.dirs.is_empty()
+    }
+}
+
+/// Remove every file under `output` for which `keep` (given the path
+/// relative to `output`) returns false, then every directory left empty.
+/// The output directory itself is kept. Symlinks are removed, never followed.
+pub fn prune_output(
+    output: &Path,
+    keep: impl Fn(&Path) -> bool,
+    shutdown: &AtomicBool,
+) -> Result<PruneReport, DeployError> {
+    let mut report = PruneReport::default();
+    if !output.is_dir() {
+        return Ok(report);
+    }
+
+    // Contents first, so a directory is visited after everything in it
+    for entry in WalkDir::new(output)
+        .min_depth(1)
+        .contents_first(true)
+        .sort_by_file_name()
+    {
+        if shutdown.load(Ordering::Relaxed) {
+            return Err(DeployError::Cancelled);
+        }
+
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        let Ok(relative) = path.strip_prefix(output) else {
+            continue;
+        };
+
+        if entry.file_type().is_dir() {
+            let is_empty = fs::read_dir(path)
+                .map(|mut entries| entries.next().is_none())
+                .unwrap_or(false);
+            if is_empty {
+                fs::remove_dir(path).map_err(|e| prune_error(path, e))?;
+                report.dirs.push(relative.to_path_buf());
+            }
+            continue;
+        }
+
+        if keep(relative) || is_protected(relative) {
+            continue;
+        }
+        fs::remove_file(path).map_err(|e| prune_error(path, e))?;
+        report.files.push(relative.to_path_buf());
+    }
+
+    report.files.sort_unstable();
+    Ok(report)
+}
+
+fn prune_error(path: &Path, e: std::io::Error) -> DeployError {
+    DeployError::PruneFailed {
+        path: path.to_path_buf(),
+        source: e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "x").unwrap();
+    }
+
+    // ==================== is_protected tests ====================
+
+    #[test]
+    fn test_is_protected() {
+        assert!(is_protected(Path::new(".htaccess")));
+        assert!(is_protected(Path::new("Magento_Catalog/.htaccess")));
+        assert!(!is_protected(Path::new("js/app.js")));
+        assert!(!is_protected(Path::new("htaccess")));
+    }
+
+    // ==================== prune_output tests ====================
+
+    #[test]
+    fn test_prune_output_removes_orphans() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.path().join("out");
+        write(out.join("js/app.js"));
+        write(out.join("js/old.js"));
+        write(out.join("Old_Module/gone/deep.js"));
+        write(out.join(".htaccess"));
+        write(out.join("Old_Module/.htaccess"));
+
+        let shutdown = AtomicBool::new(false);
+        let report = prune_output(&out, |p| p == Path::new("js/app.js"), &shutdown).unwrap();
+
+        assert_eq!(
+            report.files,
+            vec![
+                PathBuf::from("Old_Module/gone/deep.js"),
+                PathBuf::from("js/old.js")
+            ]
+        );
+        assert_eq!(report.dirs, vec![PathBuf::from("Old_Module/gone")]);
+        assert!(out.join("js/app.js").exists());
+        assert!(out.join(".htaccess").exists());
+        // Kept because its protected file is still there
+        assert!(out.join("Old_Module/.htaccess").exists());
+    }
+
+    #[test]
+    fn test_prune_output_keeps_output_dir() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.path().join("out");
+        write(out.join("a/b.js"));
+
+        let shutdown = AtomicBool::new(false);
+        let report = prune_output(&out, |_| false, &shutdown).unwrap();
+
+        assert_eq!(report.files, vec![PathBuf::from("a/b.js")]);
+        assert_eq!(report.dirs, vec![PathBuf::from("a")]);
+        assert!(out.is_dir());
+    }
+
+    #[test]
+    fn test_prune_output_missing_dir() {
+        let temp = TempDir::new().unwrap();
+        let shutdown = AtomicBool::new(false);
+        let report = prune_output(&temp.path().join("missing"), |_| false, &shutdown).unwrap();
+        assert!(report.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prune_output_does_not_follow_symlinks() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.path().join("out");
+        let elsewhere = temp.path().join("elsewhere");
+        write(elsewhere.join("keep.js"));
+        fs::create_dir_all(&out).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, out.join("linked")).unwrap();
+
+        let shutdown = AtomicBool::new(false);
+        let report = prune_output(&out, |_| false, &shutdown).unwrap();
+
+        assert_eq!(report.files, vec![PathBuf::from("linked")]);
+        assert!(elsewhere.join("keep.js").exists());
+    }
+
+    #[test]
+    fn test_prune_output_cancelled() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.path().join("out");
+        write(out.join("a.js"));
+
+        let shutdown = AtomicBool::new(true);
+        assert!(matches!(
+            prune_output(&out, |_| false, &shutdown),
+            Err(DeployError::Cancelled)
+        ));
+        assert!(out.join("a.js").exists());
+    }
+}
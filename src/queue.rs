@@ -0,0 +1,971 @@
+//! Global file-level work queue.
+//!
+//! Flattens every job's `DeployPlan` into one list of (job, file) units so
+//! Rayon balances individual files across the whole deployment instead of
+//! whole themes. Overrides are already resolved by the plans, so each
+//! destination appears exactly once and the winner doesn't depend on thread
+//! scheduling.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::compress::{refresh_sidecars, write_sidecars};
+use crate::copier::{
+    effective_link_mode, install_files, write_file, CopyOptions, FileFailure, LinkMode,
+    INSTALL_BATCH_SIZE,
+};
+use crate::cssurl::{resolve_urls, UnresolvedUrl};
+use crate::deployer::DeployStats;
+use crate::error::DeployError;
+use crate::minify::{transform_file, MinifySaved, MinifyStats, Transform};
+use crate::plan::{DeployPlan, PlanEntry};
+use crate::retry::{classify, ErrorClass, RetryPolicy, Throttle};
+
+/// Source, destination and link mode of a file to install
+type InstallFile = (PathBuf, PathBuf, LinkMode);
+
+/// One file to materialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkUnit {
+    /// Index of the job (plan) in the queue
+    pub job: u32,
+    /// Index of the entry within the job's plan
+    pub entry: u32,
+}
+
+/// A plan's source resolved for execution
+#[derive(Debug)]
+struct SourceRoot {
+    src: PathBuf,
+    link_mode: LinkMode,
+}
+
+/// Outcome of one job after the queue has run
+#[derive(Debug, Default)]
+pub struct JobOutcome {
+    /// Files materialized (copied or linked)
+    pub files: u64,
+    /// Bytes written
+    pub bytes: u64,
+    /// First error hit by this job; its remaining files were skipped
+    pub error: Option<DeployError>,
+    /// Files that failed with `keep_going`, sorted by destination
+    pub failures: Vec<FileFailure>,
+    /// Some of this job's files were skipped because of shutdown
+    pub cancelled: bool,
+    /// Time from queue start until the job's last file finished
+    pub duration: Duration,
+    /// Bytes saved by minifying this job's files, per kind
+    pub minified: MinifySaved,
+    /// Module notation `url()` references to assets not deployed, sorted
+    pub unresolved: Vec<UnresolvedUrl>,
+}
+
+/// Per-job counters updated from worker threads
+#[derive(Default)]
+struct JobTally {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    remaining: AtomicU64,
+    cancelled: AtomicBool,
+    error: OnceLock<DeployError>,
+    failures: Mutex<Vec<FileFailure>>,
+    finished: OnceLock<Duration>,
+    /// Only the `saved` counters are used
+    minified: MinifyStats,
+    unresolved: Mutex<Vec<UnresolvedUrl>>,
+}
+
+/// Flattened (job, file) units for a set of plans
+#[derive(Debug)]
+pub struct WorkQueue<'a> {
+    plans: &'a [DeployPlan],
+    /// Per job, its sources in priority order
+    roots: Vec<Vec<SourceRoot>>,
+    units: Vec<WorkUnit>,
+    /// Files left in place whose sidecars are checked (`--precompress`)
+    refresh: Vec<WorkUnit>,
+}
+
+impl<'a> WorkQueue<'a> {
+    /// Build the queue, one unit per planned file
+    pub fn build(plans: &'a [DeployPlan], options: &CopyOptions) -> Self {
+        let roots = plans
+            .iter()
+            .map(|plan| {
+                plan.sources()
+                    .iter()
+                    .map(|source| {
+                        let link_mode =
+                            effective_link_mode(source.path(), plan.output(), options.link_mode);
+                        SourceRoot {
+                            src: absolute_for(source.path(), link_mode),
+                            link_mode,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let units = plans
+            .iter()
+            .enumerate()
+            .flat_map(|(job, plan)| {
+                (0..plan.len()).map(move |entry| WorkUnit {
+                    job: job as u32,
+                    entry: entry as u32,
+                })
+            })
+            .collect();
+
+        Self {
+            plans,
+            roots,
+            units,
+            refresh: Vec::new(),
+        }
+    }
+
+    /// Keep only the units `keep` selects (e.g. files changed since the last deploy)
+    pub fn retain(&mut self, keep: impl Fn(&WorkUnit) -> bool) {
+        self.units.retain(|unit| keep(unit));
+    }
+
+    /// Check the sidecars of the files `select` picks without installing
+    /// them, rewriting missing or stale ones. Only with `--precompress`;
+    /// meant for files the incremental deploy leaves alone.
+    pub fn refresh_sidecars(&mut self, select: impl Fn(&WorkUnit) -> bool) {
+        self.refresh = self
+            .plans
+            .iter()
+            .enumerate()
+            .flat_map(|(job, plan)| {
+                (0..plan.len()).map(move |entry| WorkUnit {
+                    job: job as u32,
+                    entry: entry as u32,
+                })
+            })
+            .filter(|unit| select(unit))
+            .collect();
+    }
+
+    /// All units, grouped by job
+    pub fn units(&self) -> &[WorkUnit] {
+        &self.units
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Number of jobs the queue was built for
+    pub fn job_count(&self) -> usize {
+        self.plans.len()
+    }
+
+    fn entry(&self, unit: &WorkUnit) -> &PlanEntry {
+        &self.plans[unit.job as usize].entries()[unit.entry as usize]
+    }
+
+    /// Source file a unit reads from
+    pub fn source_path(&self, unit: &WorkUnit) -> PathBuf {
+        let entry = self.entry(unit);
+        self.roots[unit.job as usize][entry.source]
+            .src
+            .join(&entry.relative)
+    }
+
+    /// Destination a unit writes to
+    pub fn dest_path(&self, unit: &WorkUnit) -> PathBuf {
+        self.plans[unit.job as usize].dest_path(self.entry(unit))
+    }
+
+    /// Write a transformed unit in one go: minified if its transform says
+    /// so, with the module notation `url()`s of CSS rewritten.
+    /// Returns the bytes written; savings are counted once it is.
+    fn install_transformed_unit(
+        &self,
+        unit: &WorkUnit,
+        (src, dst): (&Path, &Path),
+        transform: Transform,
+        options: &CopyOptions,
+        stats: &MinifyStats,
+        tally: &JobTally,
+    ) -> Result<u64, DeployError> {
+        let (mut contents, saved) = transform_file(src, dst, transform, options)?;
+
+        let dest = &self.entry(unit).dest;
+        let mut unresolved = Vec::new();
+        if dest.extension().is_some_and(|ext| ext == "css") {
+            let plan = &self.plans[unit.job as usize];
+            if let Some(resolved) = resolve_urls(&contents, dest, |path| plan.get(path).is_some()) {
+                contents = resolved.css;
+                unresolved = resolved.unresolved;
+            }
+        }
+        let bytes = write_file(src, dst, &contents, options)?;
+
+        stats.record(transform, saved);
+        tally.minified.record(transform, saved);
+        tally
+            .unresolved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(unresolved.into_iter().map(|reference| UnresolvedUrl {
+                css: dest.clone(),
+                reference,
+            }));
+        Ok(bytes)
+    }
+
+    /// Install a batch that includes transformed files: those are
+    /// generated from their sources, the rest goes through `copy`
+    fn install_with_transforms(
+        &self,
+        batch: &[(&WorkUnit, InstallFile)],
+        options: &CopyOptions,
+        stats: &MinifyStats,
+        tallies: &[JobTally],
+        copy: impl FnOnce(&[InstallFile]) -> Vec<Result<Option<u64>, DeployError>>,
+    ) -> Vec<Result<Option<u64>, DeployError>> {
+        let copies: Vec<InstallFile> = batch
+            .iter()
+            .filter(|(unit, _)| self.entry(unit).transform.is_none())
+            .map(|(_, file)| file.clone())
+            .collect();
+        let mut copied = copy(&copies).into_iter();
+
+        batch
+            .iter()
+            .map(|(unit, (src, dst, _))| match self.entry(unit).transform {
+                Some(transform) => self
+                    .install_transformed_unit(
+                        unit,
+                        (src, dst),
+                        transform,
+                        options,
+                        stats,
+                        &tallies[unit.job as usize],
+                    )
+                    .map(Some),
+                None => copied.next().unwrap_or(Ok(Some(0))),
+            })
+            .collect()
+    }
+
+    /// Materialize every unit in parallel.
+    ///
+    /// A failing job skips its remaining files; other jobs keep going.
+    /// With `keep_going`, failed files are recorded and the job carries on.
+    /// `on_job_done` is called once per job when its last file finishes.
+    /// Returns one outcome per job, in job order.
+    pub fn execute(
+        &self,
+        shutdown: &AtomicBool,
+        options: &CopyOptions,
+        stats: &DeployStats,
+        on_job_done: &(dyn Fn(usize) + Sync),
+    ) -> Vec<JobOutcome> {
+        let start = Instant::now();
+        let tallies: Vec<JobTally> = (0..self.job_count()).map(|_| JobTally::default()).collect();
+
+        let refresh: &[WorkUnit] = if options.precompress.is_some() {
+            &self.refresh
+        } else {
+            &[]
+        };
+        for unit in self.units.iter().chain(refresh) {
+            tallies[unit.job as usize]
+                .remaining
+                .fetch_add(1, Ordering::Relaxed);
+        }
+
+        for (job, tally) in tallies.iter().enumerate() {
+            if tally.remaining.load(Ordering::Relaxed) == 0 {
+                on_job_done(job);
+            }
+        }
+
+        let throttle = Throttle::new(rayon::current_num_threads());
+        let policy = RetryPolicy::default();
+
+        let record_error =
+            |tally: &JobTally, src: PathBuf, dst: PathBuf, error: DeployError| match error {
+                DeployError::Cancelled => tally.cancelled.store(true, Ordering::Relaxed),
+                error if options.keep_going => {
+                    stats.errors.0.fetch_add(1, Ordering::Relaxed);
+                    tally
+                        .failures
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(FileFailure { src, dst, error });
+                }
+                e => {
+                    if tally.error.set(e).is_ok() {
+                        stats.errors.0.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            };
+        let finish_unit = |unit: &WorkUnit| {
+            let tally = &tallies[unit.job as usize];
+            if tally.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                let _ = tally.finished.set(start.elapsed());
+                on_job_done(unit.job as usize);
+            }
+        };
+
+        // Batches let the io_uring backend submit many files at once
+        let install = || {
+            self.units.par_chunks(INSTALL_BATCH_SIZE).for_each(|chunk| {
+                let runnable: Vec<&WorkUnit> = chunk
+                    .iter()
+                    .filter(|unit| {
+                        let tally = &tallies[unit.job as usize];
+                        if shutdown.load(Ordering::Relaxed) {
+                            tally.cancelled.store(true, Ordering::Relaxed);
+                            return false;
+                        }
+                        tally.error.get().is_none()
+                    })
+                    .collect();
+
+                let files: Vec<InstallFile> = runnable
+                    .iter()
+                    .map(|unit| {
+                        let root = &self.roots[unit.job as usize][self.entry(unit).source];
+                        (self.source_path(unit), self.dest_path(unit), root.link_mode)
+                    })
+                    .collect();
+                let results = {
+                    let _permit = throttle.acquire();
+                    let copy =
+                        |files: &[InstallFile]| install_files(files, options, &stats.methods);
+                    if runnable
+                        .iter()
+                        .any(|unit| self.entry(unit).transform.is_some())
+                    {
+                        let batch: Vec<(&WorkUnit, InstallFile)> = runnable
+                            .iter()
+                            .copied()
+                            .zip(files.iter().cloned())
+                            .collect();
+                        let install = |batch: &[(&WorkUnit, InstallFile)]| {
+                            self.install_with_transforms(
+                                batch,
+                                options,
+                                &stats.minified,
+                                &tallies,
+                                copy,
+                            )
+                        };
+                        install_with_retry(&batch, install, stats, &throttle, &policy, shutdown)
+                    } else {
+                        install_with_retry(&files, copy, stats, &throttle, &policy, shutdown)
+                    }
+                };
+
+                for ((unit, (src, dst, _)), result) in runnable.iter().zip(files).zip(results) {
+                    let tally = &tallies[unit.job as usize];
+                    // Compress while the file is still in the page cache. A file
+                    // left in place only gets its missing or stale sidecars.
+                    let result = result.and_then(|bytes| match (&options.precompress, bytes) {
+                        (Some(precompress), Some(_)) => {
+                            write_sidecars(&dst, precompress, &options.attrs, &stats.sidecars)
+                                .map(|()| bytes)
+                        }
+                        (Some(precompress), None) => {
+                            refresh_sidecars(&dst, precompress, &options.attrs, &stats.sidecars)
+                                .map(|()| bytes)
+                        }
+                        (None, _) => Ok(bytes),
+                    });
+                    match result {
+                        Ok(Some(bytes)) => {
+                            tally.files.fetch_add(1, Ordering::Relaxed);
+                            tally.bytes.fetch_add(bytes, Ordering::Relaxed);
+                            stats.files_copied.0.fetch_add(1, Ordering::Relaxed);
+                            stats.bytes_copied.0.fetch_add(bytes, Ordering::Relaxed);
+                        }
+                        // Left in place by skip_identical: counted there
+                        Ok(None) => {}
+                        Err(error) => record_error(tally, src, dst, error),
+                    }
+                }
+
+                chunk.iter().for_each(finish_unit);
+            })
+        };
+
+        let refresh_chunk = |chunk: &[WorkUnit]| {
+            for unit in chunk {
+                let tally = &tallies[unit.job as usize];
+                if shutdown.load(Ordering::Relaxed) {
+                    tally.cancelled.store(true, Ordering::Relaxed);
+                } else if let (Some(precompress), None) = (&options.precompress, tally.error.get())
+                {
+                    let dst = self.dest_path(unit);
+                    if let Err(error) =
+                        refresh_sidecars(&dst, precompress, &options.attrs, &stats.sidecars)
+                    {
+                        record_error(tally, self.source_path(unit), dst, error);
+                    }
+                }
+                finish_unit(unit);
+            }
+        };
+        rayon::join(install, || {
+            refresh
+                .par_chunks(INSTALL_BATCH_SIZE)
+                .for_each(refresh_chunk)
+        });
+
+        tallies
+            .into_iter()
+            .map(|tally| {
+                let mut failures = tally
+                    .failures
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner);
+                failures.sort_unstable_by(|a, b| a.dst.cmp(&b.dst));
+                let mut unresolved = tally
+                    .unresolved
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner);
+                unresolved.sort_unstable();
+                JobOutcome {
+                    files: tally.files.load(Ordering::Relaxed),
+                    bytes: tally.bytes.load(Ordering::Relaxed),
+                    error: tally.error.into_inner(),
+                    failures,
+                    cancelled: tally.cancelled.load(Ordering::Relaxed),
+                    duration: tally.finished.into_inner().unwrap_or_default(),
+                    minified: tally.minified.saved(),
+                    unresolved,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Install a batch with `install`, retrying files that failed with a
+/// transient error. Running out of file descriptors also lowers the
+/// throttle's limit.
+fn install_with_retry<T: Clone>(
+    files: &[T],
+    install: impl Fn(&[T]) -> Vec<Result<Option<u64>, DeployError>>,
+    stats: &DeployStats,
+    throttle: &Throttle,
+    policy: &RetryPolicy,
+    shutdown: &AtomicBool,
+) -> Vec<Result<Option<u64>, DeployError>> {
+    let mut results = install(files);
+
+    for retry in 0..policy.max_retries {
+        let mut fd_exhausted = false;
+        let failed: Vec<usize> = (0..results.len())
+            .filter(|&i| match &results[i] {
+                Err(e) => match classify(e) {
+                    ErrorClass::FdExhausted => {
+                        fd_exhausted = true;
+                        true
+                    }
+                    ErrorClass::Transient => true,
+                    ErrorClass::Permanent => false,
+                },
+                Ok(_) => false,
+            })
+            .collect();
+        if failed.is_empty() || shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+
+        if fd_exhausted {
+            throttle.back_off();
+            stats.fd_backoffs.0.fetch_add(1, Ordering::Relaxed);
+        }
+        std::thread::sleep(policy.delay(retry));
+
+        let again: Vec<T> = failed.iter().map(|&i| files[i].clone()).collect();
+        stats
+            .retries
+            .0
+            .fetch_add(again.len() as u64, Ordering::Relaxed);
+        for (i, result) in failed.into_iter().zip(install(&again)) {
+            results[i] = result;
+        }
+    }
+
+    let fd_exhausted = results
+        .iter()
+        .any(|r| matches!(r, Err(e) if classify(e) == ErrorClass::FdExhausted));
+    if !fd_exhausted {
+        throttle.record_success();
+    }
+    results
+}
+
+/// Symlinks must point at absolute source paths
+fn absolute_for(path: &Path, link_mode: LinkMode) -> PathBuf {
+    if link_mode == LinkMode::Symlink && !path.is_absolute() {
+        if let Ok(cwd) = std::env::current_dir() {
+            return cwd.join(path);
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::FileSource;
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    fn web(path: PathBuf) -> FileSource {
+        FileSource::ThemeWeb {
+            theme: "Test/theme".to_string(),
+            path,
+        }
+    }
+
+    fn dev_options() -> CopyOptions {
+        CopyOptions {
+            include_dev: true,
+            ..CopyOptions::default()
+        }
+    }
+
+    fn plan(output: PathBuf, sources: Vec<FileSource>) -> DeployPlan {
+        DeployPlan::build(output, sources, true)
+    }
+
+    // ==================== WorkQueue::build tests ====================
+
+    #[test]
+    fn test_build_uses_plan_winner() {
+        let temp = TempDir::new().unwrap();
+        let high = temp.path().join("high");
+        let low = temp.path().join("low");
+        fs::create_dir_all(&high).unwrap();
+        fs::create_dir_all(&low).unwrap();
+        fs::write(high.join("app.js"), "high").unwrap();
+        fs::write(low.join("app.js"), "low").unwrap();
+        fs::write(low.join("lib.js"), "lib").unwrap();
+
+        let plans = vec![plan(
+            temp.path().join("out"),
+            vec![web(high.clone()), web(low.clone())],
+        )];
+        let queue = WorkQueue::build(&plans, &dev_options());
+
+        assert_eq!(queue.len(), 2);
+        let app = queue
+            .units()
+            .iter()
+            .find(|u| queue.dest_path(u).ends_with("app.js"))
+            .unwrap();
+        assert_eq!(queue.source_path(app), high.join("app.js"));
+        assert_eq!(queue.dest_path(app), temp.path().join("out/app.js"));
+    }
+
+    #[test]
+    fn test_build_flattens_jobs() {
+        let temp = TempDir::new().unwrap();
+        let shared = temp.path().join("lib");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("a.js"), "a").unwrap();
+        fs::write(shared.join("b.js"), "b").unwrap();
+
+        let plans: Vec<DeployPlan> = ["en_US", "nl_NL", "de_DE"]
+            .iter()
+            .map(|locale| {
+                plan(
+                    temp.path().join(locale),
+                    vec![FileSource::Library {
+                        path: shared.clone(),
+                    }],
+                )
+            })
+            .collect();
+        let queue = WorkQueue::build(&plans, &dev_options());
+
+        assert_eq!(queue.job_count(), 3);
+        assert_eq!(queue.len(), 6);
+        assert_eq!(queue.units()[5], WorkUnit { job: 2, entry: 1 });
+    }
+
+    // ==================== WorkQueue::execute tests ====================
+
+    #[test]
+    fn test_install_with_retry_skips_permanent_errors() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("a.js");
+        fs::write(&src, "a").unwrap();
+
+        let files = vec![
+            (src, temp.path().join("out/a.js"), LinkMode::Copy),
+            (
+                temp.path().join("missing.js"),
+                temp.path().join("out/missing.js"),
+                LinkMode::Copy,
+            ),
+        ];
+        let stats = DeployStats::new();
+        let throttle = Throttle::new(4);
+        let options = dev_options();
+        let results = install_with_retry(
+            &files,
+            |files| install_files(files, &options, &stats.methods),
+            &stats,
+            &throttle,
+            &RetryPolicy::default(),
+            &AtomicBool::new(false),
+        );
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert_eq!(stats.retries.0.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.fd_backoffs.0.load(Ordering::Relaxed), 0);
+        assert_eq!(throttle.limit(), 4);
+    }
+
+    #[test]
+    fn test_install_with_retry_retries_transformed_files() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("app.js"), "var  answer = 42; // the answer\n").unwrap();
+
+        let options = CopyOptions {
+            minify: Some(crate::minify::Minify {
+                js: true,
+                ..crate::minify::Minify::default()
+            }),
+            ..dev_options()
+        };
+        let mut plans = vec![plan(temp.path().join("out"), vec![web(src)])];
+        let minify = options.minify.as_ref().unwrap();
+        plans[0].derive(|entry| minify.derive(&entry.dest, "frontend/Test/theme/en_US"));
+        let queue = WorkQueue::build(&plans, &options);
+        let batch: Vec<(&WorkUnit, InstallFile)> = queue
+            .units()
+            .iter()
+            .map(|unit| {
+                let file = (
+                    queue.source_path(unit),
+                    queue.dest_path(unit),
+                    LinkMode::Copy,
+                );
+                (unit, file)
+            })
+            .collect();
+
+        let stats = DeployStats::new();
+        let tallies = [JobTally::default()];
+        let attempts = AtomicUsize::new(0);
+        let results = install_with_retry(
+            &batch,
+            |batch| {
+                // The first attempt is interrupted
+                if attempts.fetch_add(1, Ordering::Relaxed) == 0 {
+                    return batch
+                        .iter()
+                        .map(|(_, (src, dst, _))| {
+                            Err(DeployError::CopyFailed {
+                                src: src.clone(),
+                                dst: dst.clone(),
+                                source: std::io::ErrorKind::Interrupted.into(),
+                            })
+                        })
+                        .collect();
+                }
+                queue.install_with_transforms(
+                    batch,
+                    &options,
+                    &stats.minified,
+                    &tallies,
+                    |copies| install_files(copies, &options, &stats.methods),
+                )
+            },
+            &stats,
+            &Throttle::new(4),
+            &RetryPolicy {
+                base_delay: Duration::ZERO,
+                ..RetryPolicy::default()
+            },
+            &AtomicBool::new(false),
+        );
+
+        assert!(results.iter().all(|r| matches!(r, Ok(Some(_)))));
+        assert_eq!(stats.retries.0.load(Ordering::Relaxed), 2);
+        assert!(temp.path().join("out/app.min.js").is_file());
+        assert_eq!(stats.minified.js.files.load(Ordering::Relaxed), 1);
+        assert!(tallies[0].minified.saved().js > 0);
+    }
+
+    #[test]
+    fn test_execute_per_job_accounting() {
+        let temp = TempDir::new().unwrap();
+        let big = temp.path().join("big");
+        let small = temp.path().join("small");
+        fs::create_dir_all(&big).unwrap();
+        fs::create_dir_all(&small).unwrap();
+        for i in 0..10 {
+            fs::write(big.join(format!("{i}.js")), "12345").unwrap();
+        }
+        fs::write(small.join("one.js"), "1").unwrap();
+
+        let plans = vec![
+            plan(temp.path().join("out_big"), vec![web(big)]),
+            plan(temp.path().join("out_small"), vec![web(small)]),
+            plan(temp.path().join("out_empty"), Vec::new()),
+        ];
+        let queue = WorkQueue::build(&plans, &dev_options());
+
+        let shutdown = AtomicBool::new(false);
+        let stats = DeployStats::new();
+        let done = AtomicUsize::new(0);
+        let outcomes = queue.execute(&shutdown, &dev_options(), &stats, &|_| {
+            done.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(outcomes[0].files, 10);
+        assert_eq!(outcomes[0].bytes, 50);
+        assert_eq!(outcomes[1].files, 1);
+        assert_eq!(outcomes[2].files, 0);
+        assert!(outcomes.iter().all(|o| o.error.is_none() && !o.cancelled));
+        assert_eq!(done.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.files_copied.0.load(Ordering::Relaxed), 11);
+    }
+
+    #[test]
+    fn test_execute_overwrites_previous_deploy() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let out = temp.path().join("out");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&out).unwrap();
+        fs::write(src.join("app.js"), "new").unwrap();
+        fs::write(out.join("app.js"), "stale").unwrap();
+
+        let plans = vec![plan(out.clone(), vec![web(src)])];
+        let queue = WorkQueue::build(&plans, &dev_options());
+        let outcomes = queue.execute(
+            &AtomicBool::new(false),
+            &dev_options(),
+            &DeployStats::new(),
+            &|_| {},
+        );
+
+        assert_eq!(outcomes[0].files, 1);
+        assert_eq!(fs::read_to_string(out.join("app.js")).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_resolves_css_urls_and_keeps_other_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let out = temp.path().join("out");
+        fs::create_dir_all(src.join("css")).unwrap();
+        let with_refs = "a{background:url('Vendor_Module::img/a.png')}";
+        fs::write(src.join("css/styles.css"), with_refs).unwrap();
+        fs::write(src.join("css/plain.css"), "a::before{color:red}").unwrap();
+
+        let mut plans = vec![plan(out.clone(), vec![web(src.clone())])];
+        crate::cssurl::mark_references(&mut plans[0], |_| true);
+        let options = CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..dev_options()
+        };
+        let queue = WorkQueue::build(&plans, &options);
+        let outcomes = queue.execute(
+            &AtomicBool::new(false),
+            &options,
+            &DeployStats::new(),
+            &|_| {},
+        );
+
+        assert_eq!(
+            fs::read_to_string(out.join("css/styles.css")).unwrap(),
+            "a{background:url('../Vendor_Module/img/a.png')}"
+        );
+        assert_eq!(
+            fs::read_to_string(src.join("css/styles.css")).unwrap(),
+            with_refs
+        );
+        assert_eq!(
+            outcomes[0].unresolved,
+            vec![UnresolvedUrl {
+                css: PathBuf::from("css/styles.css"),
+                reference: "Vendor_Module::img/a.png".to_string(),
+            }]
+        );
+        // CSS without references stays a hard link
+        assert_eq!(
+            fs::metadata(out.join("css/plain.css")).unwrap().ino(),
+            fs::metadata(src.join("css/plain.css")).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn test_execute_skip_identical_not_counted_as_copied() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let out = temp.path().join("out");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&out).unwrap();
+        fs::write(src.join("same.js"), "same").unwrap();
+        fs::write(src.join("new.js"), "new").unwrap();
+        fs::write(out.join("same.js"), "same").unwrap();
+
+        let options = CopyOptions {
+            skip_identical: true,
+            ..dev_options()
+        };
+        let plans = vec![plan(out.clone(), vec![web(src)])];
+        let queue = WorkQueue::build(&plans, &options);
+        let stats = DeployStats::new();
+        let outcomes = queue.execute(&AtomicBool::new(false), &options, &stats, &|_| {});
+
+        assert_eq!(outcomes[0].files, 1);
+        assert_eq!(outcomes[0].bytes, 3);
+        assert_eq!(stats.files_copied.0.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.methods.identical.load(Ordering::Relaxed), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_skip_identical_keeps_sidecars() {
+        use crate::compress::{sidecar_path, Codec, Precompress};
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        let out = temp.path().join("out");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("app.js"), "var answer = 42;\n".repeat(100)).unwrap();
+
+        let options = CopyOptions {
+            skip_identical: true,
+            precompress: Some(Precompress {
+                codecs: vec![Codec::Gzip],
+                extensions: vec!["js".to_string()],
+                min_size: 0,
+            }),
+            ..dev_options()
+        };
+        let plans = vec![plan(out.clone(), vec![web(src)])];
+        let sidecar = sidecar_path(&out.join("app.js"), Codec::Gzip);
+
+        WorkQueue::build(&plans, &options).execute(
+            &AtomicBool::new(false),
+            &options,
+            &DeployStats::new(),
+            &|_| {},
+        );
+        let before = fs::metadata(&sidecar).unwrap();
+
+        let stats = DeployStats::new();
+        WorkQueue::build(&plans, &options).execute(
+            &AtomicBool::new(false),
+            &options,
+            &stats,
+            &|_| {},
+        );
+        let after = fs::metadata(&sidecar).unwrap();
+
+        assert_eq!(stats.methods.identical.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.sidecars.written.load(Ordering::Relaxed), 0);
+        assert_eq!(after.ino(), before.ino());
+        assert_eq!(after.modified().unwrap(), before.modified().unwrap());
+    }
+
+    #[test]
+    fn test_execute_cancelled() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("app.js"), "app").unwrap();
+
+        let plans = vec![plan(temp.path().join("out"), vec![web(src)])];
+        let queue = WorkQueue::build(&plans, &dev_options());
+
+        let shutdown = AtomicBool::new(true);
+        let outcomes = queue.execute(&shutdown, &dev_options(), &DeployStats::new(), &|_| {});
+
+        assert!(outcomes[0].cancelled);
+        assert_eq!(outcomes[0].files, 0);
+        assert!(!temp.path().join("out").join("app.js").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_failure_isolated_to_job() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("app.js"), "app").unwrap();
+
+        // A file where the output directory should be makes that job fail
+        let blocked = temp.path().join("blocked");
+        fs::write(&blocked, "not a directory").unwrap();
+
+        let plans = vec![
+            plan(blocked, vec![web(src.clone())]),
+            plan(temp.path().join("ok"), vec![web(src)]),
+        ];
+        let queue = WorkQueue::build(&plans, &dev_options());
+
+        let stats = DeployStats::new();
+        let outcomes = queue.execute(&AtomicBool::new(false), &dev_options(), &stats, &|_| {});
+
+        assert!(outcomes[0].error.is_some());
+        assert!(outcomes[1].error.is_none());
+        assert_eq!(outcomes[1].files, 1);
+        assert_eq!(stats.errors.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_execute_keep_going_collects_failures() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        for name in ["a.js", "b.js", "c.js"] {
+            fs::write(src.join(name), name).unwrap();
+        }
+
+        let plans = vec![plan(temp.path().join("out"), vec![web(src.clone())])];
+        let options = CopyOptions {
+            keep_going: true,
+            ..dev_options()
+        };
+        let queue = WorkQueue::build(&plans, &options);
+        // Vanishes between planning and copying
+        fs::remove_file(src.join("b.js")).unwrap();
+
+        let stats = DeployStats::new();
+        let outcomes = queue.execute(&AtomicBool::new(false), &options, &stats, &|_| {});
+
+        assert!(outcomes[0].error.is_none());
+        assert_eq!(outcomes[0].files, 2);
+        assert_eq!(outcomes[0].failures.len(), 1);
+        let failure = &outcomes[0].failures[0];
+        assert_eq!(failure.src, src.join("b.js"));
+        assert_eq!(failure.dst, temp.path().join("out/b.js"));
+        assert!(temp.path().join("out/c.js").exists());
+        assert_eq!(stats.errors.0.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,126 @@
+//! Copy rate limits for deploys on servers that are serving traffic.
+//!
+//! `--io-limit` caps bytes per second and `--files-per-sec` caps files per
+//! second. Every file the copier installs reserves its share of a shared
+//! schedule before it is opened and sleeps until its slot comes up, so the
+//! limits hold across all workers. An idle limiter lets a short burst
+//! through rather than banking unlimited credit.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Credit an idle limiter may accumulate
+const MAX_BURST: Duration = Duration::from_millis(50);
+
+/// Shared bytes/sec and files/sec limit
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: Option<u64>,
+    files_per_sec: Option<u64>,
+    /// When the next reservation may start
+    next: Mutex<Option<Instant>>,
+    /// Total time workers spent waiting, in nanoseconds
+    waited_ns: AtomicU64,
+}
+
+impl RateLimiter {
+    /// A limiter for the given caps; `None` when neither is set
+    pub fn new(bytes_per_sec: Option<u64>, files_per_sec: Option<u64>) -> Option<Self> {
+        let bytes_per_sec = bytes_per_sec.filter(|&n| n > 0);
+        let files_per_sec = files_per_sec.filter(|&n| n > 0);
+        if bytes_per_sec.is_none() && files_per_sec.is_none() {
+            return None;
+        }
+        Some(Self {
+            bytes_per_sec,
+            files_per_sec,
+            next: Mutex::new(None),
+            waited_ns: AtomicU64::new(0),
+        })
+    }
+
+    /// Time `files` files totalling `bytes` bytes take at the limit
+    fn cost(&self, files: u64, bytes: u64) -> Duration {
+        let per = |amount: u64, rate: Option<u64>| {
+            rate.map_or(Duration::ZERO, |rate| {
+                Duration::from_secs_f64(amount as f64 / rate as f64)
+            })
+        };
+        per(bytes, self.bytes_per_sec).max(per(files, self.files_per_sec))
+    }
+
+    /// Reserve a slot for `files` files totalling `bytes` bytes and return
+    /// how long to wait until it starts
+    fn reserve(&self, files: u64, bytes: u64, now: Instant) -> Duration {
+        let cost = self.cost(files, bytes);
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let earliest = now.checked_sub(MAX_BURST).unwrap_or(now);
+        let start = next.map_or(earliest, |next| next.max(earliest));
+        *next = Some(start + cost);
+
+        start.saturating_duration_since(now)
+    }
+
+    /// Block until `files` files totalling `bytes` bytes may be written
+    pub fn acquire(&self, files: u64, bytes: u64) {
+        let wait = self.reserve(files, bytes, Instant::now());
+        if !wait.is_zero() {
+            self.waited_ns
+                .fetch_add(wait.as_nanos() as u64, Ordering::Relaxed);
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Total time workers have waited on the limit
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited_ns.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ==================== RateLimiter tests ====================
+
+    #[test]
+    fn test_new_without_limits() {
+        assert!(RateLimiter::new(None, None).is_none());
+        assert!(RateLimiter::new(Some(0), Some(0)).is_none());
+        assert!(RateLimiter::new(Some(1), None).is_some());
+    }
+
+    #[test]
+    fn test_cost_uses_tighter_limit() {
+        let limiter = RateLimiter::new(Some(1_000_000), Some(100)).unwrap();
+        // 10 MB at 1 MB/s beats 1 file at 100 files/s
+        assert_eq!(limiter.cost(1, 10_000_000), Duration::from_secs(10));
+        // 50 tiny files at 100 files/s
+        assert_eq!(limiter.cost(50, 50), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_reserve_schedules_back_to_back() {
+        let limiter = RateLimiter::new(None, Some(10)).unwrap();
+        let now = Instant::now();
+
+        // Each file costs 100ms; the first starts right away (burst credit)
+        assert_eq!(limiter.reserve(1, 0, now), Duration::ZERO);
+        assert_eq!(limiter.reserve(1, 0, now), Duration::from_millis(50));
+        assert_eq!(limiter.reserve(1, 0, now), Duration::from_millis(150));
+
+        // After a long idle period only the burst is credited
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1, 0, later), Duration::ZERO);
+        assert_eq!(limiter.reserve(1, 0, later), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_acquire_small_amounts_do_not_wait() {
+        let limiter = RateLimiter::new(Some(1_000_000_000), None).unwrap();
+        limiter.acquire(1, 1000);
+        assert_eq!(limiter.waited(), Duration::ZERO);
+    }
+}
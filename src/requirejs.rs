@@ -0,0 +1,333 @@
+//! Merged `requirejs-config.js` generation (`--requirejs-config`).
+//!
+//! Magento builds `pub/static/{area}/{Vendor}/{theme}/{locale}/requirejs-config.js`
+//! from every `requirejs-config.js` outside `web/`: the modules'
+//! `view/base` and `view/{area}` ones in module load order (app/etc/config.php),
+//! then, from the root ancestor down to the theme itself, each theme's
+//! `{Module_Name}/requirejs-config.js` files and its own one. Each file is
+//! wrapped in a function calling `require.config(config)`, and the whole in
+//! one taking `require`, as `Magento\Framework\RequireJs\Config` does.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::copier::{write_file, CopyOptions};
+use crate::error::DeployError;
+use crate::minify::minify_js;
+use crate::scanner::discover_modules;
+use crate::theme::Theme;
+
+/// Name of the per-module, per-theme and merged file
+pub const CONFIG_FILE: &str = "requirejs-config.js";
+
+/// Merged file written alongside with `--minify-js`
+pub const MIN_CONFIG_FILE: &str = "requirejs-config.min.js";
+
+/// Modules in load order with whether they are enabled, as listed in
+/// app/etc/config.php. Empty if it can't be read.
+pub fn module_load_order(magento_root: &Path) -> Vec<(String, bool)> {
+    fs::read_to_string(magento_root.join("app/etc/config.php"))
+        .map(|php| parse_module_list(&php))
+        .unwrap_or_default()
+}
+
+/// Entries of a config.php's `'modules'` array, in order
+pub fn parse_module_list(php: &str) -> Vec<(String, bool)> {
+    let Some(start) = php.find("'modules'") else {
+        return Vec::new();
+    };
+    let list = &php[start..];
+    // The array holds no nested ones: it ends at the first closing bracket
+    let Some(open) = list.find(['[', '(']) else {
+        return Vec::new();
+    };
+    let end = list[open..]
+        .find([']', ')'])
+        .map_or(list.len(), |len| open + len);
+    let Ok(entry) = Regex::new(r#"['"]([A-Za-z0-9]+_[A-Za-z0-9_]+)['"]\s*=>\s*([01])"#) else {
+        return Vec::new();
+    };
+    entry
+        .captures_iter(&list[open..end])
+        .map(|caps| (caps[1].to_string(), &caps[2] == "1"))
+        .collect()
+}
+
+/// Module `requirejs-config.js` files of `area`, in load order. Modules
+/// config.php doesn't list come last, by name; disabled ones are left out.
+pub fn module_config_files(magento_root: &Path, area: &str) -> Vec<PathBuf> {
+    let load_order = module_load_order(magento_root);
+    let position: HashMap<&str, (usize, bool)> = load_order
+        .iter()
+        .enumerate()
+        .map(|(i, (name, enabled))| (name.as_str(), (i, *enabled)))
+        .collect();
+    let mut modules = discover_modules(magento_root);
+    modules.retain(|(name, _)| {
+        position
+            .get(name.as_str())
+            .map_or(true, |&(_, enabled)| enabled)
+    });
+    // Stable: unlisted modules keep their name order
+    modules.sort_by_key(|(name, _)| position.get(name.as_str()).map_or(usize::MAX, |&(i, _)| i));
+
+    modules
+        .iter()
+        .flat_map(|(_, package)| {
+            ["base", area].into_iter().flat_map(move |view_area| {
+                [package.join("view"), package.join("src/view")]
+                    .into_iter()
+                    .map(move |view| view.join(view_area).join(CONFIG_FILE))
+            })
+        })
+        .filter(|file| file.is_file())
+        .collect()
+}
+
+/// Theme `requirejs-config.js` files, from the root ancestor of
+/// `parent_chain` (nearest parent first) down to `theme`
+pub fn theme_config_files(theme: &Theme, parent_chain: &[&Theme]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for current in parent_chain.iter().rev().copied().chain([theme]) {
+        let mut modular: Vec<PathBuf> = fs::read_dir(&current.path)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().contains('_'))
+            .map(|entry| entry.path().join(CONFIG_FILE))
+            .filter(|file| file.is_file())
+            .collect();
+        modular.sort();
+        files.extend(modular);
+
+        let own = current.path.join(CONFIG_FILE);
+        if own.is_file() {
+            files.push(own);
+        }
+    }
+    files
+}
+
+/// Wrap the contents of `configs` the way Magento merges them
+pub fn merge(configs: &[String]) -> String {
+    let mut merged = String::from("(function(require){\n");
+    for config in configs {
+        merged.push_str("(function() {\n");
+        merged.push_str(config);
+        merged.push_str("\nrequire.config(config);\n})();\n");
+    }
+    merged.push_str("\n\n\n})(require);");
+    merged
+}
+
+/// Write the merged `requirejs-config.js` of `files` into `output` (and its
+/// minified `.min.js` with `--minify-js`). Returns the bytes written.
+pub fn write_config(
+    files: &[PathBuf],
+    output: &Path,
+    options: &CopyOptions,
+) -> Result<u64, DeployError> {
+    let configs = files
+        .iter()
+        .map(|file| {
+            fs::read_to_string(file).map_err(|source| DeployError::CopyFailed {
+                src: file.clone(),
+                dst: output.join(CONFIG_FILE),
+                source,
+            })
+        })
+        .collect::<Result<Vec<String>, DeployError>>()?;
+    let merged = merge(&configs);
+    // `--preserve-mtime` takes the newest source's
+    let newest = files
+        .iter()
+        .max_by_key(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .map_or(output, PathBuf::as_path);
+    let mut bytes = write_file(
+        newest,
+        &output.join(CONFIG_FILE),
+        merged.as_bytes(),
+        options,
+    )?;
+
+    if options.minify.as_ref().is_some_and(|minify| minify.js) {
+        let minified = minify_js(merged.as_bytes()).unwrap_or_else(|| merged.clone().into_bytes());
+        bytes += write_file(newest, &output.join(MIN_CONFIG_FILE), &minified, options)?;
+    }
+    Ok(bytes)
+}
+
+/// Whether `relative` (to an output) is a file [`write_config`] writes
+pub fn is_generated(relative: &Path, options: &CopyOptions) -> bool {
+    relative == Path::new(CONFIG_FILE)
+        || (relative == Path::new(MIN_CONFIG_FILE)
+            && options.minify.as_ref().is_some_and(|minify| minify.js))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minify::Minify;
+    use crate::theme::{Area, ThemeCode, ThemeType};
+    use tempfile::TempDir;
+
+    fn write(path: PathBuf, contents: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn module(root: &Path, package: &str, name: &str) -> PathBuf {
+        let dir = root.join("vendor").join(package);
+        write(
+            dir.join("etc/module.xml"),
+            &format!(r#"<config><module name="{name}"/></config>"#),
+        );
+        dir
+    }
+
+    fn theme(root: &Path, name: &str, parent: Option<&str>) -> Theme {
+        Theme {
+            vendor: "Vendor".to_string(),
+            name: name.to_string(),
+            area: Area::Frontend,
+            path: root.join("app/design/frontend/Vendor").join(name),
+            parent: parent.map(|p| ThemeCode::new("Vendor", p)),
+            theme_type: ThemeType::Hyva,
+        }
+    }
+
+    // ==================== parse_module_list tests ====================
+
+    #[test]
+    fn test_parse_module_list() {
+        let php = "<?php\nreturn [\n    'modules' => [\n        'Magento_Store' => 1,\n        'Magento_Theme' => 1,\n        'Magento_Swatches' => 0,\n        \"Hyva_Theme\" => 1\n    ],\n    'system' => ['Foo_Bar' => 1]\n];\n";
+        assert_eq!(
+            parse_module_list(php),
+            vec![
+                ("Magento_Store".to_string(), true),
+                ("Magento_Theme".to_string(), true),
+                ("Magento_Swatches".to_string(), false),
+                ("Hyva_Theme".to_string(), true),
+            ]
+        );
+        assert!(parse_module_list("<?php return [];").is_empty());
+    }
+
+    // ==================== config file order tests ====================
+
+    #[test]
+    fn test_module_config_files_load_order() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        let theme_module = module(root, "magento/module-theme", "Magento_Theme");
+        let store = module(root, "magento/module-store", "Magento_Store");
+        let swatches = module(root, "magento/module-swatches", "Magento_Swatches");
+        let hyva = module(root, "hyva/compat", "Hyva_Compat");
+        let files = [
+            write(theme_module.join("view/frontend/requirejs-config.js"), "t"),
+            write(store.join("view/frontend/requirejs-config.js"), "s"),
+            write(store.join("view/base/requirejs-config.js"), "sb"),
+            write(store.join("view/adminhtml/requirejs-config.js"), "sa"),
+            write(swatches.join("view/frontend/requirejs-config.js"), "w"),
+            write(hyva.join("src/view/frontend/requirejs-config.js"), "h"),
+        ];
+
+        // No config.php: by name
+        assert_eq!(
+            module_config_files(root, "frontend"),
+            vec![
+                files[5].clone(),
+                files[2].clone(),
+                files[1].clone(),
+                files[4].clone(),
+                files[0].clone()
+            ]
+        );
+
+        write(
+            root.join("app/etc/config.php"),
+            "<?php return ['modules' => ['Magento_Theme' => 1, 'Magento_Store' => 1, 'Magento_Swatches' => 0]];",
+        );
+        assert_eq!(
+            module_config_files(root, "frontend"),
+            // Hyva_Compat isn't listed: last
+            vec![
+                files[0].clone(),
+                files[2].clone(),
+                files[1].clone(),
+                files[5].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_theme_config_files_fallback_order() {
+        let temp = TempDir::new().unwrap();
+        let parent = theme(temp.path(), "parent", None);
+        let child = theme(temp.path(), "child", Some("parent"));
+        let files = [
+            write(parent.path.join("requirejs-config.js"), "p"),
+            write(
+                parent.path.join("Magento_Catalog/requirejs-config.js"),
+                "pc",
+            ),
+            write(child.path.join("requirejs-config.js"), "c"),
+            write(child.path.join("web/requirejs-config.js"), "web"),
+        ];
+
+        assert_eq!(
+            theme_config_files(&child, &[&parent]),
+            vec![files[1].clone(), files[0].clone(), files[2].clone()]
+        );
+    }
+
+    // ==================== merge tests ====================
+
+    #[test]
+    fn test_merge_wraps_like_magento() {
+        assert_eq!(
+            merge(&["var config = {};".to_string(), "var config = {map: {}};\n".to_string()]),
+            "(function(require){\n(function() {\nvar config = {};\nrequire.config(config);\n})();\n(function() {\nvar config = {map: {}};\n\nrequire.config(config);\n})();\n\n\n\n})(require);"
+        );
+        assert_eq!(merge(&[]), "(function(require){\n\n\n\n})(require);");
+    }
+
+    #[test]
+    fn test_write_config_with_minified_copy() {
+        let temp = TempDir::new().unwrap();
+        let src = write(
+            temp.path().join("a.js"),
+            "var config = {\n    deps: ['a']\n};\n",
+        );
+        let output = temp.path().join("out");
+
+        write_config(std::slice::from_ref(&src), &output, &CopyOptions::default()).unwrap();
+        assert!(fs::read_to_string(output.join(CONFIG_FILE))
+            .unwrap()
+            .contains("var config = {\n    deps: ['a']\n};\n\nrequire.config(config);"));
+        assert!(!output.join(MIN_CONFIG_FILE).exists());
+
+        let options = CopyOptions {
+            minify: Some(Minify {
+                js: true,
+                ..Minify::default()
+            }),
+            ..CopyOptions::default()
+        };
+        write_config(&[src], &output, &options).unwrap();
+        assert_eq!(
+            fs::read_to_string(output.join(MIN_CONFIG_FILE)).unwrap(),
+            "(function(require){(function(){var config={deps:['a']};require.config(config);})();})(require);"
+        );
+        assert!(is_generated(Path::new(MIN_CONFIG_FILE), &options));
+        assert!(!is_generated(
+            Path::new(MIN_CONFIG_FILE),
+            &CopyOptions::default()
+        ));
+        assert!(!is_generated(Path::new("js/requirejs-config.js"), &options));
+    }
+}
@@ -0,0 +1,250 @@
+//! Retries for transient I/O errors and back-off on file descriptor exhaustion.
+//!
+//! Files that fail with `EINTR`, `EAGAIN` or `ESTALE` (NFS) are retried a
+//! few times with exponential back-off. Running out of file descriptors
+//! (`EMFILE`/`ENFILE`) is retried the same way, and additionally halves the
+//! number of batches the work queue keeps in flight; the limit creeps back
+//! up as batches succeed again.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::error::DeployError;
+
+/// How an install error should be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Out of file descriptors: retry with less concurrency
+    FdExhausted,
+    /// Worth retrying as is
+    Transient,
+    /// Retrying won't help
+    Permanent,
+}
+
+/// Classify an error returned for a single file
+pub fn classify(error: &DeployError) -> ErrorClass {
+    let Some(e) = error.io_error() else {
+        return ErrorClass::Permanent;
+    };
+
+    #[cfg(target_os = "linux")]
+    match e.raw_os_error() {
+        Some(libc::EMFILE) | Some(libc::ENFILE) => return ErrorClass::FdExhausted,
+        Some(libc::EINTR) | Some(libc::EAGAIN) | Some(libc::ESTALE) => {
+            return ErrorClass::Transient
+        }
+        _ => {}
+    }
+
+    match e.kind() {
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock => ErrorClass::Transient,
+        _ => ErrorClass::Permanent,
+    }
+}
+
+/// Bounded exponential back-off
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries per file after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles on each further one
+    pub base_delay: Duration,
+    /// Upper bound for a single delay
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_millis(5),
+            max_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based)
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1u32 << retry.min(16))
+            .min(self.max_delay)
+    }
+}
+
+/// Successful batches needed before the in-flight limit grows by one
+const GROW_AFTER: usize = 8;
+
+/// Adaptive limit on batches in flight (AIMD: halve on `EMFILE`, grow by
+/// one after a run of successes)
+#[derive(Debug)]
+pub struct Throttle {
+    max: usize,
+    limit: AtomicUsize,
+    successes: AtomicUsize,
+    active: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A batch slot; released on drop
+pub struct Permit<'a> {
+    throttle: &'a Throttle,
+}
+
+impl Throttle {
+    /// Start at (and never exceed) `max` batches in flight
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            limit: AtomicUsize::new(max),
+            successes: AtomicUsize::new(0),
+            active: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Current in-flight limit
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Wait for a free slot
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut active = self.active.lock().unwrap_or_else(PoisonError::into_inner);
+        while *active >= self.limit() {
+            active = self
+                .released
+                .wait(active)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *active += 1;
+        Permit { throttle: self }
+    }
+
+    /// File descriptors ran out: halve the limit
+    pub fn back_off(&self) {
+        let _ = self
+            .limit
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |limit| {
+                Some((limit / 2).max(1))
+            });
+        self.successes.store(0, Ordering::Relaxed);
+    }
+
+    /// A batch went through without running out of descriptors
+    pub fn record_success(&self) {
+        if self.limit() >= self.max {
+            return;
+        }
+        if self.successes.fetch_add(1, Ordering::Relaxed) + 1 >= GROW_AFTER {
+            self.successes.store(0, Ordering::Relaxed);
+            let _ = self
+                .limit
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |limit| {
+                    Some((limit + 1).min(self.max))
+                });
+            self.released.notify_one();
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut active = self
+            .throttle
+            .active
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *active -= 1;
+        self.throttle.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::path::PathBuf;
+
+    fn copy_failed(e: io::Error) -> DeployError {
+        DeployError::CopyFailed {
+            src: PathBuf::from("/a"),
+            dst: PathBuf::from("/b"),
+            source: e,
+        }
+    }
+
+    // ==================== classify tests ====================
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_classify_raw_errors() {
+        let raw = |code| copy_failed(io::Error::from_raw_os_error(code));
+        assert_eq!(classify(&raw(libc::EMFILE)), ErrorClass::FdExhausted);
+        assert_eq!(classify(&raw(libc::ENFILE)), ErrorClass::FdExhausted);
+        assert_eq!(classify(&raw(libc::EINTR)), ErrorClass::Transient);
+        assert_eq!(classify(&raw(libc::EAGAIN)), ErrorClass::Transient);
+        assert_eq!(classify(&raw(libc::ESTALE)), ErrorClass::Transient);
+        assert_eq!(classify(&raw(libc::ENOENT)), ErrorClass::Permanent);
+    }
+
+    #[test]
+    fn test_classify_without_io_source() {
+        assert_eq!(classify(&DeployError::Cancelled), ErrorClass::Permanent);
+        assert_eq!(
+            classify(&copy_failed(io::Error::from(io::ErrorKind::Interrupted))),
+            ErrorClass::Transient
+        );
+    }
+
+    // ==================== RetryPolicy tests ====================
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_millis(5));
+        assert_eq!(policy.delay(1), Duration::from_millis(10));
+        assert_eq!(policy.delay(3), Duration::from_millis(40));
+        assert_eq!(policy.delay(30), Duration::from_millis(500));
+    }
+
+    // ==================== Throttle tests ====================
+
+    #[test]
+    fn test_throttle_back_off_and_recover() {
+        let throttle = Throttle::new(8);
+        assert_eq!(throttle.limit(), 8);
+
+        throttle.back_off();
+        assert_eq!(throttle.limit(), 4);
+        throttle.back_off();
+        throttle.back_off();
+        throttle.back_off();
+        assert_eq!(throttle.limit(), 1);
+
+        for _ in 0..GROW_AFTER {
+            throttle.record_success();
+        }
+        assert_eq!(throttle.limit(), 2);
+
+        for _ in 0..GROW_AFTER * 20 {
+            throttle.record_success();
+        }
+        assert_eq!(throttle.limit(), 8);
+    }
+
+    #[test]
+    fn test_throttle_permits() {
+        let throttle = Throttle::new(2);
+        let a = throttle.acquire();
+        let _b = throttle.acquire();
+        assert_eq!(*throttle.active.lock().unwrap(), 2);
+        drop(a);
+        assert_eq!(*throttle.active.lock().unwrap(), 1);
+        let _c = throttle.acquire();
+        assert_eq!(*throttle.active.lock().unwrap(), 2);
+    }
+}
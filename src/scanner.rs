@@ -81,6 +81,40 @@ pub enum FileSource {
     },
 }
 
+impl FileSource {
+    /// Directory the files are read from
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::ThemeWeb { path, .. }
+            | Self::Library { path }
+            | Self::VendorModule { path, .. }
+            | Self::ThemeModuleOverride { path, .. } => path,
+        }
+    }
+
+    /// Module subdirectory the files are deployed under (None = output root)
+    pub fn module(&self) -> Option<&str> {
+        match self {
+            Self::VendorModule { module, .. } | Self::ThemeModuleOverride { module, .. } => {
+                Some(module)
+            }
+            Self::ThemeWeb { .. } | Self::Library { .. } => None,
+        }
+    }
+
+    /// Short description of where the files come from, e.g. `theme:Hyva/default`
+    pub fn origin(&self) -> String {
+        match self {
+            Self::ThemeWeb { theme, .. } => format!("theme:{theme}"),
+            Self::Library { .. } => "library".to_string(),
+            Self::VendorModule { module, .. } => format!("module:{module}"),
+            Self::ThemeModuleOverride { theme, module, .. } => {
+                format!("override:{theme}:{module}")
+            }
+        }
+    }
+}
+
 /// Discover all themes in app/design/{area}/ using parallel iteration
 #[must_use = "this returns the discovered themes which should be processed"]
 pub fn discover_themes(magento_root: &Path, area: Area) -> Result<Vec<Theme>, DeployError> {
@@ -276,6 +310,29 @@ pub fn scan_vendor_module_sources(magento_root: &Path, area: Area) -> Vec<FileSo
         .collect()
 }
 
+/// Installed modules as (name, package directory): vendor/{vendor}/{package}
+/// and app/code/{Vendor}/{Module} directories with an etc/module.xml, sorted
+/// by name
+pub fn discover_modules(magento_root: &Path) -> Vec<(String, PathBuf)> {
+    let mut modules: Vec<(String, PathBuf)> = ["vendor", "app/code"]
+        .iter()
+        .flat_map(|base| {
+            WalkDir::new(magento_root.join(base))
+                .min_depth(2)
+                .max_depth(2)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_dir())
+        })
+        .filter_map(|entry| {
+            let name = get_module_name(entry.path())?;
+            Some((name, entry.into_path()))
+        })
+        .collect();
+    modules.sort();
+    modules
+}
+
 /// Scan theme module overrides in app/design/{area}/{Vendor}/{theme}/{Module_Name}/web/
 pub fn scan_theme_module_overrides(theme: &Theme) -> Vec<FileSource> {
     // Pre-allocate for typical theme overrides (5-10 modules)
@@ -349,6 +406,28 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    // ==================== FileSource tests ====================
+
+    #[test]
+    fn test_file_source_path_and_module() {
+        let web = FileSource::ThemeWeb {
+            theme: "Hyva/default".to_string(),
+            path: PathBuf::from("/theme/web"),
+        };
+        assert_eq!(web.path(), Path::new("/theme/web"));
+        assert_eq!(web.module(), None);
+
+        let module = FileSource::ThemeModuleOverride {
+            theme: "Hyva/default".to_string(),
+            module: "Magento_Catalog".to_string(),
+            path: PathBuf::from("/theme/Magento_Catalog/web"),
+        };
+        assert_eq!(module.path(), Path::new("/theme/Magento_Catalog/web"));
+        assert_eq!(module.module(), Some("Magento_Catalog"));
+        assert_eq!(module.origin(), "override:Hyva/default:Magento_Catalog");
+        assert_eq!(web.origin(), "theme:Hyva/default");
+    }
+
     // ==================== parse_module_xml tests ====================
 
     #[test]
@@ -758,6 +837,32 @@ mod tests {
         assert_eq!(sources.len(), 1);
     }
 
+    // ==================== discover_modules tests ====================
+
+    #[test]
+    fn test_discover_modules_vendor_and_app_code() {
+        let temp = TempDir::new().unwrap();
+        for (dir, name) in [
+            ("vendor/magento/module-store/etc", "Magento_Store"),
+            ("vendor/hyva/module-name/src/etc", "Hyva_Module"),
+            ("app/code/Acme/Banner/etc", "Acme_Banner"),
+        ] {
+            let etc = temp.path().join(dir);
+            fs::create_dir_all(&etc).unwrap();
+            fs::write(
+                etc.join("module.xml"),
+                format!(r#"<config><module name="{name}"/></config>"#),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(temp.path().join("vendor/composer/installers")).unwrap();
+
+        let modules = discover_modules(temp.path());
+        let names: Vec<&str> = modules.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Acme_Banner", "Hyva_Module", "Magento_Store"]);
+        assert_eq!(modules[1].1, temp.path().join("vendor/hyva/module-name"));
+    }
+
     // ==================== scan_theme_module_overrides tests ====================
 
     #[test]
@@ -0,0 +1,376 @@
+//! Pre-flight disk space check.
+//!
+//! Before anything is copied, the bytes and inodes a run will need are
+//! estimated from its plans and compared with what `statvfs(3)` reports as
+//! free on the output filesystem, so a deploy that can't fit is refused
+//! instead of failing halfway with `DiskFull`.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::copier::{effective_link_mode, CopyMode, CopyOptions, LinkMode};
+use crate::error::DeployError;
+use crate::manifest::Manifest;
+use crate::plan::DeployPlan;
+
+/// Allocation unit assumed when rounding file sizes up
+const BLOCK_SIZE: u64 = 4096;
+
+/// What a run is expected to allocate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpaceEstimate {
+    /// Data bytes, each file rounded up to whole blocks
+    pub bytes: u64,
+    /// Files, symlinks and directories to create
+    pub inodes: u64,
+}
+
+impl SpaceEstimate {
+    /// Account for one copied file of `size` bytes
+    pub fn add_file(&mut self, size: u64) {
+        self.bytes += size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        self.inodes += 1;
+    }
+
+    pub fn add(&mut self, other: &SpaceEstimate) {
+        self.bytes += other.bytes;
+        self.inodes += other.inodes;
+    }
+
+    /// The same output produced `times` times
+    pub fn times(&self, times: u64) -> SpaceEstimate {
+        SpaceEstimate {
+            bytes: self.bytes * times,
+            inodes: self.inodes * times,
+        }
+    }
+}
+
+/// Estimate what deploying `plan` allocates. `sizes` holds the stat of
+/// each planned source (the next manifest); `copy` limits the estimate to
+/// the entries that will actually be copied. Replaced files are counted as
+/// new ones, every destination directory as created, transformed files and
+/// precompressed sidecars at their source's size, so the estimate errs on
+/// the high side.
+pub fn estimate_plan(
+    plan: &DeployPlan,
+    sizes: &Manifest,
+    copy: Option<&[bool]>,
+    options: &CopyOptions,
+) -> SpaceEstimate {
+    let mut estimate = SpaceEstimate::default();
+    let mut dirs: HashSet<&Path> = HashSet::new();
+    // Hard links can't cross devices: those sources are copied, like the
+    // work queue does
+    let link_modes: Vec<LinkMode> = plan
+        .sources()
+        .iter()
+        .map(|source| effective_link_mode(source.path(), plan.output(), options.link_mode))
+        .collect();
+
+    for (idx, entry) in plan.entries().iter().enumerate() {
+        if copy.is_some_and(|copy| !copy.get(idx).copied().unwrap_or(true)) {
+            continue;
+        }
+        if let Some(parent) = entry.dest.parent() {
+            dirs.insert(parent);
+        }
+
+        let size = sizes.get(&entry.dest).map(|e| e.size).unwrap_or(0);
+        match link_modes[entry.source] {
+            // Written out whatever the link mode
+            _ if entry.transform.is_some() => estimate.add_file(size),
+            // A new name for an existing inode
+            LinkMode::Hardlink => {}
+            LinkMode::Symlink => estimate.inodes += 1,
+            LinkMode::Copy if options.copy_mode == CopyMode::Reflink => estimate.inodes += 1,
+            LinkMode::Copy => estimate.add_file(size),
+        }
+
+        // Sidecars are only kept when smaller than their file
+        if let Some(precompress) = &options.precompress {
+            if precompress.applies(&entry.dest, size) {
+                for _ in &precompress.codecs {
+                    estimate.add_file(size);
+                }
+            }
+        }
+    }
+
+    estimate.inodes += dirs.len() as u64;
+    estimate
+}
+
+/// Free space on a filesystem, as available to unprivileged users
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSpace {
+    pub bytes: u64,
+    /// `None` when the filesystem has no fixed inode count (e.g. btrfs)
+    pub inodes: Option<u64>,
+}
+
+/// Free space on the filesystem holding `path`, or its nearest existing
+/// ancestor (outputs usually don't exist yet)
+#[cfg(target_os = "linux")]
+pub fn free_space(path: &Path) -> io::Result<FreeSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(Path::new("/"));
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: statvfs succeeded and filled the struct
+    let stat = unsafe { stat.assume_init() };
+
+    #[allow(clippy::useless_conversion)] // field widths differ per platform
+    let (bavail, frsize, files, favail) = (
+        u64::from(stat.f_bavail),
+        u64::from(stat.f_frsize),
+        u64::from(stat.f_files),
+        u64::from(stat.f_favail),
+    );
+
+    Ok(FreeSpace {
+        bytes: bavail.saturating_mul(frsize),
+        inodes: (files > 0).then_some(favail),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn free_space(_path: &Path) -> io::Result<FreeSpace> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// A resource the output filesystem doesn't have enough of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortage {
+    pub path: PathBuf,
+    /// "bytes" or "inodes"
+    pub resource: &'static str,
+    pub needed: u64,
+    pub available: u64,
+}
+
+impl Shortage {
+    pub fn to_error(&self) -> DeployError {
+        DeployError::InsufficientSpace {
+            path: self.path.clone(),
+            resource: self.resource,
+            needed: self.needed,
+            available: self.available,
+        }
+    }
+}
+
+/// Compare an estimate with the free space at `path`.
+/// Filesystems that can't be queried are assumed to have room.
+pub fn check(path: &Path, estimate: &SpaceEstimate) -> Option<Shortage> {
+    let free = free_space(path).ok()?;
+    shortage(path, estimate, &free)
+}
+
+fn shortage(path: &Path, estimate: &SpaceEstimate, free: &FreeSpace) -> Option<Shortage> {
+    if estimate.bytes > free.bytes {
+        return Some(Shortage {
+            path: path.to_path_buf(),
+            resource: "bytes",
+            needed: estimate.bytes,
+            available: free.bytes,
+        });
+    }
+
+    match free.inodes {
+        Some(inodes) if estimate.inodes > inodes => Some(Shortage {
+            path: path.to_path_buf(),
+            resource: "inodes",
+            needed: estimate.inodes,
+            available: inodes,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::{Codec, Precompress};
+    use crate::manifest::compare;
+    use crate::minify::Transform;
+    use crate::scanner::FileSource;
+    use std::fs;
+    use tempfile::TempDir;
+
+    // ==================== SpaceEstimate tests ====================
+
+    #[test]
+    fn test_estimate_rounds_to_blocks() {
+        let mut estimate = SpaceEstimate::default();
+        estimate.add_file(0);
+        estimate.add_file(1);
+        estimate.add_file(4096);
+        estimate.add_file(4097);
+
+        assert_eq!(estimate.bytes, 4096 * 4);
+        assert_eq!(estimate.inodes, 4);
+        assert_eq!(
+            estimate.times(3),
+            SpaceEstimate {
+                bytes: 4096 * 12,
+                inodes: 12
+            }
+        );
+    }
+
+    // ==================== estimate_plan tests ====================
+
+    fn web_plan(src: &Path, output: PathBuf) -> DeployPlan {
+        DeployPlan::build(
+            output,
+            vec![FileSource::ThemeWeb {
+                theme: "Test/theme".to_string(),
+                path: src.to_path_buf(),
+            }],
+            true,
+        )
+    }
+
+    fn hardlink_options() -> CopyOptions {
+        CopyOptions {
+            link_mode: LinkMode::Hardlink,
+            ..CopyOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_estimate_plan_hardlink_counts_transforms_and_sidecars() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("app.js"), "x".repeat(5000)).unwrap();
+        fs::write(src.join("logo.png"), "png").unwrap();
+        let mut plan = web_plan(&src, temp.path().join("out"));
+        let sizes = compare(&plan, None).next;
+
+        // Same device: links only allocate the directory
+        let estimate = estimate_plan(&plan, &sizes, None, &hardlink_options());
+        assert_eq!(
+            estimate,
+            SpaceEstimate {
+                bytes: 0,
+                inodes: 1
+            }
+        );
+
+        plan.derive(|entry| {
+            (entry.dest.extension()? == "js")
+                .then(|| (entry.dest.with_extension("min.js"), Transform::MinifyJs))
+        });
+        let sizes = compare(&plan, None).next;
+        let options = CopyOptions {
+            precompress: Some(Precompress {
+                codecs: vec![Codec::Gzip, Codec::Brotli],
+                extensions: vec!["js".to_string()],
+                min_size: 1024,
+            }),
+            ..hardlink_options()
+        };
+        let estimate = estimate_plan(&plan, &sizes, None, &options);
+        // app.min.js written out, plus two sidecars each for app.js and
+        // app.min.js; logo.png is too small and not allow-listed
+        assert_eq!(
+            estimate,
+            SpaceEstimate {
+                bytes: 8192 * 5,
+                inodes: 5 + 1
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_estimate_plan_cross_device_hardlink_copies() {
+        let Ok(shm) = TempDir::new_in("/dev/shm") else {
+            return;
+        };
+        let temp = TempDir::new().unwrap();
+        if effective_link_mode(shm.path(), temp.path(), LinkMode::Hardlink) == LinkMode::Hardlink {
+            // Same filesystem here: nothing to check
+            return;
+        }
+        fs::write(shm.path().join("app.js"), "x".repeat(100)).unwrap();
+        let plan = web_plan(shm.path(), temp.path().join("out"));
+        let sizes = compare(&plan, None).next;
+
+        let estimate = estimate_plan(&plan, &sizes, None, &hardlink_options());
+        assert_eq!(
+            estimate,
+            SpaceEstimate {
+                bytes: 4096,
+                inodes: 2
+            }
+        );
+    }
+
+    // ==================== check tests ====================
+
+    #[test]
+    fn test_shortage_bytes_then_inodes() {
+        let path = Path::new("/pub/static");
+        let estimate = SpaceEstimate {
+            bytes: 1000,
+            inodes: 10,
+        };
+
+        let roomy = FreeSpace {
+            bytes: 5000,
+            inodes: Some(100),
+        };
+        assert_eq!(shortage(path, &estimate, &roomy), None);
+
+        let short_bytes = FreeSpace {
+            bytes: 999,
+            inodes: Some(5),
+        };
+        assert_eq!(
+            shortage(path, &estimate, &short_bytes).unwrap().resource,
+            "bytes"
+        );
+
+        let short_inodes = FreeSpace {
+            bytes: 5000,
+            inodes: Some(5),
+        };
+        let s = shortage(path, &estimate, &short_inodes).unwrap();
+        assert_eq!((s.resource, s.needed, s.available), ("inodes", 10, 5));
+
+        let no_inode_limit = FreeSpace {
+            bytes: 5000,
+            inodes: None,
+        };
+        assert_eq!(shortage(path, &estimate, &no_inode_limit), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_free_space_of_missing_path_uses_ancestor() {
+        let temp = TempDir::new().unwrap();
+        let free = free_space(&temp.path().join("pub/static/frontend")).unwrap();
+        assert!(free.bytes > 0);
+
+        assert!(check(temp.path(), &SpaceEstimate::default()).is_none());
+        let huge = SpaceEstimate {
+            bytes: u64::MAX,
+            inodes: 0,
+        };
+        assert_eq!(check(temp.path(), &huge).unwrap().resource, "bytes");
+    }
+}